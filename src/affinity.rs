@@ -0,0 +1,99 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+/// CPUs worker threads may run on; unset means no restriction. Each restriction
+/// (reserved cores, physical cores only) narrows the set further.
+static ALLOWED_CPUS: Mutex<Option<Vec<usize>>> = Mutex::new(None);
+static REALTIME: AtomicBool = AtomicBool::new(false);
+// So a refused priority request warns once, not once per thread.
+static REALTIME_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Keeps the first `reserved` CPUs free for the OS by restricting every worker
+/// thread to the rest. Returns the reserved CPU ids, or `None` when there would
+/// be no CPU left to mine on.
+pub fn reserve_cores(reserved: usize, total_cpus: usize) -> Option<Vec<usize>> {
+    if reserved >= total_cpus {
+        return None;
+    }
+    restrict_to(&(reserved..total_cpus).collect::<Vec<_>>());
+    Some((0..reserved).collect())
+}
+
+/// Keeps only the given CPUs (e.g. one per physical core) in the allowed set.
+/// Returns how many CPUs worker threads may now run on.
+pub fn restrict_to(cpus: &[usize]) -> usize {
+    let mut allowed = ALLOWED_CPUS.lock().unwrap();
+    let narrowed: Vec<usize> = match allowed.as_ref() {
+        Some(current) => current.iter().copied().filter(|cpu| cpus.contains(cpu)).collect(),
+        None => cpus.to_vec(),
+    };
+    let count = narrowed.len();
+    *allowed = Some(narrowed);
+    count
+}
+
+/// Makes worker threads request SCHED_FIFO when they start. Only meant to be
+/// combined with reserved cores, which keep the OS schedulable.
+pub fn enable_realtime() {
+    REALTIME.store(true, Ordering::SeqCst);
+}
+
+/// Number of CPUs worker threads are allowed on, if restricted.
+pub fn allowed_cpu_count() -> Option<usize> {
+    ALLOWED_CPUS.lock().unwrap().as_ref().map(Vec::len)
+}
+
+/// Restricts the calling thread to the allowed CPUs (the scheduler still moves
+/// it freely within that set) and raises its priority if `--realtime` is on.
+#[cfg(target_os = "linux")]
+pub fn apply_to_current_thread() {
+    if REALTIME.load(Ordering::Relaxed) {
+        raise_priority();
+    }
+    let Some(cpus) = ALLOWED_CPUS.lock().unwrap().clone() else {
+        return;
+    };
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            tracing::warn!("sched_setaffinity failed: {}", std::io::Error::last_os_error());
+        }
+    }
+}
+
+/// SCHED_FIFO at the lowest real-time priority, or failing that (no
+/// CAP_SYS_NICE / RLIMIT_RTPRIO) the strongest nice value permitted.
+#[cfg(target_os = "linux")]
+fn raise_priority() {
+    unsafe {
+        let param = libc::sched_param { sched_priority: 1 };
+        let err = libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param);
+        if err == 0 {
+            return;
+        }
+        // Without a pid, setpriority on Linux applies to the calling thread only.
+        let reniced = libc::setpriority(libc::PRIO_PROCESS, 0, -10) == 0;
+        if !REALTIME_WARNED.swap(true, Ordering::SeqCst) {
+            eprintln!(
+                "⚠️  --realtime: SCHED_FIFO not permitted ({}); {}",
+                std::io::Error::from_raw_os_error(err),
+                if reniced { "running worker threads at nice -10 instead" } else { "running at normal priority" }
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply_to_current_thread() {
+    if ALLOWED_CPUS.lock().unwrap().is_some() {
+        tracing::warn!("Core reservation and --physical-only are only enforced on Linux");
+    }
+    if REALTIME.load(Ordering::Relaxed) && !REALTIME_WARNED.swap(true, Ordering::SeqCst) {
+        eprintln!("⚠️  --realtime is only supported on Linux; running at normal priority");
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{hash_rate::get_hash_rate_tracker, stats};
+use serde_json::{json, Value};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// Starts a thread serving the read-only stats API: `GET /stats` returns JSON,
+/// `GET /config` the effective configuration the miner started with, and `GET /`
+/// a self-contained dashboard that polls the stats.
+pub fn start(addr: SocketAddr, config: Value) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let config = config.to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            // One request per connection, served inline; the page polls every few seconds.
+            if let Err(e) = handle(stream, &config) {
+                tracing::debug!("API request failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, config: &str) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/") => ("200 OK", "text/html; charset=utf-8", DASHBOARD_HTML.to_string()),
+        ("GET", "/stats") => ("200 OK", "application/json", stats_json()),
+        ("GET", "/config") => ("200 OK", "application/json", config.to_string()),
+        ("GET", _) => ("404 Not Found", "text/plain", "not found".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "read-only API".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn stats_json() -> String {
+    let (hash_rate, total_hashes, uptime) = {
+        let tracker = get_hash_rate_tracker().lock().unwrap();
+        (tracker.get_hash_rate(), tracker.get_total_hashes(), tracker.get_elapsed_time())
+    };
+    json!({
+        "hashrate": hash_rate,
+        "total_hashes": total_hashes,
+        "uptime_secs": uptime.as_secs(),
+        "shares": {
+            "accepted": stats::accepted_shares(),
+            "rejected": stats::rejected_shares(),
+            "rejected_by_reason": stats::reject_reasons()
+                .iter()
+                .map(|(reason, count)| (reason.label().replace(' ', "_"), json!(count)))
+                .collect::<serde_json::Map<_, _>>(),
+        },
+        "rebuilding_dataset": stats::is_rebuilding_dataset(),
+        "thread_restarts": stats::thread_restarts(),
+        "difficulty": stats::difficulty_history().last().map(|&(_, difficulty)| difficulty),
+        "share_latency_ms": stats::average_share_latency().map(|latency| latency.as_millis() as u64),
+        "submit_latency_ms": stats::average_submit_latency().map(|latency| latency.as_millis() as u64),
+        "first_share_secs": stats::time_to_first_share().map(|after| after.as_secs()),
+        "pools": stats::pool_stats().iter().map(|pool| json!({
+            "url": pool.url,
+            "connected_secs": pool.connected.as_secs(),
+            "accepted": pool.accepted,
+            "rejected": pool.rejected,
+            "average_hashrate": pool.average_hash_rate(),
+        })).collect::<Vec<_>>(),
+    })
+    .to_string()
+}
@@ -0,0 +1,93 @@
+use randomx_rs::RandomXFlag;
+use std::{
+    fmt,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+/// Argon2 implementation RandomX fills its cache with, for `--argon2`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Argon2Impl {
+    /// Whatever randomx-rs detects.
+    #[default]
+    Auto,
+    Avx2,
+    Ssse3,
+    /// The portable implementation, without SIMD.
+    Ref,
+}
+
+static SELECTED: AtomicU8 = AtomicU8::new(Argon2Impl::Auto as u8);
+
+impl Argon2Impl {
+    /// Whether this CPU can run the implementation; forcing one it lacks would crash.
+    pub fn is_available(self) -> bool {
+        match self {
+            Argon2Impl::Auto | Argon2Impl::Ref => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Argon2Impl::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Argon2Impl::Ssse3 => is_x86_feature_detected!("ssse3"),
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            _ => false,
+        }
+    }
+
+    /// The implementation RandomX picks for `flags`.
+    pub fn of(flags: RandomXFlag) -> Self {
+        if (flags & RandomXFlag::FLAG_ARGON2) == RandomXFlag::FLAG_ARGON2_AVX2 {
+            Argon2Impl::Avx2
+        } else if (flags & RandomXFlag::FLAG_ARGON2) == RandomXFlag::FLAG_ARGON2_SSSE3 {
+            Argon2Impl::Ssse3
+        } else {
+            Argon2Impl::Ref
+        }
+    }
+
+    fn flag(self) -> RandomXFlag {
+        match self {
+            Argon2Impl::Avx2 => RandomXFlag::FLAG_ARGON2_AVX2,
+            Argon2Impl::Ssse3 => RandomXFlag::FLAG_ARGON2_SSSE3,
+            Argon2Impl::Auto | Argon2Impl::Ref => RandomXFlag::FLAG_DEFAULT,
+        }
+    }
+}
+
+impl fmt::Display for Argon2Impl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Argon2Impl::Auto => "auto",
+            Argon2Impl::Avx2 => "avx2",
+            Argon2Impl::Ssse3 => "ssse3",
+            Argon2Impl::Ref => "ref",
+        })
+    }
+}
+
+/// Sets the implementation every later `apply` forces; call once, before the workers start.
+pub fn select(argon2: Argon2Impl) {
+    SELECTED.store(argon2 as u8, Ordering::Relaxed);
+}
+
+fn selected() -> Argon2Impl {
+    match SELECTED.load(Ordering::Relaxed) {
+        x if x == Argon2Impl::Avx2 as u8 => Argon2Impl::Avx2,
+        x if x == Argon2Impl::Ssse3 as u8 => Argon2Impl::Ssse3,
+        x if x == Argon2Impl::Ref as u8 => Argon2Impl::Ref,
+        _ => Argon2Impl::Auto,
+    }
+}
+
+/// `flags` with the Argon2 bits replaced by the selected implementation (left alone on auto).
+pub fn apply(mut flags: RandomXFlag) -> RandomXFlag {
+    let selected = selected();
+    if selected != Argon2Impl::Auto {
+        flags.remove(RandomXFlag::FLAG_ARGON2);
+        flags.insert(selected.flag());
+    }
+    flags
+}
+
+/// The implementation cache builds actually use after `apply`.
+pub fn effective() -> Argon2Impl {
+    Argon2Impl::of(apply(RandomXFlag::get_recommended_flags()))
+}
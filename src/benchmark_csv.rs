@@ -0,0 +1,41 @@
+use crate::hash_rate::get_hash_rate_tracker;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Starts a thread appending an `elapsed_secs,hashrate` row every few seconds.
+/// The rate is computed from all hashes in the interval, so unlike the
+/// reported hash rate it also covers warmup.
+pub fn start(path: &Path, header: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for line in header.lines() {
+        writeln!(out, "# {}", line)?;
+    }
+    writeln!(out, "elapsed_secs,hashrate")?;
+    out.flush()?;
+
+    thread::spawn(move || {
+        let mut last_hashes = get_hash_rate_tracker().lock().unwrap().get_lifetime_hashes();
+        loop {
+            thread::sleep(SAMPLE_INTERVAL);
+            let (hashes, elapsed) = {
+                let tracker = get_hash_rate_tracker().lock().unwrap();
+                (tracker.get_lifetime_hashes(), tracker.get_elapsed_time())
+            };
+            let rate = hashes.saturating_sub(last_hashes) as f64 / SAMPLE_INTERVAL.as_secs_f64();
+            last_hashes = hashes;
+            // Flushed per row so an interrupted run still leaves a usable file.
+            if let Err(e) = writeln!(out, "{:.1},{:.2}", elapsed.as_secs_f64(), rate).and_then(|_| out.flush()) {
+                eprintln!("ERROR: Failed to write benchmark CSV: {}", e);
+                return;
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use chrono::{Local, NaiveTime};
+use std::time::Instant;
+
+/// Where time-dependent logic gets "now" from, so it can run on something other
+/// than the system clock.
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    /// Local wall-clock time of day, for schedules tied to the user's hours.
+    fn time_of_day(&self) -> NaiveTime;
+}
+
+/// The monotonic system clock. Zero-sized, so code generic over `Clock` costs
+/// nothing over calling `Instant::now` directly.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline(always)]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn time_of_day(&self) -> NaiveTime {
+        Local::now().time()
+    }
+}
+
+#[cfg(test)]
+pub use mock::MockClock;
+
+#[cfg(test)]
+mod mock {
+    use super::Clock;
+    use chrono::NaiveTime;
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    /// A clock that only moves when told to, for testing time-dependent logic
+    /// without sleeping. Clones share the same time, so a test keeps one to
+    /// advance the clock it handed to the code under test.
+    #[derive(Debug, Clone)]
+    pub struct MockClock(Arc<Mutex<(Instant, NaiveTime)>>);
+
+    impl MockClock {
+        /// Starts at midnight.
+        pub fn new() -> Self {
+            Self::at(NaiveTime::MIN)
+        }
+
+        /// Starts at `time_of_day`.
+        pub fn at(time_of_day: NaiveTime) -> Self {
+            // Far enough past process start that code subtracting its windows
+            // from "now" can't underflow.
+            Self(Arc::new(Mutex::new((Instant::now() + Duration::from_secs(24 * 60 * 60), time_of_day))))
+        }
+
+        pub fn advance(&self, by: Duration) {
+            let mut time = self.0.lock().unwrap();
+            time.0 += by;
+            // Wraps past midnight like a wall clock.
+            time.1 += chrono::Duration::from_std(by).unwrap();
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.0.lock().unwrap().0
+        }
+
+        fn time_of_day(&self) -> NaiveTime {
+            self.0.lock().unwrap().1
+        }
+    }
+}
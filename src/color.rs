@@ -0,0 +1,124 @@
+use owo_colors::{OwoColorize, Style};
+use std::{
+    fmt,
+    io::IsTerminal,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// When console output is colored, for `--color`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorWhen {
+    /// Color only when stdout is a terminal and `NO_COLOR` is unset.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Decides once, at startup, whether `Colorize` output carries escape codes.
+pub fn init(when: ColorWhen) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    ENABLED.store(should_color(when, std::io::stdout().is_terminal(), no_color), Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// `no_color`: whether `NO_COLOR` is set to a non-empty value.
+fn should_color(when: ColorWhen, is_terminal: bool, no_color: bool) -> bool {
+    match when {
+        ColorWhen::Always => true,
+        ColorWhen::Never => false,
+        ColorWhen::Auto => is_terminal && !no_color,
+    }
+}
+
+/// A value that is printed with `style` only when color is enabled.
+pub struct Styled<'a, T: ?Sized> {
+    value: &'a T,
+    style: Style,
+}
+
+impl<T: fmt::Display + ?Sized> fmt::Display for Styled<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if enabled() {
+            fmt::Display::fmt(&self.value.style(self.style), f)
+        } else {
+            fmt::Display::fmt(self.value, f)
+        }
+    }
+}
+
+/// Drop-in for the `OwoColorize` methods the console output uses, routed
+/// through the `--color` decision.
+pub trait Colorize: fmt::Display {
+    fn paint(&self, style: Style) -> Styled<'_, Self> {
+        Styled { value: self, style }
+    }
+
+    fn black(&self) -> Styled<'_, Self> {
+        self.paint(Style::new().black())
+    }
+
+    fn red(&self) -> Styled<'_, Self> {
+        self.paint(Style::new().red())
+    }
+
+    fn green(&self) -> Styled<'_, Self> {
+        self.paint(Style::new().green())
+    }
+
+    fn yellow(&self) -> Styled<'_, Self> {
+        self.paint(Style::new().yellow())
+    }
+
+    fn blue(&self) -> Styled<'_, Self> {
+        self.paint(Style::new().blue())
+    }
+
+    fn purple(&self) -> Styled<'_, Self> {
+        self.paint(Style::new().purple())
+    }
+
+    fn cyan(&self) -> Styled<'_, Self> {
+        self.paint(Style::new().cyan())
+    }
+
+    fn bold(&self) -> Styled<'_, Self> {
+        self.paint(Style::new().bold())
+    }
+
+    fn dimmed(&self) -> Styled<'_, Self> {
+        self.paint(Style::new().dimmed())
+    }
+
+    fn underline(&self) -> Styled<'_, Self> {
+        self.paint(Style::new().underline())
+    }
+}
+
+impl<T: fmt::Display + ?Sized> Colorize for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_colors_only_a_terminal_without_no_color() {
+        assert!(should_color(ColorWhen::Auto, true, false));
+        assert!(!should_color(ColorWhen::Auto, false, false));
+        assert!(!should_color(ColorWhen::Auto, true, true));
+        assert!(!should_color(ColorWhen::Auto, false, true));
+    }
+
+    #[test]
+    fn always_and_never_ignore_the_environment() {
+        for (is_terminal, no_color) in [(true, false), (false, false), (true, true), (false, true)] {
+            assert!(should_color(ColorWhen::Always, is_terminal, no_color));
+            assert!(!should_color(ColorWhen::Never, is_terminal, no_color));
+        }
+    }
+}
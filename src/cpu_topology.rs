@@ -0,0 +1,184 @@
+/// Per-thread RandomX scratchpad; each hashing thread wants this much L3 to itself.
+const SCRATCHPAD_BYTES: u64 = 2 * 1024 * 1024;
+
+/// A group of logical CPUs sharing one L3 cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L3Domain {
+    pub size_bytes: u64,
+    pub cpus: usize,
+}
+
+/// Threads that fit the detected L3 caches: per domain, one per scratchpad that fits,
+/// but never more than the domain's CPUs. `None` when topology is unavailable.
+pub fn cache_fit_threads(domains: &[L3Domain]) -> Option<usize> {
+    if domains.is_empty() {
+        return None;
+    }
+    let threads = domains
+        .iter()
+        .map(|d| ((d.size_bytes / SCRATCHPAD_BYTES) as usize).min(d.cpus))
+        .sum::<usize>();
+    Some(threads.max(1))
+}
+
+#[cfg(target_os = "linux")]
+pub fn l3_domains() -> Vec<L3Domain> {
+    use std::{collections::BTreeMap, fs};
+
+    // Keyed by shared_cpu_list so each L3 is counted once, not once per CPU sharing it.
+    let mut domains: BTreeMap<String, L3Domain> = BTreeMap::new();
+    let Ok(entries) = fs::read_dir("/sys/devices/system/cpu") else {
+        return Vec::new();
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.strip_prefix("cpu").is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())) {
+            continue;
+        }
+        let Ok(caches) = fs::read_dir(entry.path().join("cache")) else {
+            continue;
+        };
+        for cache in caches.flatten() {
+            let path = cache.path();
+            let read = |file: &str| fs::read_to_string(path.join(file)).map(|s| s.trim().to_string());
+            if read("level").ok().as_deref() != Some("3") {
+                continue;
+            }
+            let (Ok(size), Ok(shared)) = (read("size"), read("shared_cpu_list")) else {
+                continue;
+            };
+            let (Some(size_bytes), Some(cpus)) = (parse_cache_size(&size), count_cpu_list(&shared)) else {
+                continue;
+            };
+            domains.entry(shared).or_insert(L3Domain { size_bytes, cpus });
+        }
+    }
+    domains.into_values().collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn l3_domains() -> Vec<L3Domain> {
+    Vec::new()
+}
+
+/// One logical CPU (the lowest-numbered SMT sibling) per physical core, from
+/// each CPU's `topology/thread_siblings_list`. `None` when topology is unavailable.
+#[cfg(target_os = "linux")]
+pub fn physical_cores() -> Option<Vec<usize>> {
+    use std::{collections::BTreeSet, fs};
+
+    let mut cores = BTreeSet::new();
+    for entry in fs::read_dir("/sys/devices/system/cpu").ok()?.flatten() {
+        let name = entry.file_name();
+        let Some(cpu) = name.to_str().and_then(|n| n.strip_prefix("cpu")).and_then(|n| n.parse::<usize>().ok()) else {
+            continue;
+        };
+        // Offline CPUs have no topology directory.
+        let Ok(siblings) = fs::read_to_string(entry.path().join("topology/thread_siblings_list")) else {
+            continue;
+        };
+        cores.insert(parse_cpu_list(siblings.trim())?.into_iter().min().unwrap_or(cpu));
+    }
+    (!cores.is_empty()).then(|| cores.into_iter().collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn physical_cores() -> Option<Vec<usize>> {
+    None
+}
+
+/// CPUs the cgroup quota allows, rounded up: cgroup v2 `cpu.max`, else the v1
+/// CFS quota/period. `None` when there is no quota.
+#[cfg(target_os = "linux")]
+pub fn cgroup_cpu_limit() -> Option<usize> {
+    use std::fs;
+
+    // Our own cgroup first (host view), then the root of the mount (container view).
+    let own_v2 = fs::read_to_string("/proc/self/cgroup").ok().and_then(|groups| {
+        groups
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .map(|path| format!("/sys/fs/cgroup{}/cpu.max", path.trim_end_matches('/')))
+    });
+    for path in own_v2.iter().map(String::as_str).chain(["/sys/fs/cgroup/cpu.max"]) {
+        if let Ok(max) = fs::read_to_string(path) {
+            let mut fields = max.split_whitespace();
+            let (Some(quota), Some(period)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            // "max" means unlimited.
+            return quota_cpus(quota.parse().ok()?, period.parse().ok()?);
+        }
+    }
+
+    let read = |file: &str| fs::read_to_string(format!("/sys/fs/cgroup/cpu/{}", file)).ok()?.trim().parse::<i64>().ok();
+    let quota = read("cpu.cfs_quota_us")?;
+    if quota <= 0 {
+        return None;
+    }
+    quota_cpus(quota as u64, read("cpu.cfs_period_us")? as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn cgroup_cpu_limit() -> Option<usize> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn quota_cpus(quota: u64, period: u64) -> Option<usize> {
+    if period == 0 {
+        return None;
+    }
+    Some((quota.div_ceil(period) as usize).max(1))
+}
+
+/// Online CPUs regardless of any cgroup quota, which `available_parallelism` already applies.
+#[cfg(target_os = "linux")]
+pub fn online_cpus() -> Option<usize> {
+    let n = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+    (n > 0).then_some(n as usize)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn online_cpus() -> Option<usize> {
+    None
+}
+
+/// Parses sysfs cache sizes such as "32768K" or "32M".
+fn parse_cache_size(size: &str) -> Option<u64> {
+    let (digits, multiplier) = match size.chars().last()? {
+        'K' => (&size[..size.len() - 1], 1024),
+        'M' => (&size[..size.len() - 1], 1024 * 1024),
+        'G' => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    digits.parse::<u64>().ok().map(|n| n * multiplier)
+}
+
+/// Expands a sysfs cpu list such as "0-7,16-23" into CPU ids.
+#[cfg(target_os = "linux")]
+fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.split(',').filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => cpus.extend(start.parse::<usize>().ok()?..=end.parse::<usize>().ok()?),
+            None => cpus.push(part.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+/// Counts the CPUs in a sysfs cpu list such as "0-7,16-23".
+fn count_cpu_list(list: &str) -> Option<usize> {
+    list.split(',').filter(|part| !part.is_empty()).try_fold(0, |count, part| {
+        let n = match part.split_once('-') {
+            Some((start, end)) => end.parse::<usize>().ok()?.checked_sub(start.parse::<usize>().ok()?)? + 1,
+            None => {
+                part.parse::<usize>().ok()?;
+                1
+            }
+        };
+        Some(count + n)
+    })
+}
@@ -0,0 +1,215 @@
+//! `--dataset-cache-dir`: each built RandomX dataset is saved under its seed hash
+//! and flags, so a restart on the same seed reads it back instead of spending a
+//! minute or more rebuilding it.
+
+use crate::color::Colorize;
+use once_cell::sync::OnceCell;
+use randomx_rs::RandomXFlag;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Identifies a dataset file and the version of its header.
+const MAGIC: &[u8; 8] = b"ANMRXDS1";
+const EXTENSION: &str = "rxds";
+/// Datasets kept in the directory; older ones are deleted after each save. Two
+/// cover a restart right after a seed change at about 2 GiB each.
+const MAX_FILES: usize = 2;
+
+static DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Turns the cache on; call once at startup.
+pub fn set_dir(dir: PathBuf) {
+    let _ = DIR.set(dir);
+}
+
+pub fn dir() -> Option<&'static Path> {
+    DIR.get().map(PathBuf::as_path)
+}
+
+/// The flags a saved dataset is keyed by. Large pages only change how it was
+/// allocated, and may differ between runs after a fallback.
+fn key_flags(flags: RandomXFlag) -> u32 {
+    (flags - RandomXFlag::FLAG_LARGE_PAGES).bits()
+}
+
+fn file_path(dir: &Path, seed: &[u8], flags: RandomXFlag) -> PathBuf {
+    dir.join(format!("{}-{:08x}.{}", hex::encode(seed), key_flags(flags), EXTENSION))
+}
+
+/// Magic, seed, flags and dataset length, so a file is only ever loaded into a
+/// dataset it was saved from.
+fn header(seed: &[u8], flags: RandomXFlag, len: usize) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&(seed.len() as u32).to_le_bytes());
+    header.extend_from_slice(seed);
+    header.extend_from_slice(&key_flags(flags).to_le_bytes());
+    header.extend_from_slice(&(len as u64).to_le_bytes());
+    header
+}
+
+/// Fills `dataset` from the file saved for `seed` and `flags`. `Ok(None)` if there
+/// is none; an error if it exists but doesn't match. The caller still has to check
+/// the contents hash correctly before using them.
+pub fn load(dir: &Path, seed: &[u8], flags: RandomXFlag, dataset: &mut [u8]) -> io::Result<Option<PathBuf>> {
+    let path = file_path(dir, seed, flags);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let expected = header(seed, flags, dataset.len());
+    if file.metadata()?.len() != (expected.len() + dataset.len()) as u64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has the wrong size", path.display())));
+    }
+    let mut reader = BufReader::new(file);
+    let mut found = vec![0u8; expected.len()];
+    reader.read_exact(&mut found)?;
+    if found != expected {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} was saved for another seed or flags", path.display())));
+    }
+    reader.read_exact(dataset)?;
+    Ok(Some(path))
+}
+
+/// Writes `dataset` for `seed` and `flags`, then trims the directory to the
+/// newest `MAX_FILES` datasets. Written under a temporary name and renamed, so
+/// a crash mid-save never leaves a truncated file to load.
+pub fn save(dir: &Path, seed: &[u8], flags: RandomXFlag, dataset: &[u8]) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = file_path(dir, seed, flags);
+    let partial = path.with_extension("partial");
+    let mut writer = BufWriter::new(File::create(&partial)?);
+    writer.write_all(&header(seed, flags, dataset.len()))?;
+    writer.write_all(dataset)?;
+    writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+    fs::rename(&partial, &path)?;
+    prune(dir, MAX_FILES)?;
+    Ok(path)
+}
+
+/// Deletes a file that failed to load or verify, so it isn't tried again.
+pub fn discard(dir: &Path, seed: &[u8], flags: RandomXFlag) {
+    let _ = fs::remove_file(file_path(dir, seed, flags));
+}
+
+/// Deletes all but the `keep` most recently written datasets.
+fn prune(dir: &Path, keep: usize) -> io::Result<()> {
+    let mut files: Vec<_> = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension().is_some_and(|extension| extension == EXTENSION))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    for (_, path) in files.into_iter().skip(keep) {
+        fs::remove_file(&path)?;
+        tracing::info!(path = %path.display(), "Deleted an old cached RandomX dataset");
+    }
+    Ok(())
+}
+
+pub fn loaded(path: &Path) {
+    tracing::info!(path = %path.display(), "Loaded the RandomX dataset from disk");
+    println!("{} Loaded the RandomX dataset from {}", "💾".cyan(), path.display());
+}
+
+pub fn rejected(reason: &str) {
+    tracing::warn!(reason, "Cached RandomX dataset not used; rebuilding it");
+    eprintln!("{} Cached RandomX dataset not used ({}); rebuilding it", "⚠️".yellow(), reason);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    const SEED: &[u8] = &[0xab; 32];
+
+    fn flags() -> RandomXFlag {
+        RandomXFlag::FLAG_FULL_MEM | RandomXFlag::FLAG_JIT
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("anonminer-dataset-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn dataset(fill: u8) -> Vec<u8> {
+        (0..4096).map(|i| (i as u8).wrapping_add(fill)).collect()
+    }
+
+    #[test]
+    fn saved_dataset_loads_back() {
+        let dir = temp_dir("roundtrip");
+        save(&dir, SEED, flags(), &dataset(1)).unwrap();
+        let mut loaded = vec![0; 4096];
+        assert!(load(&dir, SEED, flags(), &mut loaded).unwrap().is_some());
+        assert_eq!(loaded, dataset(1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn large_pages_dont_change_the_key() {
+        let dir = temp_dir("large-pages");
+        save(&dir, SEED, flags() | RandomXFlag::FLAG_LARGE_PAGES, &dataset(1)).unwrap();
+        let mut loaded = vec![0; 4096];
+        assert!(load(&dir, SEED, flags(), &mut loaded).unwrap().is_some());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn other_seeds_and_flags_find_nothing() {
+        let dir = temp_dir("keys");
+        save(&dir, SEED, flags(), &dataset(1)).unwrap();
+        let mut loaded = vec![0; 4096];
+        assert!(load(&dir, &[0xcd; 32], flags(), &mut loaded).unwrap().is_none());
+        assert!(load(&dir, SEED, flags() | RandomXFlag::FLAG_SECURE, &mut loaded).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mismatched_files_are_refused() {
+        let dir = temp_dir("invalid");
+        let path = save(&dir, SEED, flags(), &dataset(1)).unwrap();
+
+        // A dataset of another size.
+        let mut loaded = vec![0; 2048];
+        assert_eq!(load(&dir, SEED, flags(), &mut loaded).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // Truncated, as by a full disk.
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let mut loaded = vec![0; 4096];
+        assert_eq!(load(&dir, SEED, flags(), &mut loaded).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // Right size, someone else's header.
+        let mut corrupted = bytes.clone();
+        corrupted[0] ^= 0xff;
+        fs::write(&path, &corrupted).unwrap();
+        assert_eq!(load(&dir, SEED, flags(), &mut loaded).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        discard(&dir, SEED, flags());
+        assert!(load(&dir, SEED, flags(), &mut loaded).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_the_newest_datasets_are_kept() {
+        let dir = temp_dir("prune");
+        for seed in 0..4u8 {
+            save(&dir, &[seed; 32], flags(), &dataset(seed)).unwrap();
+            // Distinct modification times, even on coarse filesystems.
+            thread::sleep(Duration::from_millis(20));
+        }
+        let mut loaded = vec![0; 4096];
+        assert!(load(&dir, &[0; 32], flags(), &mut loaded).unwrap().is_none());
+        assert!(load(&dir, &[1; 32], flags(), &mut loaded).unwrap().is_none());
+        assert!(load(&dir, &[2; 32], flags(), &mut loaded).unwrap().is_some());
+        assert!(load(&dir, &[3; 32], flags(), &mut loaded).unwrap().is_some());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), MAX_FILES);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+use randomx_rs::RandomXFlag;
+use serde_json::{json, Value};
+use sysinfo::{CpuRefreshKind, MemoryRefreshKind, RefreshKind, System};
+
+const RANDOMX_FLAG_NAMES: [(&str, RandomXFlag); 7] = [
+    ("large_pages", RandomXFlag::FLAG_LARGE_PAGES),
+    ("hard_aes", RandomXFlag::FLAG_HARD_AES),
+    ("full_mem", RandomXFlag::FLAG_FULL_MEM),
+    ("jit", RandomXFlag::FLAG_JIT),
+    ("secure", RandomXFlag::FLAG_SECURE),
+    ("argon2_ssse3", RandomXFlag::FLAG_ARGON2_SSSE3),
+    ("argon2_avx2", RandomXFlag::FLAG_ARGON2_AVX2),
+];
+
+pub fn flag_names(flags: RandomXFlag) -> Vec<&'static str> {
+    RANDOMX_FLAG_NAMES
+        .iter()
+        .filter(|(_, flag)| flags.contains(*flag))
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Everything that usually explains a low hash rate, gathered read-only: no
+/// sudo, no huge page or MSR writes.
+pub fn collect() -> Value {
+    let sys = System::new_with_specifics(
+        RefreshKind::nothing()
+            .with_cpu(CpuRefreshKind::everything())
+            .with_memory(MemoryRefreshKind::everything()),
+    );
+    let (brand, vendor) = sys
+        .cpus()
+        .first()
+        .map(|cpu| (cpu.brand().trim().to_string(), cpu.vendor_id().to_string()))
+        .unwrap_or_default();
+
+    let randomx_flags = flag_names(RandomXFlag::get_recommended_flags());
+
+    json!({
+        "cpu": {
+            "brand": brand,
+            "vendor": vendor,
+            "arch": System::cpu_arch(),
+            "features": cpu_features(),
+            "physical_cores": System::physical_core_count(),
+            "logical_cpus": sys.cpus().len(),
+            "available_parallelism": std::thread::available_parallelism().map(|n| n.get()).ok(),
+        },
+        "memory": {
+            "total_bytes": sys.total_memory(),
+            "available_bytes": sys.available_memory(),
+        },
+        "huge_pages": huge_pages(),
+        "msr": msr(),
+        "randomx": {
+            "recommended_flags": randomx_flags,
+        },
+        "os": {
+            "name": System::name(),
+            "version": System::long_os_version(),
+            "kernel": System::kernel_version(),
+        },
+    })
+}
+
+/// CPU features RandomX can use, with the names `cpu_features` reports them by.
+pub const RANDOMX_CPU_FEATURES: [(&str, &str); 4] = [
+    ("aes", "AES-NI (hardware AES)"),
+    ("ssse3", "SSSE3 (Argon2 cache init)"),
+    ("sse4.1", "SSE4.1"),
+    ("avx2", "AVX2 (Argon2 cache init)"),
+];
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn cpu_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if is_x86_feature_detected!("aes") {
+        features.push("aes");
+    }
+    if is_x86_feature_detected!("ssse3") {
+        features.push("ssse3");
+    }
+    if is_x86_feature_detected!("sse4.1") {
+        features.push("sse4.1");
+    }
+    if is_x86_feature_detected!("avx") {
+        features.push("avx");
+    }
+    if is_x86_feature_detected!("avx2") {
+        features.push("avx2");
+    }
+    if is_x86_feature_detected!("avx512f") {
+        features.push("avx512f");
+    }
+    features
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub fn cpu_features() -> Vec<&'static str> {
+    Vec::new()
+}
+
+#[cfg(target_os = "linux")]
+fn huge_pages() -> Value {
+    use crate::worker::{read_meminfo_value, read_nr_hugepages};
+    json!({
+        "nr_hugepages": read_nr_hugepages(),
+        "total": read_meminfo_value("HugePages_Total"),
+        "free": read_meminfo_value("HugePages_Free"),
+        "page_size_kb": read_meminfo_value("Hugepagesize"),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn huge_pages() -> Value {
+    Value::Null
+}
+
+/// Huge pages as (total, free, page size in kB), when the OS reports them.
+#[cfg(target_os = "linux")]
+pub fn huge_page_counts() -> Option<(u64, u64, u64)> {
+    use crate::worker::read_meminfo_value;
+    Some((
+        read_meminfo_value("HugePages_Total")?,
+        read_meminfo_value("HugePages_Free")?,
+        read_meminfo_value("Hugepagesize")?,
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn huge_page_counts() -> Option<(u64, u64, u64)> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn msr() -> Value {
+    json!({
+        "module_loaded": std::path::Path::new("/sys/module/msr").exists(),
+        "device_present": std::path::Path::new("/dev/cpu/0/msr").exists(),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn msr() -> Value {
+    Value::Null
+}
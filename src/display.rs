@@ -1,9 +1,87 @@
-use owo_colors::OwoColorize;
-use std::time::Duration;
+use crate::{argon2::Argon2Impl, cpu_topology::L3Domain, hash_analysis::HashLogSummary, hashrate_alert::HashRateAlert, stats::PoolStats, stratum::RejectReason, worker::{OnDisconnect, ThreadMode}};
+use randomx_rs::RandomXFlag;
+use crate::color::Colorize;
+use std::{
+    io::IsTerminal,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Unit every displayed hash rate uses, for `--hashrate-unit`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HashRateUnit {
+    /// Scale to H/s, KH/s, MH/s or GH/s by magnitude.
+    #[default]
+    Auto,
+    H,
+    Kh,
+    Mh,
+}
+
+/// How much startup decoration to print, for `--banner`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BannerStyle {
+    /// `full` when stdout is a terminal, `compact` otherwise (logs, CI, systemd).
+    #[default]
+    Auto,
+    /// The boxed banner and the multi-line startup summary.
+    Full,
+    /// One line with the version, thread count and mode.
+    Compact,
+    None,
+}
+
+static HASH_RATE_UNIT: AtomicU8 = AtomicU8::new(HashRateUnit::Auto as u8);
+static HASH_RATE_PRECISION: AtomicU8 = AtomicU8::new(2);
+static HASH_RATE_RAW: AtomicBool = AtomicBool::new(false);
+static BELL_ON_SHARE: AtomicBool = AtomicBool::new(false);
+
+impl HashRateUnit {
+    fn current() -> Self {
+        match HASH_RATE_UNIT.load(Ordering::Relaxed) {
+            x if x == HashRateUnit::H as u8 => HashRateUnit::H,
+            x if x == HashRateUnit::Kh as u8 => HashRateUnit::Kh,
+            x if x == HashRateUnit::Mh as u8 => HashRateUnit::Mh,
+            _ => HashRateUnit::Auto,
+        }
+    }
+
+    /// Divisor and status-line suffix for `rate` (`""` for plain H/s).
+    fn scale(self, rate: f64) -> (f64, &'static str) {
+        match self {
+            HashRateUnit::H => (1.0, ""),
+            HashRateUnit::Kh => (1_000.0, "K"),
+            HashRateUnit::Mh => (1_000_000.0, "M"),
+            HashRateUnit::Auto if rate >= 1_000_000_000.0 => (1_000_000_000.0, "G"),
+            HashRateUnit::Auto if rate >= 1_000_000.0 => (1_000_000.0, "M"),
+            HashRateUnit::Auto if rate >= 1_000.0 => (1_000.0, "K"),
+            HashRateUnit::Auto => (1.0, ""),
+        }
+    }
+}
 
 pub struct Display;
 
 impl Display {
+    /// Prints the banner and startup summary in `style`.
+    pub fn startup(style: BannerStyle, threads: usize, mode: &str) {
+        let style = match style {
+            BannerStyle::Auto if std::io::stdout().is_terminal() => BannerStyle::Full,
+            BannerStyle::Auto => BannerStyle::Compact,
+            style => style,
+        };
+        match style {
+            BannerStyle::Full => {
+                Self::banner();
+                Self::startup_info(threads, mode);
+            }
+            BannerStyle::Compact => {
+                println!("{} AnonMiner v0.1.2 starting: {} threads, {} mode", "▶".green(), threads.to_string().yellow(), mode.yellow());
+            }
+            BannerStyle::Auto | BannerStyle::None => {}
+        }
+    }
+
     pub fn banner() {
         println!();
         println!("{}", "╔═══════════════════════════════════════════════════════════════╗".cyan());
@@ -21,13 +99,16 @@ impl Display {
         println!();
     }
 
-    pub fn hash_rate_report(hash_rate: f64, elapsed: Duration) {
+    pub fn hash_rate_report(hash_rate: f64, elapsed: Duration, details: &[(&str, String)]) {
         let formatted_rate = Self::format_hash_rate(hash_rate);
         
         println!("{}", "┌─ Mining Stats ────────────────────────────────────────────────┐".blue());
         println!("{} {}", "│".blue(), "Current Performance".bold().underline());
         println!("{} Hash Rate: {}", "│".blue(), formatted_rate.green().bold());
         println!("{} Runtime: {}", "│".blue(), Self::format_duration(elapsed).cyan());
+        for (label, value) in details {
+            println!("{} {}: {}", "│".blue(), label, value.cyan());
+        }
         println!("{}", "└───────────────────────────────────────────────────────────────┘".blue());
         println!();
     }
@@ -36,11 +117,254 @@ impl Display {
         println!("{} {}", "✓".green(), format!("Job ID {} submitted. Valid share number {}!", job_id, share_count).green().bold());
     }
 
+    pub fn share_accepted(job_id: &str, accepted_count: u64) {
+        println!("{} {}", "✓".green(), format!("Share for job {} accepted by pool ({} accepted).", job_id, accepted_count).green());
+    }
+
+    pub fn first_share_accepted(after: Duration) {
+        println!("{}", Self::format_first_share(after).green().bold());
+    }
+
+    /// e.g. "First share accepted after 02:13 ✓"
+    pub fn format_first_share(after: Duration) -> String {
+        format!("First share accepted after {} ✓", Self::format_duration(after))
+    }
+
+    /// e.g. "⛔ Pool temporarily banned us (...), waiting 300s before reconnecting"
+    pub fn format_pool_ban(error: &dyn std::fmt::Display, cooldown: Duration) -> String {
+        format!(
+            "{} Pool temporarily banned us ({}), waiting {}s before reconnecting",
+            "⛔".red(),
+            error,
+            cooldown.as_secs()
+        )
+    }
+
+    pub const REBUILDING_DATASET: &'static str = "Rebuilding dataset for new epoch…";
+
+    pub const SHUTDOWN_REQUESTED: &'static str = "🛑 Stop requested, shutting down…";
+
+    pub fn shutdown_summary(elapsed: Duration, total_hashes: u64, accepted: u64, rejected: u64) {
+        println!(
+            "{} Mined for {}: {} hashes, {} accepted / {} rejected shares",
+            "👋".cyan(),
+            Self::format_duration(elapsed),
+            total_hashes,
+            accepted.to_string().green(),
+            rejected.to_string().red()
+        );
+    }
+
+    /// e.g. "3 low difficulty, 1 stale"; most frequent first.
+    pub fn format_reject_reasons(reasons: &[(RejectReason, u64)]) -> String {
+        let mut reasons = reasons.to_vec();
+        reasons.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        reasons.iter().map(|(reason, count)| format!("{} {}", count, reason.label())).collect::<Vec<_>>().join(", ")
+    }
+
+    /// The per-reason breakdown under the shutdown summary, if anything was rejected.
+    pub fn reject_breakdown(reasons: &[(RejectReason, u64)]) {
+        if !reasons.is_empty() {
+            println!("   Rejected: {}", Self::format_reject_reasons(reasons));
+        }
+    }
+
+    pub fn format_dataset_rebuild(rebuilding: bool) -> String {
+        if rebuilding {
+            format!("{} {} (hashing resumes when it is done)", "🔄".yellow(), Self::REBUILDING_DATASET.yellow())
+        } else {
+            format!("{} Dataset rebuilt, hashing resumed", "✅".green())
+        }
+    }
+
+    pub fn format_reject_ratio(ratio: f64, max: f64, pool_url: &str) -> String {
+        format!(
+            "{} {}",
+            "🚨".red(),
+            format!(
+                "{:.0}% of recent shares were rejected by {} (limit {:.0}%); the pool or its configuration looks broken",
+                ratio * 100.0,
+                pool_url,
+                max * 100.0
+            )
+            .red()
+            .bold()
+        )
+    }
+
+    pub fn format_connection_lost(on_disconnect: OnDisconnect) -> String {
+        let behaviour = match on_disconnect {
+            OnDisconnect::KeepHashing => "workers keep hashing the last job",
+            OnDisconnect::Pause => "workers paused until a new job arrives",
+        };
+        format!("{} Connection lost. Attempting to reconnect... ({})", "⚠️".red(), behaviour)
+    }
+
+    pub fn share_rejected(job_id: &str, reason: &str) {
+        println!("{} {}", "✗".red(), format!("Share for job {} rejected by pool: {}", job_id, reason).red());
+    }
+
+    pub fn new_block(height: u64) {
+        println!("{} {}", "⛓".cyan(), format!("New block at height {}", height).cyan());
+    }
+
     pub fn job_received(job_id: &str) {
         let job_int = u64::from_str_radix(job_id, 16).unwrap_or(0);
         println!("{} {}", "↻".blue(), format!("New job received: {} (0x{})...", job_int, job_id).blue());
     }
 
+    pub fn cache_topology(domains: &[L3Domain], threads: usize) {
+        let caches = domains
+            .iter()
+            .map(|d| format!("{} MB/{} CPUs", d.size_bytes / (1024 * 1024), d.cpus))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("{} L3 cache: {} → {} light-mode threads", "ℹ️".cyan(), caches, threads.to_string().yellow());
+    }
+
+    pub fn reserved_cores(cores: &[usize], threads: usize) {
+        let cores = cores.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ");
+        println!("{} Reserved core(s) {} for the OS → {} mining threads", "ℹ️".cyan(), cores, threads.to_string().yellow());
+    }
+
+    pub fn physical_cores(physical: usize, logical: usize, threads: usize) {
+        println!(
+            "{} {} physical cores, {} logical CPUs → {} mining threads, SMT siblings left idle",
+            "ℹ️".cyan(),
+            physical,
+            logical,
+            threads.to_string().yellow()
+        );
+    }
+
+    pub fn thread_modes(modes: &[ThreadMode]) {
+        println!("{} {}", "🧵".cyan(), Self::format_thread_modes(modes));
+        let degraded = Self::cache_only_threads(modes);
+        if degraded > 0 {
+            println!("{} {} thread(s) could not allocate a dataset and are hashing cache-only at reduced speed", "⚠️".yellow(), degraded);
+        }
+        println!();
+    }
+
+    pub fn dataset_build_time(build_time: Duration) {
+        println!("{} {}", "⏱".cyan(), Self::format_dataset_build_time(build_time));
+        println!();
+    }
+
+    /// Names the Argon2 implementation so build times can be compared across `--argon2` runs.
+    pub fn format_dataset_build_time(build_time: Duration) -> String {
+        format!("Dataset built in {:.1}s (argon2 {})", build_time.as_secs_f64(), crate::argon2::effective())
+    }
+
+    pub fn argon2(used: Argon2Impl, forced: bool) {
+        println!(
+            "{} Argon2 cache init: {} ({})",
+            "ℹ️".cyan(),
+            used.to_string().yellow(),
+            if forced { "forced by --argon2" } else { "auto-detected" }
+        );
+    }
+
+    /// Threads running without the full-memory dataset.
+    pub fn cache_only_threads(modes: &[ThreadMode]) -> usize {
+        modes
+            .iter()
+            .filter(|mode| matches!(mode, ThreadMode::Running(flags) if !flags.contains(RandomXFlag::FLAG_FULL_MEM)))
+            .count()
+    }
+
+    /// e.g. "8 threads: 6 full+large-pages, 2 full+no-large-pages"
+    pub fn format_thread_modes(modes: &[ThreadMode]) -> String {
+        let mut groups: Vec<(String, usize)> = Vec::new();
+        for mode in modes {
+            let label = match mode {
+                ThreadMode::Starting => "starting".to_string(),
+                ThreadMode::Paused => "paused".to_string(),
+                ThreadMode::Failed => "failed".to_string(),
+                ThreadMode::Stopped => "stopped".to_string(),
+                ThreadMode::Running(flags) => format!(
+                    "{}+{}",
+                    if flags.contains(RandomXFlag::FLAG_FULL_MEM) { "full" } else { "light" },
+                    if flags.contains(RandomXFlag::FLAG_LARGE_PAGES) { "large-pages" } else { "no-large-pages" },
+                ),
+            };
+            match groups.iter_mut().find(|(l, _)| *l == label) {
+                Some((_, count)) => *count += 1,
+                None => groups.push((label, 1)),
+            }
+        }
+        let groups = groups
+            .iter()
+            .map(|(label, count)| format!("{} {}", count, label))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{} threads: {}", modes.len(), groups)
+    }
+
+    /// Under a uniform nonce search each ratio bucket should hold about twice
+    /// the one before it, until the ratio approaches u64::MAX / threshold.
+    pub fn hash_log_summary(summary: &HashLogSummary) {
+        println!("{} {}", "📊".cyan(), "Hash log analysis".bold());
+        println!("  {} Hashes: {} ({} malformed lines skipped)", "├".black(), summary.hashes.to_string().yellow(), summary.malformed_lines);
+        match summary.min_hash {
+            Some(min) => println!("  {} Min hash: {} ({:.3e} of the hash space)", "├".black(), min.to_string().yellow(), min as f64 / u64::MAX as f64),
+            None => println!("  {} Min hash: -", "├".black()),
+        }
+        println!("  {} Shares meeting target: {} (expected {:.2})", "├".black(), summary.shares.to_string().green(), summary.expected_shares);
+        println!("  {} Hash / threshold:", "└".black());
+        let largest = summary.ratio_histogram.iter().copied().max().unwrap_or(0).max(1);
+        for (bucket, &count) in summary.ratio_histogram.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let label = match bucket {
+                0 => "< 1x (share)".to_string(),
+                b if b == summary.ratio_histogram.len() - 1 => format!(">= 2^{}x", b - 1),
+                b => format!("2^{}x-2^{}x", b - 1, b),
+            };
+            let bar = "█".repeat((count * 40 / largest) as usize);
+            println!("      {:>14} {:>10} {}", label, count, bar.blue());
+        }
+        println!();
+    }
+
+    pub fn cpu_flags(features: &[&str], huge_pages: Option<(u64, u64, u64)>, recommended: &[&str]) {
+        println!("{} {}", "🔍".cyan(), "CPU support for RandomX".bold());
+        for (name, label) in crate::diagnostics::RANDOMX_CPU_FEATURES {
+            if features.contains(&name) {
+                println!("  {} {}", "✓".green(), label);
+            } else {
+                println!("  {} {}", "✗".red(), label.dimmed());
+            }
+        }
+        match huge_pages {
+            Some((total, free, size_kb)) if total > 0 => {
+                println!("  {} Huge pages: {} of {} free ({} kB each)", "✓".green(), free, total, size_kb)
+            }
+            Some(_) => println!("  {} Huge pages: none allocated (run with sudo or set vm.nr_hugepages)", "✗".red()),
+            None => println!("  {} Huge pages: not reported by this OS", "?".yellow()),
+        }
+        println!("  {} Recommended RandomX flags: {}", "└".black(), recommended.join(", ").yellow());
+    }
+
+    pub fn traffic_capture(path: &std::path::Path, redacted: bool) {
+        println!(
+            "{} Capturing pool traffic to {}{}",
+            "📼".cyan(),
+            path.display().to_string().yellow(),
+            if redacted { " (wallets, login ids, password and rig id redacted)" } else { " (unredacted: do not share publicly)" }
+        );
+    }
+
+    pub fn self_test_start() {
+        println!("{} {}", "🧪".cyan(), "Running RandomX self-test...".cyan());
+    }
+
+    pub fn self_test_passed() {
+        println!("{} {}", "✅".green(), "RandomX self-test passed.".green());
+        println!();
+    }
+
     pub fn connection_info(pool: &str, wallet: &str) {
         let short_wallet = if wallet.len() > 12 { &wallet[..12] } else { wallet };
         println!("{} {}", "🔗".cyan(), "Connection Details".bold());
@@ -49,15 +373,82 @@ impl Display {
         println!();
     }
 
-    fn format_hash_rate(rate: f64) -> String {
-        if rate >= 1_000_000_000.0 {
-            format!("{:.2} GH/s", rate / 1_000_000_000.0)
-        } else if rate >= 1_000_000.0 {
-            format!("{:.2} MH/s", rate / 1_000_000.0)
-        } else if rate >= 1_000.0 {
-            format!("{:.2} KH/s", rate / 1_000.0)
+    /// `--show-wallet-qr`: the wallet address as a QR code to scan with a wallet
+    /// app, followed by the full address. Any `.worker` or `+difficulty` suffix
+    /// the pool takes in the user name is left out. With color off the code is
+    /// drawn light-on-dark, as most terminals show it; with color, black on white.
+    pub fn wallet_qr(wallet: &str) {
+        let address = wallet.split(['.', '+']).next().unwrap_or(wallet);
+        let code = match qrcode::QrCode::new(address) {
+            Ok(code) => code,
+            Err(e) => {
+                println!("{} Cannot render the wallet as a QR code: {}", "⚠️".yellow(), e);
+                return;
+            }
+        };
+        // Four modules of quiet zone on each side; one column per module.
+        let needed = code.width() + 8;
+        let columns = std::io::stdout().is_terminal().then(crossterm::terminal::size).and_then(Result::ok).map(|(columns, _)| columns as usize);
+        println!("{} {}", "📱".cyan(), "Wallet Address".bold());
+        if columns.is_some_and(|columns| columns < needed) {
+            println!("  Terminal too narrow for the QR code ({} columns needed)", needed);
         } else {
-            format!("{:.2} H/s", rate)
+            let (dark, light) = if crate::color::enabled() {
+                (qrcode::render::unicode::Dense1x2::Dark, qrcode::render::unicode::Dense1x2::Light)
+            } else {
+                (qrcode::render::unicode::Dense1x2::Light, qrcode::render::unicode::Dense1x2::Dark)
+            };
+            let image = code.render::<qrcode::render::unicode::Dense1x2>().dark_color(dark).light_color(light).build();
+            for line in image.lines() {
+                println!("{}", line.paint(owo_colors::Style::new().black().on_white()));
+            }
+        }
+        println!("  {}", address.yellow());
+        println!();
+    }
+
+    pub fn pool_endpoint(peer: &std::net::SocketAddr) {
+        println!("{} Connected to {} (plain TCP, no TLS)", "🌐".cyan(), peer.to_string().yellow());
+        println!();
+    }
+
+    /// Sets the unit for every later formatted hash rate; call once at startup.
+    pub fn set_hash_rate_unit(unit: HashRateUnit) {
+        HASH_RATE_UNIT.store(unit as u8, Ordering::Relaxed);
+    }
+
+    /// Sets the decimal places of every later formatted hash rate and whether a scaled
+    /// rate is followed by the exact H/s value; call once at startup.
+    pub fn set_hash_rate_precision(decimals: u8, raw: bool) {
+        HASH_RATE_PRECISION.store(decimals, Ordering::Relaxed);
+        HASH_RATE_RAW.store(raw, Ordering::Relaxed);
+    }
+
+    /// Turns on `--bell-on-share`; call once at startup.
+    pub fn set_bell_on_share(enabled: bool) {
+        BELL_ON_SHARE.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn bell_on_share() -> bool {
+        BELL_ON_SHARE.load(Ordering::Relaxed)
+    }
+
+    /// Rings the terminal bell for an accepted share when `--bell-on-share` is on,
+    /// except during warmup.
+    pub fn share_bell(warmed_up: bool) {
+        if warmed_up && Self::bell_on_share() {
+            crate::output::bell();
+        }
+    }
+
+    pub fn format_hash_rate(rate: f64) -> String {
+        let (divisor, prefix) = HashRateUnit::current().scale(rate);
+        let decimals = HASH_RATE_PRECISION.load(Ordering::Relaxed) as usize;
+        let scaled = format!("{:.*} {}H/s", decimals, rate / divisor, prefix);
+        if HASH_RATE_RAW.load(Ordering::Relaxed) && !prefix.is_empty() {
+            format!("{} ({:.*} H/s)", scaled, decimals, rate)
+        } else {
+            scaled
         }
     }
 
@@ -74,14 +465,117 @@ impl Display {
         }
     }
 
-    pub fn format_hash_rate_report(hash_rate: f64, elapsed: Duration) -> String {
+    pub fn format_luck(accepted: u64, expected: f64) -> String {
+        if expected > 0.0 {
+            format!("found {}, expected {:.2} (luck {:.0}%)", accepted, expected, accepted as f64 / expected * 100.0)
+        } else {
+            format!("found {}, expected 0.00", accepted)
+        }
+    }
+
+    /// e.g. "184 ms (found → accepted, rolling average)"
+    pub fn format_share_latency(average: Duration) -> String {
+        format!("{} ms (found → accepted, rolling average)", average.as_millis())
+    }
+
+    /// e.g. "3 ms (found → sent, rolling average)"
+    pub fn format_submit_latency(average: Duration) -> String {
+        format!("{} ms (found → sent, rolling average)", average.as_millis())
+    }
+
+    /// e.g. "120000 (40000 → 80000 → 120000, changed 03:12 ago)"; the history shows
+    /// at most the last five values.
+    pub fn format_difficulty(history: &[(Instant, u64)]) -> String {
+        let Some(&(changed_at, current)) = history.last() else {
+            return "unknown".into();
+        };
+        if history.len() == 1 {
+            return current.to_string();
+        }
+        let recent: Vec<String> = history.iter().rev().take(5).rev().map(|(_, d)| d.to_string()).collect();
+        format!("{} ({}, changed {} ago)", current, recent.join(" → "), Self::format_duration(changed_at.elapsed()))
+    }
+
+    /// e.g. "pool.example:3333 01:02:03 12/0 6.20 KH/s | donate.example:443 00:01:00 0/0 6.10 KH/s"
+    pub fn format_pool_stats(pools: &[PoolStats]) -> String {
+        pools
+            .iter()
+            .map(|pool| {
+                format!(
+                    "{} {} {}/{} {}",
+                    pool.url,
+                    Self::format_duration(pool.connected),
+                    pool.accepted,
+                    pool.rejected,
+                    Self::format_hash_rate(pool.average_hash_rate())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    pub fn format_hash_rate_alert(alert: &HashRateAlert) -> String {
+        match *alert {
+            HashRateAlert::Dropped { short, long } => format!(
+                "{} Hash rate dropped to {} (15 min average {}, {:.0}% lower): check for thermal throttling or a dead thread",
+                "🚨".red(),
+                Self::format_hash_rate(short),
+                Self::format_hash_rate(long),
+                (1.0 - short / long) * 100.0
+            ),
+            HashRateAlert::Recovered { short, long } => format!(
+                "{} Hash rate recovered to {} (15 min average {})",
+                "✅".green(),
+                Self::format_hash_rate(short),
+                Self::format_hash_rate(long)
+            ),
+        }
+    }
+
+    pub fn format_throttle(sleep: Duration, target_percent: u8) -> String {
+        format!("{:.2} ms/batch (target {}% CPU)", sleep.as_secs_f64() * 1000.0, target_percent)
+    }
+
+    /// Rewrites the current terminal line; plain text so it can be embedded in tmux/status bars.
+    pub fn status_line(hash_rate: Option<f64>, accepted: u64, elapsed: Duration, pool_url: &str) {
+        crate::output::status_line(&Self::format_status_line(hash_rate, accepted, elapsed, pool_url));
+    }
+
+    /// e.g. "H/s 6.2K | acc 14 | up 01:23:45 | pool herominers"
+    pub fn format_status_line(hash_rate: Option<f64>, accepted: u64, elapsed: Duration, pool_url: &str) -> String {
+        let rate = match hash_rate.map(|rate| (rate, HashRateUnit::current().scale(rate))) {
+            Some((rate, (_, ""))) => format!("{:.0}", rate),
+            Some((rate, (divisor, suffix))) => format!("{:.1}{}", rate / divisor, suffix),
+            None => "warmup".to_string(),
+        };
+        format!("H/s {} | acc {} | up {} | pool {}", rate, accepted, Self::format_duration(elapsed), Self::pool_name(pool_url))
+    }
+
+    /// Short name for a pool URL: the domain label before the TLD ("xmr.herominers.com:1111" -> "herominers").
+    fn pool_name(url: &str) -> &str {
+        let host = url.split("://").last().unwrap_or(url);
+        let host = host.split(['/', ':']).next().unwrap_or(host);
+        let labels: Vec<&str> = host.split('.').collect();
+        if labels.len() >= 2 && labels.iter().any(|l| l.parse::<u8>().is_err()) {
+            labels[labels.len() - 2]
+        } else {
+            host
+        }
+    }
+
+    pub fn format_hash_rate_report(hash_rate: f64, elapsed: Duration, details: &[(&str, String)]) -> String {
         let formatted_rate = Self::format_hash_rate(hash_rate);
         let formatted_duration = Self::format_duration(elapsed);
-        format!(
-            "┌─ Mining Stats ────────────────────────────────────────────────┐\n│ {}\n│ Hash Rate: {}\n│ Runtime: {}\n└───────────────────────────────────────────────────────────────┘",
+        let mut report = format!(
+            "┌─ Mining Stats ────────────────────────────────────────────────┐\n│ {}\n│ Hash Rate: {}\n│ Runtime: {}\n",
             "Current Performance".bold().underline(),
             formatted_rate.green().bold(),
             formatted_duration.cyan()
-        )
+        );
+        for (label, value) in details {
+            report.push_str(&format!("│ {}: {}\n", label, value.cyan()));
+        }
+        report.push_str("└───────────────────────────────────────────────────────────────┘");
+        report
     }
 }
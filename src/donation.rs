@@ -0,0 +1,295 @@
+use crate::{
+    clock::{Clock, SystemClock},
+    suspend,
+};
+use chrono::NaiveTime;
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Donation is owed at `level`% of mining time, paid in one block per cycle.
+const CYCLE_DURATION: Duration = Duration::from_secs(100 * 60);
+/// The commitment starts this far ahead so the first block falls mid-cycle
+/// (after 50 minutes) and later ones every `CYCLE_DURATION` after that.
+const DONATION_START_OFFSET: Duration = Duration::from_secs(50 * 60);
+
+/// Local time-of-day range donations are kept out of; may wrap past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AvoidWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl AvoidWindow {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl fmt::Display for AvoidWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
+
+/// Parses `HH:MM-HH:MM` for `--donate-window-avoid`.
+pub fn parse_avoid_window(s: &str) -> Result<AvoidWindow, String> {
+    let (start, end) = s.split_once('-').ok_or("expected HH:MM-HH:MM")?;
+    let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").map_err(|e| format!("invalid time '{}': {}", t, e));
+    let window = AvoidWindow { start: parse(start)?, end: parse(end)? };
+    if window.start == window.end {
+        return Err("the avoided window must be shorter than a full day".into());
+    }
+    Ok(window)
+}
+
+/// Tracks donated time against the time owed, so donation can be deferred out of
+/// an avoided window and caught up afterwards without lowering the total.
+#[derive(Debug)]
+pub struct DonationSchedule<C: Clock = SystemClock> {
+    clock: C,
+    level: u8,
+    avoid: Option<AvoidWindow>,
+    started: Instant,
+    donated: Duration,
+    donating_since: Option<Instant>,
+    // `suspend::suspended_total` when the schedule started and when the current
+    // block did: frozen time neither accrues debt nor pays it off.
+    suspended_at_start: Duration,
+    suspended_at_donation: Duration,
+}
+
+impl DonationSchedule {
+    pub fn new(level: u8, avoid: Option<AvoidWindow>) -> Self {
+        Self::with_clock(level, avoid, SystemClock)
+    }
+}
+
+impl<C: Clock> DonationSchedule<C> {
+    pub fn with_clock(level: u8, avoid: Option<AvoidWindow>, clock: C) -> Self {
+        Self {
+            started: clock.now(),
+            clock,
+            level,
+            avoid,
+            donated: Duration::ZERO,
+            donating_since: None,
+            suspended_at_start: suspend::suspended_total(),
+            suspended_at_donation: Duration::ZERO,
+        }
+    }
+
+    fn owed(&self) -> Duration {
+        let running = self.elapsed_since(self.started).saturating_sub(suspend::suspended_total() - self.suspended_at_start);
+        (running + DONATION_START_OFFSET) * self.level as u32 / 100
+    }
+
+    /// Time in the current donation block, less any spent suspended.
+    fn current_block(&self) -> Duration {
+        self.donating_since.map_or(Duration::ZERO, |since| {
+            self.elapsed_since(since).saturating_sub(suspend::suspended_total() - self.suspended_at_donation)
+        })
+    }
+
+    fn elapsed_since(&self, since: Instant) -> Duration {
+        self.clock.now().saturating_duration_since(since)
+    }
+
+    fn donated(&self) -> Duration {
+        self.donated + self.current_block()
+    }
+
+    fn in_avoided_window(&self) -> bool {
+        self.avoid.is_some_and(|window| window.contains(self.clock.time_of_day()))
+    }
+
+    /// Whether the miner should be on the donation pool right now. A block starts
+    /// once a full cycle's share is owed and runs until the debt is paid; the
+    /// avoided window pauses both, leaving the debt to be paid after it.
+    pub fn should_donate(&self) -> bool {
+        if self.level == 0 || self.in_avoided_window() {
+            return false;
+        }
+        let owed = self.owed();
+        let donated = self.donated();
+        if self.donating_since.is_some() {
+            donated < owed
+        } else {
+            owed.saturating_sub(donated) >= CYCLE_DURATION * self.level as u32 / 100
+        }
+    }
+
+    /// Call once the donation pool is the active connection.
+    pub fn start_donating(&mut self) {
+        if self.donating_since.is_none() {
+            self.donating_since = Some(self.clock.now());
+            self.suspended_at_donation = suspend::suspended_total();
+        }
+    }
+
+    /// Call once the user's pool is the active connection again.
+    pub fn stop_donating(&mut self) {
+        self.donated += self.current_block();
+        self.donating_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn first_block_comes_after_half_a_cycle() {
+        let clock = MockClock::new();
+        let schedule = DonationSchedule::with_clock(1, None, clock.clone());
+        assert!(!schedule.should_donate());
+        clock.advance(50 * MINUTE - Duration::from_secs(1));
+        assert!(!schedule.should_donate());
+        clock.advance(Duration::from_secs(1));
+        assert!(schedule.should_donate());
+    }
+
+    #[test]
+    fn block_runs_until_the_debt_is_paid_then_waits_a_cycle() {
+        let clock = MockClock::new();
+        let mut schedule = DonationSchedule::with_clock(1, None, clock.clone());
+        clock.advance(50 * MINUTE);
+        schedule.start_donating();
+        // 1% of 100 minutes, plus 1% of the block itself.
+        clock.advance(Duration::from_secs(60));
+        assert!(schedule.should_donate());
+        clock.advance(Duration::from_secs(1));
+        assert!(!schedule.should_donate());
+        schedule.stop_donating();
+
+        // The next block is due once another cycle's share is owed, a little over
+        // 100 minutes on.
+        clock.advance(100 * MINUTE);
+        assert!(!schedule.should_donate());
+        clock.advance(MINUTE);
+        assert!(schedule.should_donate());
+    }
+
+    #[test]
+    fn higher_levels_donate_longer_blocks() {
+        let clock = MockClock::new();
+        let mut schedule = DonationSchedule::with_clock(5, None, clock.clone());
+        clock.advance(50 * MINUTE);
+        assert!(schedule.should_donate());
+        schedule.start_donating();
+        clock.advance(5 * MINUTE);
+        assert!(schedule.should_donate());
+        clock.advance(MINUTE);
+        assert!(!schedule.should_donate());
+    }
+
+    #[test]
+    fn level_zero_never_donates() {
+        let clock = MockClock::new();
+        let schedule = DonationSchedule::with_clock(0, None, clock.clone());
+        clock.advance(24 * 60 * MINUTE);
+        assert!(!schedule.should_donate());
+    }
+
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn avoid_window_parses() {
+        let window = parse_avoid_window("22:00-06:00").unwrap();
+        assert_eq!(window, AvoidWindow { start: time("22:00"), end: time("06:00") });
+        assert_eq!(window.to_string(), "22:00-06:00");
+        assert_eq!(parse_avoid_window(" 09:00 - 17:30 ").unwrap().to_string(), "09:00-17:30");
+    }
+
+    #[test]
+    fn malformed_avoid_windows_are_refused() {
+        assert!(parse_avoid_window("22:00").is_err());
+        assert!(parse_avoid_window("25:00-06:00").is_err());
+        assert!(parse_avoid_window("22:00-6pm").is_err());
+        assert!(parse_avoid_window("10:00-10:00").is_err());
+    }
+
+    #[test]
+    fn avoid_window_contains_its_start_but_not_its_end() {
+        let window = parse_avoid_window("09:00-17:00").unwrap();
+        assert!(!window.contains(time("08:59")));
+        assert!(window.contains(time("09:00")));
+        assert!(window.contains(time("16:59")));
+        assert!(!window.contains(time("17:00")));
+    }
+
+    #[test]
+    fn avoid_window_can_wrap_past_midnight() {
+        let window = parse_avoid_window("22:00-06:00").unwrap();
+        assert!(window.contains(time("22:00")));
+        assert!(window.contains(time("23:59")));
+        assert!(window.contains(time("00:00")));
+        assert!(window.contains(time("05:59")));
+        assert!(!window.contains(time("06:00")));
+        assert!(!window.contains(time("12:00")));
+    }
+
+    #[test]
+    fn donation_due_in_the_window_is_deferred_and_caught_up() {
+        let clock = MockClock::new();
+        let mut schedule = DonationSchedule::with_clock(1, Some(parse_avoid_window("00:30-02:00").unwrap()), clock.clone());
+        // The first block falls due at 00:50, inside the window.
+        clock.advance(50 * MINUTE);
+        assert!(!schedule.should_donate());
+        clock.advance(69 * MINUTE);
+        assert!(!schedule.should_donate());
+
+        // At 02:00 the whole debt is paid in one block: 1% of 170 minutes plus 1%
+        // of the block itself, about 103 seconds instead of the usual 61.
+        clock.advance(MINUTE);
+        assert!(schedule.should_donate());
+        schedule.start_donating();
+        clock.advance(Duration::from_secs(103));
+        assert!(schedule.should_donate());
+        clock.advance(Duration::from_secs(1));
+        assert!(!schedule.should_donate());
+    }
+
+    #[test]
+    fn deferral_works_across_midnight() {
+        let clock = MockClock::at(time("23:00"));
+        let mut schedule = DonationSchedule::with_clock(1, Some(parse_avoid_window("23:30-00:30").unwrap()), clock.clone());
+        // Due at 23:50, held until 00:30.
+        clock.advance(50 * MINUTE);
+        assert!(!schedule.should_donate());
+        clock.advance(39 * MINUTE);
+        assert!(!schedule.should_donate());
+        clock.advance(MINUTE);
+        assert!(schedule.should_donate());
+
+        // 1% of 140 minutes plus 1% of the block: about 85 seconds.
+        schedule.start_donating();
+        clock.advance(Duration::from_secs(84));
+        assert!(schedule.should_donate());
+        clock.advance(Duration::from_secs(1));
+        assert!(!schedule.should_donate());
+    }
+
+    #[test]
+    fn window_starting_mid_block_pauses_it() {
+        let clock = MockClock::at(time("11:00"));
+        let mut schedule = DonationSchedule::with_clock(1, Some(parse_avoid_window("11:51-12:00").unwrap()), clock.clone());
+        clock.advance(50 * MINUTE);
+        assert!(schedule.should_donate());
+        schedule.start_donating();
+        clock.advance(Duration::from_secs(30));
+        assert!(schedule.should_donate());
+        clock.advance(Duration::from_secs(30));
+        assert!(!schedule.should_donate());
+    }
+}
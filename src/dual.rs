@@ -0,0 +1,221 @@
+use crate::{
+    color::Colorize,
+    display::Display,
+    donation::DonationSchedule,
+    job::Job,
+    shutdown, stats,
+    stratum::{connect::ConnectOptions, ShareOutcome, Stratum},
+    worker::Worker,
+};
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+/// How long the workers stay on one pool's job before moving to the other's.
+const SLICE_DURATION: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(60);
+const REPORT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Where and how often the donation share of mining time goes; dual mode pays
+/// it in blocks on a third connection, the same schedule single-pool mode uses.
+pub struct Donation<'a> {
+    pub schedule: DonationSchedule,
+    pub url: &'a str,
+    pub wallet: &'a str,
+    pub pass: &'a str,
+}
+
+struct Pool {
+    stratum: Stratum,
+    /// Latest job from this pool, tagged with its index.
+    job: Option<Job>,
+    /// Set while disconnected: when to try reconnecting next.
+    retry_at: Option<Instant>,
+}
+
+impl Pool {
+    fn new(stratum: Stratum) -> Self {
+        Self { stratum, job: None, retry_at: None }
+    }
+}
+
+/// Mines for two pools at once: both connections stay live, the workers hash each
+/// pool's latest job in turn for `SLICE_DURATION`, and every share is submitted to
+/// the pool whose job it was found for. Each pool therefore sees about half the
+/// hash rate, which is the point when comparing pools head to head.
+pub fn run(
+    urls: [&str; 2],
+    user: &str,
+    pass: &str,
+    options: &ConnectOptions,
+    mut donation: Donation,
+    start_worker: impl FnOnce(Job) -> Worker,
+) -> io::Result<()> {
+    let mut pools = Vec::new();
+    for url in urls {
+        pools.push(Pool::new(Stratum::login(url, user, pass, options)?));
+    }
+    println!("{} Dual-pool mining on {} and {}", "⛏".cyan(), urls[0].yellow(), urls[1].yellow());
+
+    let initial_job = loop {
+        if shutdown::requested() {
+            return Ok(());
+        }
+        if let Ok(mut job) = pools[0].stratum.try_recv_job() {
+            job.pool = 0;
+            pools[0].job = Some(job.clone());
+            break job;
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    let worker = start_worker(initial_job);
+    shutdown::install_sigterm_handler();
+
+    let mut current = 0;
+    let mut slice_started = Instant::now();
+    let mut keep_alive_timer = Instant::now();
+    let mut report_timer = Instant::now();
+    let mut share_count = 0;
+    loop {
+        if shutdown::requested() {
+            println!("{}", Display::SHUTDOWN_REQUESTED);
+            break;
+        }
+        let donating = pools.len() > urls.len();
+        stats::set_active_pool(pools[current].retry_at.is_none().then(|| pools[current].stratum.url()));
+
+        for (index, pool) in pools.iter_mut().enumerate() {
+            if pool.stratum.try_reconnect_signal().is_ok() {
+                eprintln!("{} Lost connection to {}; the other pool keeps mining", "⚠️".yellow(), pool.stratum.url());
+                pool.job = None;
+                pool.retry_at = Some(Instant::now());
+            }
+            if pool.retry_at.is_some_and(|at| at <= Instant::now()) {
+                match pool.stratum.reconnect() {
+                    Ok(()) => {
+                        println!("{} Reconnected to {}", "✅".green(), pool.stratum.url());
+                        pool.retry_at = None;
+                    }
+                    Err(e) => {
+                        eprintln!("{} Reconnecting to {} failed: {}", "❌".red(), pool.stratum.url(), e);
+                        pool.retry_at = Some(Instant::now() + RECONNECT_DELAY);
+                    }
+                }
+            }
+            if let Ok(mut job) = pool.stratum.try_recv_job() {
+                job.pool = index;
+                if index == current {
+                    Display::job_received(&job.id);
+                    worker.work(job.clone());
+                }
+                pool.job = Some(job);
+            }
+            while let Ok(result) = pool.stratum.try_recv_share_result() {
+                stats::record_pool_share_result(pool.stratum.url(), &result.outcome);
+                match &result.outcome {
+                    ShareOutcome::Accepted => {
+                        stats::record_share_latency(result.latency);
+                        crate::share_logger::log_share(&result.share, "accepted", None);
+                        println!(
+                            "{} {}",
+                            "✓".green(),
+                            format!("Share for job {} accepted by {}.", result.share.job_id, pool.stratum.url()).green()
+                        );
+                        Display::share_bell(worker.is_warmed_up());
+                    }
+                    ShareOutcome::Rejected(reason) => {
+                        crate::share_logger::log_share(&result.share, "rejected", Some(reason));
+                        Display::share_rejected(&result.share.job_id, reason);
+                    }
+                }
+            }
+        }
+
+        // Donation blocks take the workers off both pools; the pools stay connected.
+        if donation.schedule.should_donate() && !donating {
+            match Stratum::login(donation.url, donation.wallet, donation.pass, options) {
+                Ok(stratum) => {
+                    println!("{} Switching to donation pool...", "🎁".purple());
+                    pools.push(Pool::new(stratum));
+                    donation.schedule.start_donating();
+                }
+                Err(e) => eprintln!("Failed to connect to donation pool: {}", e),
+            }
+        } else if !donation.schedule.should_donate() && donating {
+            println!("{} Donation block finished; back to the dual pools", "🏡".blue());
+            pools.truncate(urls.len());
+            donation.schedule.stop_donating();
+            current = (0..urls.len()).find(|&index| pools[index].job.is_some()).unwrap_or(0);
+            if let Some(job) = pools[current].job.clone() {
+                worker.work(job);
+            }
+            slice_started = Instant::now();
+        }
+
+        // The other pool's turn comes when the slice is up, or early if this one has no job.
+        let next = if pools.len() > urls.len() {
+            urls.len()
+        } else if slice_started.elapsed() >= SLICE_DURATION || pools[current].job.is_none() {
+            (current + 1) % urls.len()
+        } else {
+            current
+        };
+        if next != current {
+            if let Some(job) = pools[next].job.clone() {
+                println!("{} Mining for {}", "⇄".cyan(), pools[next].stratum.url().yellow());
+                worker.work(job);
+                current = next;
+                slice_started = Instant::now();
+            }
+        }
+
+        while let Ok(share) = worker.try_recv_share() {
+            // A share for a donation job found just after the block ended has nowhere to go.
+            let Some(pool) = pools.get_mut(share.pool) else {
+                crate::share_logger::log_share(&share, "not_submitted", Some("connection closed"));
+                continue;
+            };
+            match pool.stratum.submit(&share) {
+                Ok(()) => {
+                    share_count += 1;
+                    crate::share_logger::log_share(&share, "submitted", None);
+                    Display::share_found(&share.job_id, share_count);
+                }
+                Err(e) => {
+                    crate::share_logger::log_share(&share, "not_submitted", Some(&e.to_string()));
+                    eprintln!("{} Share for job {} not submitted to {}: {}", "⚠️".yellow(), share.job_id, pool.stratum.url(), e);
+                }
+            }
+        }
+
+        if keep_alive_timer.elapsed() >= KEEP_ALIVE_INTERVAL {
+            keep_alive_timer = Instant::now();
+            for pool in pools.iter_mut().filter(|pool| pool.retry_at.is_none()) {
+                if let Err(e) = pool.stratum.keep_alive() {
+                    eprintln!("Keep alive to {} failed: {}", pool.stratum.url(), e);
+                }
+            }
+        }
+
+        if report_timer.elapsed() >= REPORT_INTERVAL {
+            report_timer = Instant::now();
+            if worker.is_warmed_up() {
+                let details = vec![
+                    ("Shares", Display::format_luck(stats::accepted_shares(), worker.get_expected_shares())),
+                    ("Threads", Display::format_thread_modes(&worker.get_thread_modes())),
+                    ("Pools", Display::format_pool_stats(&stats::pool_stats())),
+                ];
+                Display::hash_rate_report(worker.get_hash_rate(), worker.get_elapsed_time(), &details);
+            }
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    Display::shutdown_summary(worker.get_elapsed_time(), worker.get_total_hashes(), stats::accepted_shares(), stats::rejected_shares());
+    Display::reject_breakdown(&stats::reject_reasons());
+    worker.shutdown();
+    Ok(())
+}
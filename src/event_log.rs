@@ -0,0 +1,36 @@
+use serde_json::{json, Value};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+// The `--events-file`, once opened.
+static FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Opens `path` for appending events, one JSON object per line. A path like
+/// `/dev/fd/3` writes to a descriptor the supervising process passed in.
+pub fn init(path: &Path) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *FILE.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Writes `{"event", "timestamp", "details"}` as one line and flushes it, so a
+/// reader tailing the file sees each event as it happens. Does nothing without
+/// `--events-file`; a failed write is logged, never fatal.
+pub fn emit(event: &str, details: &Value) {
+    let mut file = FILE.lock().unwrap();
+    let Some(file) = file.as_mut() else {
+        return;
+    };
+    let line = json!({
+        "event": event,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "details": details,
+    });
+    if let Err(e) = writeln!(file, "{}", line).and_then(|()| file.flush()) {
+        tracing::warn!("Writing to the events file failed: {}", e);
+    }
+}
@@ -0,0 +1,39 @@
+use crate::stratum::{LoginRefused, PoolUnreachable};
+use std::{fmt, io, process::ExitCode};
+
+// 1 is any other error; clap's own usage errors also exit with 2.
+/// Invalid arguments or configuration, or the pool refused the login.
+pub const CONFIG: u8 = 2;
+/// No pool connection could be made.
+pub const POOL_UNREACHABLE: u8 = 3;
+/// RandomX failed its self-test or no worker thread could initialize it.
+pub const RANDOMX_INIT: u8 = 4;
+/// A mining thread stalled and could not be replaced; see `Worker::start_stall_monitor`.
+pub const THREADS_STALLED: u8 = 5;
+
+/// RandomX could not be set up, or computes wrong hashes.
+#[derive(Debug)]
+pub struct RandomXInitFailed(pub String);
+
+impl fmt::Display for RandomXInitFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for RandomXInitFailed {}
+
+/// Maps the error `main` ended with to the documented exit code.
+pub fn for_error(e: &io::Error) -> ExitCode {
+    let inner = e.get_ref();
+    let code = if inner.is_some_and(|inner| inner.is::<RandomXInitFailed>()) {
+        RANDOMX_INIT
+    } else if inner.is_some_and(|inner| inner.is::<PoolUnreachable>()) {
+        POOL_UNREACHABLE
+    } else if inner.is_some_and(|inner| inner.is::<LoginRefused>()) || e.kind() == io::ErrorKind::InvalidInput {
+        CONFIG
+    } else {
+        1
+    };
+    ExitCode::from(code)
+}
@@ -4,7 +4,11 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::{io, sync::mpsc, time::Duration};
+use std::{
+    io,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
 use tui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout},
@@ -15,50 +19,70 @@ use tui::{
 };
 
 const MAX_LOG_LINES: usize = 100;
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+/// How long the status panel stays highlighted after an accepted share, with `--bell-on-share`.
+const SHARE_FLASH_DURATION: Duration = Duration::from_millis(1500);
+
+/// Puts the terminal back in its normal state when dropped, including while a
+/// panic in the GUI thread unwinds.
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        let _ = execute!(io::stdout(), crossterm::cursor::Show);
+    }
+}
+
+/// Requests from the GUI's key bindings to the mining loop.
+#[derive(Debug, Clone, Copy)]
+pub enum GuiCommand {
+    AddThread,
+    RemoveThread,
+}
 
 pub struct Gui {
     log_rx: mpsc::Receiver<String>,
     log_messages: Vec<String>,
     gui_data_rx: mpsc::Receiver<GuiData>,
     current_gui_data: GuiData,
+    command_tx: mpsc::Sender<GuiCommand>,
+    toast: Option<(String, Instant)>,
+    share_flash: Option<Instant>,
 }
 
 impl Gui {
-    pub fn new(log_rx: mpsc::Receiver<String>, gui_data_rx: mpsc::Receiver<GuiData>) -> Self {
+    pub fn new(log_rx: mpsc::Receiver<String>, gui_data_rx: mpsc::Receiver<GuiData>, command_tx: mpsc::Sender<GuiCommand>) -> Self {
         Self {
             log_rx,
             log_messages: Vec::new(),
             gui_data_rx,
-            current_gui_data: GuiData {
-                hash_rate: 0.0,
-                total_hashes: 0,
-                elapsed_time: Duration::from_secs(0),
-                shares_found: 0,
-                is_warming_up: true,
-            },
+            current_gui_data: GuiData::default(),
+            command_tx,
+            toast: None,
+            share_flash: None,
         }
     }
 
     pub fn run(&mut self) -> io::Result<()> {
         enable_raw_mode()?;
+        let _restore = TerminalGuard;
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen)?;
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend)?;
 
-        let result = self.run_app(&mut terminal);
-
-        disable_raw_mode()?;
-        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-        terminal.show_cursor()?;
-
-        result
+        self.run_app(&mut terminal)
     }
 
     fn run_app(&mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
         let mut last_update = std::time::Instant::now();
         
         loop {
+            if crate::shutdown::requested() {
+                return Ok(());
+            }
             let now = std::time::Instant::now();
             let should_update = (now - last_update).as_millis() >= 250; // Update UI ~4 times per sec
 
@@ -67,6 +91,15 @@ impl Gui {
             }
 
             while let Ok(data) = self.gui_data_rx.try_recv() {
+                let previous = self.current_gui_data.thread_count;
+                if previous != 0 && data.thread_count != previous {
+                    self.toast = Some((format!("Threads: {} -> {}", previous, data.thread_count), Instant::now()));
+                }
+                // Shares accepted while warming up don't flash, even once warmup ends.
+                let warmed_up = !data.is_warming_up && !self.current_gui_data.is_warming_up;
+                if Display::bell_on_share() && warmed_up && data.shares_accepted > self.current_gui_data.shares_accepted {
+                    self.share_flash = Some(Instant::now());
+                }
                 self.current_gui_data = data;
             }
 
@@ -78,6 +111,12 @@ impl Gui {
                                 return Ok(());
                             }
                         }
+                        KeyCode::Char('+') | KeyCode::Char('=') => {
+                            let _ = self.command_tx.send(GuiCommand::AddThread);
+                        }
+                        KeyCode::Char('-') => {
+                            let _ = self.command_tx.send(GuiCommand::RemoveThread);
+                        }
                         _ => {}
                     }
                 }
@@ -126,17 +165,33 @@ impl Gui {
         let data = &self.current_gui_data;
 
         if !data.is_warming_up {
-            let hash_rate_str = format!("{:.2} H/s", data.hash_rate);
+            // The rate drops to zero while threads rebuild; say why instead.
+            let hash_rate_str = if data.seed_changing {
+                Display::REBUILDING_DATASET.to_string()
+            } else {
+                Display::format_hash_rate(data.hash_rate)
+            };
             let total_hashes_str = data.total_hashes.to_string();
             let elapsed_time_str = Display::format_duration(data.elapsed_time);
             let shares_found_str = data.shares_found.to_string();
+            let accepted_str = format!("{} / {}", data.shares_accepted, data.shares_rejected);
             
-            let stats = vec![
+            let mut stats = vec![
                 Row::new(vec!["Hash Rate", &hash_rate_str]),
                 Row::new(vec!["Total Hashes", &total_hashes_str]),
                 Row::new(vec!["Runtime", &elapsed_time_str]),
                 Row::new(vec!["Shares Found", &shares_found_str]),
+                Row::new(vec!["Accepted / Rejected", &accepted_str]),
+                Row::new(vec!["Luck", &data.luck]),
+                Row::new(vec!["Threads", &data.thread_modes]),
+                Row::new(vec!["Difficulty", &data.difficulty]),
             ];
+            if let Some(latency) = &data.share_latency {
+                stats.push(Row::new(vec!["Share Latency", latency.as_str()]));
+            }
+            if let Some(throttle) = &data.throttle {
+                stats.push(Row::new(vec!["Throttle", throttle.as_str()]));
+            }
 
             let stats_table = Table::new(stats)
                 .header(Row::new(vec!["Metric", "Value"]).style(Style::default().fg(Color::Yellow)))
@@ -148,18 +203,70 @@ impl Gui {
             f.render_widget(stats_table, main_content_chunks[0]);
         } else {
             // Use elapsed_time from GuiData for warmup display
-            let warmup_text = format!("Warming up... {:.1}s/45.0s", data.elapsed_time.as_secs_f64());
+            let elapsed = data.elapsed_time.as_secs_f64();
+            let warmup_text = if elapsed < 45.0 {
+                format!("Warming up... {:.1}s/45.0s", elapsed)
+            } else {
+                format!("Warming up... {:.1}s, waiting for the hash rate to settle", elapsed)
+            };
             let warmup_paragraph = Paragraph::new(warmup_text)
                 .style(Style::default().fg(Color::Yellow))
                 .alignment(tui::layout::Alignment::Center);
             f.render_widget(warmup_paragraph, main_content_chunks[0]);
         }
 
-        let status_spans = vec
-![Spans::from(Span::raw("Mining active..."))];
+        let mut status_spans = if data.is_reconnecting {
+            vec![Spans::from(Span::styled("Reconnecting to pool...", Style::default().fg(Color::Yellow)))]
+        } else {
+            vec![Spans::from(Span::raw("Mining active..."))]
+        };
+        if data.seed_changing {
+            status_spans.push(Spans::from(Span::styled(Display::REBUILDING_DATASET, Style::default().fg(Color::Yellow))));
+        }
+        match data.first_share {
+            Some(after) => status_spans.push(Spans::from(Span::styled(
+                Display::format_first_share(after),
+                Style::default().fg(Color::Green),
+            ))),
+            None => status_spans.push(Spans::from(Span::raw("Waiting for the first accepted share..."))),
+        }
+        if let Some(alert) = &data.hash_rate_alert {
+            status_spans.push(Spans::from(Span::styled(alert.as_str(), Style::default().fg(Color::Red))));
+        }
+        let side_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(6)].as_ref())
+            .split(main_content_chunks[1]);
+        let status_style = if self.share_flash.is_some_and(|at| at.elapsed() < SHARE_FLASH_DURATION) {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default()
+        };
         let shares_widget = Paragraph::new(status_spans)
-            .block(Block::default().title("Status").borders(Borders::ALL));
-        f.render_widget(shares_widget, main_content_chunks[1]);
+            .block(Block::default().title("Status").borders(Borders::ALL).border_style(status_style));
+        f.render_widget(shares_widget, side_chunks[0]);
+
+        let height_str = data.job_height.map_or_else(|| "-".to_string(), |height| height.to_string());
+        let seed_str = if data.seed_changing {
+            format!("{}… (rebuilding dataset)", data.seed_hash)
+        } else {
+            format!("{}…", data.seed_hash)
+        };
+        let job_lines = vec![
+            Spans::from(format!("Job ID:     {}", data.job_id)),
+            Spans::from(format!("Difficulty: {}", data.job_difficulty)),
+            Spans::from(format!("Height:     {}", height_str)),
+            Spans::from(format!("Seed:       {}", seed_str)),
+        ];
+        let job_style = if data.seed_changing {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        let job_widget = Paragraph::new(job_lines)
+            .style(job_style)
+            .block(Block::default().title("Current Job").borders(Borders::ALL).border_style(job_style));
+        f.render_widget(job_widget, side_chunks[1]);
 
 
         let log_spans: Vec<Spans> = self.log_messages.iter().rev().take(MAX_LOG_LINES).map(|s| {
@@ -176,9 +283,14 @@ impl Gui {
             .wrap(Wrap { trim: true });
         f.render_widget(log_widget, chunks[2]);
         
-        let footer = Paragraph::new("Press 'q' to quit")
-            .style(Style::default().fg(Color::Gray))
-            .alignment(tui::layout::Alignment::Center);
+        let footer = match &self.toast {
+            Some((toast, shown_at)) if shown_at.elapsed() < TOAST_DURATION => {
+                Paragraph::new(toast.as_str()).style(Style::default().fg(Color::Green))
+            }
+            _ => Paragraph::new("Press 'q' to quit, '+'/'-' to change threads")
+                .style(Style::default().fg(Color::Gray)),
+        }
+        .alignment(tui::layout::Alignment::Center);
         f.render_widget(footer, chunks[3]);
     }
 
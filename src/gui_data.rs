@@ -6,5 +6,52 @@ pub struct GuiData {
     pub total_hashes: u64,
     pub elapsed_time: Duration,
     pub shares_found: usize,
+    pub shares_accepted: u64,
+    pub shares_rejected: u64,
+    pub luck: String,
+    pub thread_modes: String,
+    pub thread_count: usize,
+    pub difficulty: String,
+    pub share_latency: Option<String>,
+    pub first_share: Option<Duration>,
+    pub job_id: String,
+    pub job_difficulty: u64,
+    pub job_height: Option<u64>,
+    /// Leading hex digits of the job's seed hash.
+    pub seed_hash: String,
+    /// Set while threads rebuild their dataset for a new seed (`stats::is_rebuilding_dataset`).
+    pub seed_changing: bool,
+    /// Set while a hash rate drop alert is active.
+    pub hash_rate_alert: Option<String>,
     pub is_warming_up: bool,
+    pub is_reconnecting: bool,
+    pub throttle: Option<String>,
+}
+
+impl Default for GuiData {
+    fn default() -> Self {
+        Self {
+            hash_rate: 0.0,
+            total_hashes: 0,
+            elapsed_time: Duration::from_secs(0),
+            shares_found: 0,
+            shares_accepted: 0,
+            shares_rejected: 0,
+            luck: String::new(),
+            thread_modes: String::new(),
+            thread_count: 0,
+            difficulty: String::new(),
+            share_latency: None,
+            first_share: None,
+            job_id: String::new(),
+            job_difficulty: 0,
+            job_height: None,
+            seed_hash: String::new(),
+            seed_changing: false,
+            hash_rate_alert: None,
+            is_warming_up: true,
+            is_reconnecting: false,
+            throttle: None,
+        }
+    }
 }
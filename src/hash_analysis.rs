@@ -0,0 +1,64 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+/// Buckets of hash / share threshold: bucket 0 holds shares (< 1x), bucket k
+/// holds [2^(k-1), 2^k) times the threshold, and the last bucket everything above.
+pub const RATIO_BUCKETS: usize = 32;
+
+/// Summary of a `hashes.log` written by `--debug-hash-log`.
+#[derive(Debug, Default)]
+pub struct HashLogSummary {
+    pub hashes: u64,
+    pub malformed_lines: u64,
+    pub min_hash: Option<u64>,
+    pub shares: u64,
+    /// Shares a uniform nonce search should have found, given each line's threshold.
+    pub expected_shares: f64,
+    pub ratio_histogram: [u64; RATIO_BUCKETS],
+}
+
+/// Reads a hash log (`nonce,hash_value,difficulty,job_id` per line).
+/// `.gz` logs from `--hash-log-gzip` are decompressed on the fly.
+pub fn analyze(path: &Path) -> io::Result<HashLogSummary> {
+    let file = File::open(path)?;
+    let reader: Box<dyn BufRead> = if path.extension().is_some_and(|ext| ext == "gz") {
+        Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+    let mut summary = HashLogSummary::default();
+
+    for line in reader.lines() {
+        let line = line?;
+        let mut fields = line.split(',');
+        let (Some(_nonce), Some(hash), Some(threshold)) = (fields.next(), fields.next(), fields.next()) else {
+            summary.malformed_lines += 1;
+            continue;
+        };
+        let (Ok(hash), Ok(threshold)) = (hash.parse::<u64>(), threshold.parse::<u64>()) else {
+            summary.malformed_lines += 1;
+            continue;
+        };
+
+        summary.hashes += 1;
+        summary.min_hash = Some(summary.min_hash.map_or(hash, |min| min.min(hash)));
+        summary.expected_shares += threshold as f64 / u64::MAX as f64;
+        if hash < threshold {
+            summary.shares += 1;
+        }
+        summary.ratio_histogram[ratio_bucket(hash, threshold)] += 1;
+    }
+    Ok(summary)
+}
+
+fn ratio_bucket(hash: u64, threshold: u64) -> usize {
+    if hash < threshold {
+        return 0;
+    }
+    let ratio = hash / threshold.max(1);
+    // ratio >= 1 here; floor(log2(ratio)) + 1 puts [1, 2) in bucket 1.
+    ((64 - ratio.leading_zeros()) as usize).min(RATIO_BUCKETS - 1)
+}
@@ -1,8 +1,9 @@
+use flate2::{write::GzEncoder, Compression};
 use std::{
-    fs::OpenOptions,
-    io::Write,
+    fs::{File, OpenOptions},
+    io::{self, Write},
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, Mutex,
     },
 };
@@ -10,9 +11,53 @@ use once_cell::sync::Lazy;
 
 // Static flag to control logging
 static LOGGING_ENABLED: AtomicBool = AtomicBool::new(false);
+// Workers log one hash in this many (plus every share).
+static SAMPLE_INTERVAL: AtomicU64 = AtomicU64::new(1);
+
+/// Lines per gzip member; ending members regularly bounds what a killed
+/// process loses to the data still buffered in the encoder.
+const GZIP_MEMBER_LINES: u64 = 100_000;
+
+enum LogSink {
+    Plain(File),
+    /// Written as a series of complete gzip members, which `zcat`, `gzip -d`
+    /// and `--analyze` read back as one stream.
+    Gzip { encoder: GzEncoder<File>, lines: u64 },
+}
+
+impl LogSink {
+    fn write_line(&mut self, line: std::fmt::Arguments) -> io::Result<()> {
+        match self {
+            LogSink::Plain(file) => writeln!(file, "{}", line),
+            LogSink::Gzip { encoder, lines } => {
+                *lines += 1;
+                writeln!(encoder, "{}", line)
+            }
+        }
+    }
+
+    fn member_full(&self) -> bool {
+        matches!(self, LogSink::Gzip { lines, .. } if *lines >= GZIP_MEMBER_LINES)
+    }
+
+    /// Flushes a plain file; finishes the current gzip member and starts the next.
+    fn flush(self) -> io::Result<Self> {
+        match self {
+            LogSink::Plain(mut file) => {
+                file.flush()?;
+                Ok(LogSink::Plain(file))
+            }
+            LogSink::Gzip { encoder, .. } => {
+                let mut file = encoder.finish()?;
+                file.flush()?;
+                Ok(LogSink::Gzip { encoder: GzEncoder::new(file, Compression::fast()), lines: 0 })
+            }
+        }
+    }
+}
 
 pub struct HashLogger {
-    file: Arc<Mutex<Option<std::fs::File>>>,
+    file: Arc<Mutex<Option<LogSink>>>,
 }
 
 impl HashLogger {
@@ -27,16 +72,20 @@ impl HashLogger {
         &INSTANCE
     }
 
-    pub fn init() {
+    pub fn init(gzip: bool) {
         match OpenOptions::new()
             .create(true)
             .write(true)
             .truncate(true)
-            .open("hashes.log") {
+            .open(if gzip { "hashes.log.gz" } else { "hashes.log" }) {
             Ok(file) => {
                 let instance = Self::get_instance();
                 let mut file_guard = instance.file.lock().unwrap();
-                *file_guard = Some(file);
+                *file_guard = Some(if gzip {
+                    LogSink::Gzip { encoder: GzEncoder::new(file, Compression::fast()), lines: 0 }
+                } else {
+                    LogSink::Plain(file)
+                });
                 LOGGING_ENABLED.store(true, Ordering::SeqCst);
             }
             Err(e) => {
@@ -51,11 +100,26 @@ impl HashLogger {
         }
 
         let instance = Self::get_instance();
-        let file_guard = instance.file.lock().unwrap();
-        if let Some(mut file) = file_guard.as_ref() {
-            if let Err(e) = writeln!(file, "{},{},{},{}", nonce, hash_value, difficulty, job_id) {
+        let mut file_guard = instance.file.lock().unwrap();
+        if let Some(sink) = file_guard.as_mut() {
+            if let Err(e) = sink.write_line(format_args!("{},{},{},{}", nonce, hash_value, difficulty, job_id)) {
                 eprintln!("ERROR: Failed to write to hash log: {}", e);
             }
+            if sink.member_full() {
+                Self::flush_sink(&mut file_guard);
+            }
+        }
+    }
+
+    fn flush_sink(file_guard: &mut Option<LogSink>) {
+        if let Some(sink) = file_guard.take() {
+            match sink.flush() {
+                Ok(sink) => *file_guard = Some(sink),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to flush hash log: {}", e);
+                    LOGGING_ENABLED.store(false, Ordering::SeqCst);
+                }
+            }
         }
     }
 
@@ -65,18 +129,23 @@ impl HashLogger {
         }
 
         let instance = Self::get_instance();
-        let file_guard = instance.file.lock().unwrap();
-        if let Some(mut file) = file_guard.as_ref() {
-            if let Err(e) = file.flush() {
-                eprintln!("ERROR: Failed to flush hash log: {}", e);
-            }
-        }
+        Self::flush_sink(&mut instance.file.lock().unwrap());
     }
 }
 
 // Public functions for external use
-pub fn init_hash_logger() {
-    HashLogger::init();
+/// Writes `hashes.log`, or `hashes.log.gz` when `gzip` is set.
+pub fn init_hash_logger(gzip: bool) {
+    HashLogger::init(gzip);
+}
+
+/// Set before the workers start; each thread reads it once.
+pub fn set_sample_interval(every: u64) {
+    SAMPLE_INTERVAL.store(every.max(1), Ordering::SeqCst);
+}
+
+pub fn sample_interval() -> u64 {
+    SAMPLE_INTERVAL.load(Ordering::SeqCst)
 }
 
 pub fn log_hash_value(nonce: u32, hash_value: u64, difficulty: u64, job_id: &str) {
@@ -1,34 +1,72 @@
 use std::{
+    cell::Cell,
     collections::VecDeque,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         Arc, Mutex,
     },
     time::{Duration, Instant},
 };
+use crate::clock::{Clock, SystemClock};
 use lazy_static::lazy_static;
-use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
 
+/// Hashes counted in one bucket, starting at `timestamp`.
 #[derive(Clone)]
 struct HashEvent {
     timestamp: Instant,
     count: u64,
 }
 
-// Static start time for the application
-static START_TIME: Lazy<Instant> = Lazy::new(Instant::now);
+/// Increments within this span of a bucket's start are added to it, so the rate
+/// window holds at most one entry per bucket however high the hash rate is.
+const BUCKET_DURATION: Duration = Duration::from_secs(1);
+
+/// Adds `count` hashes at `now` to the newest bucket, or starts a new one.
+fn record(events: &mut VecDeque<HashEvent>, now: Instant, count: u64) {
+    match events.back_mut() {
+        Some(bucket) if now.saturating_duration_since(bucket.timestamp) < BUCKET_DURATION => bucket.count += count,
+        _ => events.push_back(HashEvent { timestamp: now, count }),
+    }
+}
+
+// Every hash since startup, warmup included (the rate window skips warmup).
+static LIFETIME_HASHES: AtomicU64 = AtomicU64::new(0);
+
+// Threads that have reported at least one hash.
+static HASHING_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static HAS_HASHED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Window the warmup's minimum hash rate has to be sustained over.
+const WARMUP_RATE_WINDOW: Duration = Duration::from_secs(10);
+/// Warmup ends by this point even if the extra criteria are never met, so a
+/// threshold set too high cannot hide the hash rate for good.
+const MAX_WARMUP_DURATION: Duration = Duration::from_secs(5 * 60);
+
+/// Conditions, on top of the fixed warmup time, for warmup to count as complete.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WarmupCriteria {
+    /// Hashes per second over the last `WARMUP_RATE_WINDOW`.
+    pub min_hash_rate: Option<f64>,
+    /// Number of threads that must each have produced a hash.
+    pub threads: Option<usize>,
+}
 
 // Global instance of HashRateTracker
 lazy_static! {
     static ref HASH_RATE_TRACKER_INSTANCE: Arc<Mutex<HashRateTracker>> = {
-        let tracker = HashRateTracker::new(); // Default to no debug
+        let tracker = HashRateTracker::new(SystemClock); // Default to no debug
         Arc::new(Mutex::new(tracker))
     };
 }
 
-pub fn init_hash_rate_tracker(debug_all: bool) {
+pub fn init_hash_rate_tracker(debug_all: bool, warmup: WarmupCriteria) {
     let mut tracker = HASH_RATE_TRACKER_INSTANCE.lock().unwrap();
     tracker.debug_all = debug_all;
+    tracker.warmup = warmup;
 }
 
 pub fn get_hash_rate_tracker() -> &'static Arc<Mutex<HashRateTracker>> {
@@ -36,53 +74,57 @@ pub fn get_hash_rate_tracker() -> &'static Arc<Mutex<HashRateTracker>> {
 }
 
 #[derive(Clone)]
-pub struct HashRateTracker {
+pub struct HashRateTracker<C: Clock = SystemClock> {
+    clock: C,
+    // Set on first use; runtime and warmup are measured from here.
+    started: OnceCell<Instant>,
     hash_events: Arc<Mutex<VecDeque<HashEvent>>>,
     warmup_duration: Duration,
     window_duration: Duration,
     warmup_complete: Arc<AtomicBool>,
+    warmup: WarmupCriteria,
     debug_all: bool,
 }
 
-impl HashRateTracker {
-    pub fn new() -> Self {
+impl<C: Clock> HashRateTracker<C> {
+    pub fn new(clock: C) -> Self {
         Self {
+            clock,
+            started: OnceCell::new(),
             hash_events: Arc::new(Mutex::new(VecDeque::new())),
             warmup_duration: Duration::from_secs(45),
             window_duration: Duration::from_secs(120),
             warmup_complete: Arc::new(AtomicBool::new(false)),
+            warmup: WarmupCriteria::default(),
             debug_all: false,
         }
     }
 
     #[inline(always)]
     pub fn increment(&self, count: u64) {
-        let now = Instant::now();
-        
-        let global_elapsed = now.duration_since(*START_TIME);
-        
-        if global_elapsed < self.warmup_duration {
-            if self.debug_all {
-                eprintln!("DEBUG: Still in warmup - global_time: {:.2}s, needed: {:.2}s", 
-                         global_elapsed.as_secs_f64(), self.warmup_duration.as_secs_f64());
-            }
-            return;
+        LIFETIME_HASHES.fetch_add(count, Ordering::Relaxed);
+        if !HAS_HASHED.replace(true) {
+            HASHING_THREADS.fetch_add(1, Ordering::Relaxed);
         }
+        let now = self.clock.now();
+        
+        let global_elapsed = self.running_time(now);
         
         if !self.warmup_complete.load(Ordering::Relaxed) {
+            if !self.warmup_done(now, global_elapsed, count) {
+                return;
+            }
             self.warmup_complete.store(true, Ordering::SeqCst);
+            // Drop the samples kept for the warmup rate check; the reported window starts here.
+            self.hash_events.lock().unwrap().clear();
             if self.debug_all {
                 eprintln!("DEBUG: Warmup completed at {:.2}s", global_elapsed.as_secs_f64());
             }
         }
         
-        self.hash_events.lock().unwrap().push_back(HashEvent {
-            timestamp: now,
-            count,
-        });
-        
         let cutoff = now - self.window_duration;
         let mut events = self.hash_events.lock().unwrap();
+        record(&mut events, now, count);
         while let Some(event) = events.front() {
             if event.timestamp < cutoff {
                 events.pop_front();
@@ -92,9 +134,61 @@ impl HashRateTracker {
         }
     }
 
+    /// Whether the fixed warmup time has passed and `self.warmup`'s criteria are met;
+    /// during warmup, samples the last `WARMUP_RATE_WINDOW` of hashes for the rate check.
+    fn warmup_done(&self, now: Instant, global_elapsed: Duration, count: u64) -> bool {
+        if global_elapsed >= MAX_WARMUP_DURATION {
+            return true;
+        }
+        if self.warmup.min_hash_rate.is_some() {
+            let mut events = self.hash_events.lock().unwrap();
+            record(&mut events, now, count);
+            while events.front().is_some_and(|event| now - event.timestamp > WARMUP_RATE_WINDOW) {
+                events.pop_front();
+            }
+        }
+        if global_elapsed < self.warmup_duration {
+            if self.debug_all {
+                eprintln!("DEBUG: Still in warmup - global_time: {:.2}s, needed: {:.2}s", 
+                         global_elapsed.as_secs_f64(), self.warmup_duration.as_secs_f64());
+            }
+            return false;
+        }
+        if let Some(threads) = self.warmup.threads {
+            let hashing = HASHING_THREADS.load(Ordering::Relaxed);
+            if hashing < threads {
+                if self.debug_all {
+                    eprintln!("DEBUG: Still in warmup - {}/{} threads hashing", hashing, threads);
+                }
+                return false;
+            }
+        }
+        if let Some(min_hash_rate) = self.warmup.min_hash_rate {
+            let hashes: u64 = self.hash_events.lock().unwrap().iter().map(|event| event.count).sum();
+            let rate = hashes as f64 / WARMUP_RATE_WINDOW.as_secs_f64();
+            if rate < min_hash_rate {
+                if self.debug_all {
+                    eprintln!("DEBUG: Still in warmup - {:.1} H/s over the last {}s, needed: {:.1} H/s",
+                             rate, WARMUP_RATE_WINDOW.as_secs(), min_hash_rate);
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Drops the samples in the rate window, so the rate is measured afresh from here.
+    pub fn restart_window(&self) {
+        self.hash_events.lock().unwrap().clear();
+    }
+
+    pub fn is_warmup_complete(&self) -> bool {
+        self.warmup_complete.load(Ordering::Relaxed)
+    }
+
     #[inline(always)]
     pub fn get_total_hashes(&self) -> u64 {
-        let now = Instant::now();
+        let now = self.clock.now();
         
         let cutoff = now - self.window_duration;
         let mut events = self.hash_events.lock().unwrap();
@@ -111,7 +205,7 @@ impl HashRateTracker {
 
     #[inline(always)]
     pub fn get_hash_rate(&self) -> f64 {
-        let now = Instant::now();
+        let now = self.clock.now();
         
         let cutoff = now - self.window_duration;
         let mut events = self.hash_events.lock().unwrap();
@@ -145,9 +239,169 @@ impl HashRateTracker {
         total_hashes as f64 / elapsed
     }
 
+    pub fn get_lifetime_hashes(&self) -> u64 {
+        LIFETIME_HASHES.load(Ordering::Relaxed)
+    }
+
     #[inline(always)]
     pub fn get_elapsed_time(&self) -> Duration {
-        // Return total runtime since START_TIME
-        Instant::now().duration_since(*START_TIME)
+        self.running_time(self.clock.now())
+    }
+
+    /// Time since the tracker was first used, less any the process spent suspended.
+    fn running_time(&self, now: Instant) -> Duration {
+        let started = *self.started.get_or_init(|| now);
+        now.duration_since(started).saturating_sub(crate::suspend::suspended_total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn warmed_up_tracker() -> (HashRateTracker<MockClock>, MockClock) {
+        let clock = MockClock::new();
+        let tracker = HashRateTracker::new(clock.clone());
+        tracker.increment(1);
+        clock.advance(tracker.warmup_duration);
+        tracker.increment(0);
+        assert!(tracker.is_warmup_complete());
+        (tracker, clock)
+    }
+
+    fn bucket_counts(events: &VecDeque<HashEvent>) -> Vec<u64> {
+        events.iter().map(|event| event.count).collect()
+    }
+
+    #[test]
+    fn increments_within_a_second_share_a_bucket() {
+        let start = MockClock::new().now();
+        let mut events = VecDeque::new();
+        record(&mut events, start, 100);
+        record(&mut events, start + Duration::from_millis(400), 100);
+        record(&mut events, start + Duration::from_millis(999), 100);
+        assert_eq!(bucket_counts(&events), vec![300]);
+        assert_eq!(events[0].timestamp, start);
+    }
+
+    #[test]
+    fn a_new_bucket_starts_each_second() {
+        let start = MockClock::new().now();
+        let mut events = VecDeque::new();
+        record(&mut events, start, 100);
+        record(&mut events, start + BUCKET_DURATION, 50);
+        record(&mut events, start + Duration::from_millis(1500), 50);
+        record(&mut events, start + Duration::from_secs(5), 7);
+        assert_eq!(bucket_counts(&events), vec![100, 100, 7]);
+    }
+
+    #[test]
+    fn window_holds_one_bucket_per_second_at_any_rate() {
+        let (tracker, clock) = warmed_up_tracker();
+        for _ in 0..tracker.window_duration.as_millis() * 2 {
+            clock.advance(Duration::from_millis(1));
+            tracker.increment(1);
+        }
+        let buckets = tracker.hash_events.lock().unwrap().len() as u64;
+        assert!(buckets <= tracker.window_duration.as_secs() + 1, "{buckets} buckets");
+    }
+
+    #[test]
+    fn warmup_hashes_are_not_counted() {
+        let clock = MockClock::new();
+        let tracker = HashRateTracker::new(clock.clone());
+        for _ in 0..44 {
+            tracker.increment(1000);
+            clock.advance(Duration::from_secs(1));
+        }
+        assert!(!tracker.is_warmup_complete());
+        assert_eq!(tracker.get_total_hashes(), 0);
+        assert_eq!(tracker.get_hash_rate(), 0.0);
+    }
+
+    fn tracker_with(warmup: WarmupCriteria) -> (HashRateTracker<MockClock>, MockClock) {
+        let clock = MockClock::new();
+        let mut tracker = HashRateTracker::new(clock.clone());
+        tracker.warmup = warmup;
+        (tracker, clock)
+    }
+
+    /// Hashes `per_second` every second for `seconds`.
+    fn hash_for(tracker: &HashRateTracker<MockClock>, clock: &MockClock, seconds: u64, per_second: u64) {
+        for _ in 0..seconds {
+            tracker.increment(per_second);
+            clock.advance(Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn warmup_waits_for_the_minimum_hash_rate() {
+        let (tracker, clock) = tracker_with(WarmupCriteria { min_hash_rate: Some(100.0), threads: None });
+        hash_for(&tracker, &clock, 60, 10);
+        assert!(!tracker.is_warmup_complete());
+        hash_for(&tracker, &clock, 10, 200);
+        assert!(tracker.is_warmup_complete());
+    }
+
+    #[test]
+    fn warmup_waits_for_every_thread() {
+        // This thread has hashed, so a single required thread is already there.
+        let (tracker, clock) = tracker_with(WarmupCriteria { min_hash_rate: None, threads: Some(1) });
+        hash_for(&tracker, &clock, 46, 1);
+        assert!(tracker.is_warmup_complete());
+
+        let (tracker, clock) = tracker_with(WarmupCriteria { min_hash_rate: None, threads: Some(usize::MAX) });
+        hash_for(&tracker, &clock, 46, 1);
+        assert!(!tracker.is_warmup_complete());
+    }
+
+    #[test]
+    fn unmet_criteria_give_up_after_the_maximum_warmup() {
+        let (tracker, clock) = tracker_with(WarmupCriteria { min_hash_rate: Some(f64::MAX), threads: Some(usize::MAX) });
+        hash_for(&tracker, &clock, MAX_WARMUP_DURATION.as_secs(), 1);
+        assert!(!tracker.is_warmup_complete());
+        tracker.increment(1);
+        assert!(tracker.is_warmup_complete());
+    }
+
+    #[test]
+    fn rate_over_the_window() {
+        let (tracker, clock) = warmed_up_tracker();
+        for _ in 0..100 {
+            clock.advance(Duration::from_secs(1));
+            tracker.increment(500);
+        }
+        assert_eq!(tracker.get_total_hashes(), 50_000);
+        // The first bucket is the warmup's empty closing one, 100s before now.
+        assert_eq!(tracker.get_hash_rate(), 500.0);
+    }
+
+    #[test]
+    fn old_samples_age_out_of_the_window() {
+        let (tracker, clock) = warmed_up_tracker();
+        clock.advance(Duration::from_secs(1));
+        tracker.increment(1_000_000);
+        clock.advance(tracker.window_duration + Duration::from_secs(1));
+        assert_eq!(tracker.get_total_hashes(), 0);
+        assert_eq!(tracker.get_hash_rate(), 0.0);
+    }
+
+    #[test]
+    fn restart_window_forgets_earlier_samples() {
+        let (tracker, clock) = warmed_up_tracker();
+        clock.advance(Duration::from_secs(1));
+        tracker.increment(1_000_000);
+        tracker.restart_window();
+        clock.advance(Duration::from_secs(10));
+        tracker.increment(100);
+        assert_eq!(tracker.get_total_hashes(), 100);
+    }
+
+    #[test]
+    fn elapsed_time_follows_the_clock() {
+        let (tracker, clock) = warmed_up_tracker();
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(tracker.get_elapsed_time(), tracker.warmup_duration + Duration::from_secs(3600));
     }
 }
@@ -0,0 +1,96 @@
+use crate::{hash_rate::get_hash_rate_tracker, webhook::Webhook};
+use serde_json::json;
+use std::{
+    collections::VecDeque,
+    io,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+/// Recent rate that is compared against the long-window average.
+const SHORT_WINDOW: Duration = Duration::from_secs(60);
+const LONG_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, Copy)]
+pub enum HashRateAlert {
+    /// The short-window rate fell more than the configured percentage below the long-window rate.
+    Dropped { short: f64, long: f64 },
+    Recovered { short: f64, long: f64 },
+}
+
+/// Starts a thread that samples the hash counter and reports drops (and the
+/// recovery afterwards) on the returned channel and to the webhook, if any.
+pub fn start(drop_percent: u8, webhook: Option<Webhook>) -> Receiver<HashRateAlert> {
+    let (alert_tx, alert_rx) = mpsc::channel();
+    thread::spawn(move || run(drop_percent, webhook, alert_tx));
+    alert_rx
+}
+
+fn run(drop_percent: u8, webhook: Option<Webhook>, alert_tx: Sender<HashRateAlert>) {
+    let threshold = 1.0 - drop_percent as f64 / 100.0;
+    let mut samples: VecDeque<(Instant, u64)> = VecDeque::new();
+    let mut dropped = false;
+    let mut suspended = crate::suspend::suspended_total();
+    loop {
+        // Samples from before a suspend would compare the frozen gap against live hashing.
+        if crate::suspend::suspended_total() != suspended {
+            suspended = crate::suspend::suspended_total();
+            samples.clear();
+        }
+        let now = Instant::now();
+        samples.push_back((now, get_hash_rate_tracker().lock().unwrap().get_lifetime_hashes()));
+        while samples.front().is_some_and(|&(at, _)| now - at > LONG_WINDOW) {
+            samples.pop_front();
+        }
+
+        // Only judge once a full long window (warmup included) is on record.
+        let (Some(long), Some(short)) = (rate_over(&samples, LONG_WINDOW), rate_over(&samples, SHORT_WINDOW)) else {
+            thread::sleep(SAMPLE_INTERVAL);
+            continue;
+        };
+        let alert = if !dropped && short < long * threshold {
+            Some(HashRateAlert::Dropped { short, long })
+        } else if dropped && short >= long * threshold {
+            Some(HashRateAlert::Recovered { short, long })
+        } else {
+            None
+        };
+        if let Some(alert) = alert {
+            dropped = matches!(alert, HashRateAlert::Dropped { .. });
+            if let Some(webhook) = &webhook {
+                if let Err(e) = post(webhook, alert, drop_percent) {
+                    tracing::warn!("Hash rate alert webhook failed: {}", e);
+                }
+            }
+            if alert_tx.send(alert).is_err() {
+                return;
+            }
+        }
+        thread::sleep(SAMPLE_INTERVAL);
+    }
+}
+
+/// Hashes per second between the newest sample and the oldest one at least
+/// `window` before it; `None` until the samples span the window.
+fn rate_over(samples: &VecDeque<(Instant, u64)>, window: Duration) -> Option<f64> {
+    let &(newest_at, newest) = samples.back()?;
+    let &(oldest_at, oldest) = samples.iter().rev().find(|&&(at, _)| newest_at - at >= window.saturating_sub(SAMPLE_INTERVAL / 2))?;
+    let secs = (newest_at - oldest_at).as_secs_f64();
+    (secs > 0.0).then(|| newest.saturating_sub(oldest) as f64 / secs)
+}
+
+fn post(webhook: &Webhook, alert: HashRateAlert, drop_percent: u8) -> io::Result<()> {
+    let (event, short, long) = match alert {
+        HashRateAlert::Dropped { short, long } => ("hashrate_drop", short, long),
+        HashRateAlert::Recovered { short, long } => ("hashrate_recovered", short, long),
+    };
+    webhook.post(&json!({
+        "event": event,
+        "short_window_hashrate": short,
+        "long_window_hashrate": long,
+        "drop_percent_threshold": drop_percent,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    }))
+}
@@ -1,29 +1,329 @@
+use crate::target;
 use serde::{Deserialize, Deserializer};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// So the detected blob encoding is logged for the first job only.
+static ENCODING_LOGGED: AtomicBool = AtomicBool::new(false);
+
+/// Decodes a job blob or seed hash. Pools normally send hex; a few send base64,
+/// which is tried when the string is not valid hex.
+pub fn decode_blob(s: &str) -> Result<Vec<u8>, String> {
+    let (bytes, encoding) = match hex::decode(s) {
+        Ok(bytes) => (bytes, "hex"),
+        Err(hex_err) => match decode_base64(s) {
+            Some(bytes) => (bytes, "base64"),
+            None => return Err(format!("blob is neither hex ({}) nor base64", hex_err)),
+        },
+    };
+    if !ENCODING_LOGGED.swap(true, Ordering::Relaxed) {
+        tracing::info!("Pool sends job blobs as {}", encoding);
+    }
+    Ok(bytes)
+}
+
+/// Standard or URL-safe base64, padding optional.
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in s.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    // A single leftover character cannot encode a whole byte.
+    if bits >= 6 {
+        return None;
+    }
+    Some(out)
+}
+
+fn blob_from_str<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    decode_blob(&s).map_err(serde::de::Error::custom)
+}
+
+fn optional_blob_from_str<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    blob_from_str(deserializer).map(Some)
+}
 
 fn target_from_hex<'de, D>(deserializer: D) -> Result<u32, D::Error>
 where
     D: Deserializer<'de>,
 {
     let hex: String = Deserialize::deserialize(deserializer)?;
-    Ok(u32::from_le_bytes(
-        hex::decode(hex).unwrap().try_into().unwrap(),
-    ))
+    crate::target::parse_compact_target(&hex).map_err(serde::de::Error::custom)
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Job {
     #[serde(rename = "job_id")]
     pub id: String,
-    #[serde(with = "hex")]
+    #[serde(deserialize_with = "blob_from_str")]
     pub blob: Vec<u8>,
-    #[serde(rename = "seed_hash", with = "hex")]
+    #[serde(rename = "seed_hash", deserialize_with = "blob_from_str")]
     pub seed: Vec<u8>,
     #[serde(deserialize_with = "target_from_hex")]
     pub target: u32,
+    /// Difficulty to use instead of `target` when it is beyond what a 32-bit
+    /// target can express, such as a solo block template's network difficulty.
+    #[serde(skip)]
+    pub share_difficulty: Option<u64>,
+    /// Block height, when the pool sends one.
+    #[serde(default)]
+    pub height: Option<u64>,
+    /// Mining algorithm the pool says the job is for, e.g. "rx/0", when it sends one.
+    #[serde(default)]
+    pub algo: Option<String>,
+    /// Which `--dual-pool` connection the job came from; always 0 otherwise.
+    #[serde(skip)]
+    pub pool: usize,
+    /// Start of the region of the blob the pool reserves for the miner (in the
+    /// miner tx's extra field), where `extra_nonce` goes. The nonce itself always
+    /// stays at `NONCE_OFFSET`.
+    #[serde(default)]
+    pub reserved_offset: Option<usize>,
+    /// Bytes the pool assigns this connection to keep its nonce space apart from
+    /// other miners'; written at `reserved_offset`. Hex or base64 like the blob.
+    #[serde(default, deserialize_with = "optional_blob_from_str")]
+    pub extra_nonce: Option<Vec<u8>>,
 }
 
+/// The 4-byte nonce workers write into the blob starts at this offset.
+pub const NONCE_OFFSET: usize = 39;
+/// Shortest blob the nonce write fits in.
+pub const MIN_BLOB_LEN: usize = NONCE_OFFSET + 4;
+/// Algorithm requested at login; the only one the workers can hash.
+pub const ALGO: &str = "rx/0";
+/// Names pools use for `ALGO` in a job's `algo` field.
+const ALGO_NAMES: [&str; 2] = [ALGO, "randomx"];
+
 impl Job {
+    /// Rejects jobs a worker thread could not hash, such as a blob too short for the nonce.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.extra_nonce.is_some() && self.reserved_offset.is_none() {
+            return Err(format!("job {} has an extra_nonce but no reserved_offset to put it at", self.id));
+        }
+        if self.blob.len() < MIN_BLOB_LEN {
+            return Err(format!(
+                "job {} blob is {} bytes, need at least {} for the nonce",
+                self.id,
+                self.blob.len(),
+                MIN_BLOB_LEN
+            ));
+        }
+        if let (Some(offset), Some(extra_nonce)) = (self.reserved_offset, &self.extra_nonce) {
+            let end = offset.saturating_add(extra_nonce.len());
+            if self.blob.len() < end {
+                return Err(format!(
+                    "job {} blob is {} bytes, need at least {} for the extra_nonce at reserved_offset {}",
+                    self.id,
+                    self.blob.len(),
+                    end,
+                    offset
+                ));
+            }
+            if offset < MIN_BLOB_LEN && end > NONCE_OFFSET {
+                return Err(format!("job {} extra_nonce at reserved_offset {} overlaps the nonce at {}", self.id, offset, NONCE_OFFSET));
+            }
+        }
+        Ok(())
+    }
+
+    /// The job's `algo` if the pool named one other than RandomX, meaning the
+    /// hashes won't be valid for it.
+    pub fn foreign_algo(&self) -> Option<&str> {
+        self.algo.as_deref().filter(|algo| !ALGO_NAMES.iter().any(|name| algo.eq_ignore_ascii_case(name)))
+    }
+
+    /// Writes the extra nonce into a copy of this job's blob; a no-op without one.
+    /// Only call on a validated job.
+    pub fn write_extra_nonce(&self, blob: &mut [u8]) {
+        if let (Some(offset), Some(extra_nonce)) = (self.reserved_offset, &self.extra_nonce) {
+            blob[offset..offset + extra_nonce.len()].copy_from_slice(extra_nonce);
+        }
+    }
+
+    /// 64-bit threshold the top 8 bytes of a hash must not exceed to be a share.
     pub fn difficulty(&self) -> u64 {
-        u64::MAX / (u32::MAX / self.target) as u64
+        target::difficulty_to_threshold(self.pool_difficulty())
+    }
+
+    /// Difficulty as the pool reports it: the expected number of hashes per share.
+    pub fn pool_difficulty(&self) -> u64 {
+        self.share_difficulty.unwrap_or_else(|| target::compact_to_difficulty(self.target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 76-byte hashing blob: version, timestamp and previous block id up to the
+    // nonce at 39, then the Merkle root and transaction count.
+    const BLOB: &str = concat!(
+        "1010", "c6a3d7b306",
+        "8f3f0c1d5e7a9b2c4d6e8f0a1b3c5d7e9f0a2b4c6d8e0f1a3b5c7d9e1f2a4b6c",
+        "00000000",
+        "5b7d9f1a3c5e7f9b1d3f5a7c9e1b3d5f7a9c1e3b5d7f9a1c3e5b7d9f1a3c5e7f",
+        "03",
+    );
+
+    fn job(extra: &str) -> Job {
+        let json = format!(
+            r#"{{"job_id": "42", "blob": "{}", "seed_hash": "{}", "target": "b88d0600"{}}}"#,
+            BLOB,
+            "ab".repeat(32),
+            extra
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn decodes_hex_blobs() {
+        assert_eq!(decode_blob("00ff10ab"), Ok(vec![0x00, 0xff, 0x10, 0xab]));
+        assert_eq!(decode_blob("00FF10AB"), Ok(vec![0x00, 0xff, 0x10, 0xab]));
+        assert_eq!(decode_blob(""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn decodes_base64_blobs() {
+        // Not hex (odd length / non-hex digits), so tried as base64.
+        assert_eq!(decode_blob("AP8Qqw=="), Ok(vec![0x00, 0xff, 0x10, 0xab]));
+        assert_eq!(decode_blob("AP8Qqw"), Ok(vec![0x00, 0xff, 0x10, 0xab]));
+        assert_eq!(decode_blob("+/+/"), Ok(vec![0xfb, 0xff, 0xbf]));
+        assert_eq!(decode_blob("-_-_"), Ok(vec![0xfb, 0xff, 0xbf]));
+        let bytes: Vec<u8> = (0..=255).collect();
+        let base64 = concat!(
+            "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4OTo7PD0+P0BBQkNERUZHSElKS0xNTk9Q",
+            "UVJTVFVWV1hZWltcXV5fYGFiY2RlZmdoaWprbG1ub3BxcnN0dXZ3eHl6e3x9fn+AgYKDhIWGh4iJiouMjY6PkJGSk5SVlpeYmZqbnJ2en6Ch",
+            "oqOkpaanqKmqq6ytrq+wsbKztLW2t7i5uru8vb6/wMHCw8TFxsfIycrLzM3Oz9DR0tPU1dbX2Nna29zd3t/g4eLj5OXm5+jp6uvs7e7v8PHy",
+            "8/T19vf4+fr7/P3+/w=="
+        );
+        assert_eq!(decode_blob(base64), Ok(bytes));
+    }
+
+    #[test]
+    fn rejects_blobs_in_neither_encoding() {
+        assert!(decode_blob("not a blob!").is_err());
+        // A single trailing character can't encode a whole byte.
+        assert!(decode_blob("AP8Qq").is_err());
+    }
+
+    #[test]
+    fn base64_job_fields_are_decoded() {
+        let json = r#"{"job_id": "b64", "blob": "AP8Qqw==", "seed_hash": "AP8Qqw==", "target": "b88d0600"}"#;
+        let job: Job = serde_json::from_str(json).unwrap();
+        assert_eq!(job.blob, [0x00, 0xff, 0x10, 0xab]);
+        assert_eq!(job.seed, [0x00, 0xff, 0x10, 0xab]);
+    }
+
+    #[test]
+    fn plain_job_has_no_reserved_region() {
+        let job = job("");
+        assert_eq!(job.blob.len(), 76);
+        assert_eq!(job.reserved_offset, None);
+        assert_eq!(job.extra_nonce, None);
+        assert!(job.validate().is_ok());
+        let mut blob = job.blob.clone();
+        job.write_extra_nonce(&mut blob);
+        assert_eq!(blob, job.blob);
+    }
+
+    #[test]
+    fn extra_nonce_goes_at_reserved_offset_and_leaves_the_nonce_alone() {
+        let job = job(r#", "reserved_offset": 60, "extra_nonce": "deadbeef""#);
+        assert!(job.validate().is_ok());
+        let mut blob = job.blob.clone();
+        job.write_extra_nonce(&mut blob);
+        assert_eq!(&blob[60..64], &[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(&blob[NONCE_OFFSET..MIN_BLOB_LEN], &job.blob[NONCE_OFFSET..MIN_BLOB_LEN]);
+        assert_eq!(&blob[..60], &job.blob[..60]);
+        assert_eq!(&blob[64..], &job.blob[64..]);
+    }
+
+    #[test]
+    fn reserved_offset_alone_is_accepted() {
+        let job = job(r#", "reserved_offset": 60"#);
+        assert!(job.validate().is_ok());
+    }
+
+    #[test]
+    fn extra_nonce_needs_a_reserved_offset() {
+        let job = job(r#", "extra_nonce": "deadbeef""#);
+        assert!(job.validate().unwrap_err().contains("no reserved_offset"));
+    }
+
+    #[test]
+    fn extra_nonce_must_fit_in_the_blob() {
+        assert!(job(r#", "reserved_offset": 72, "extra_nonce": "deadbeef""#).validate().is_ok());
+        let job = job(r#", "reserved_offset": 73, "extra_nonce": "deadbeef""#);
+        assert!(job.validate().unwrap_err().contains("need at least 77"));
+    }
+
+    #[test]
+    fn extra_nonce_must_not_overlap_the_nonce() {
+        assert!(job(r#", "reserved_offset": 35, "extra_nonce": "deadbeef""#).validate().is_ok());
+        assert!(job(r#", "reserved_offset": 43, "extra_nonce": "deadbeef""#).validate().is_ok());
+        for offset in [36, 39, 42] {
+            let job = job(&format!(r#", "reserved_offset": {}, "extra_nonce": "deadbeef""#, offset));
+            assert!(job.validate().unwrap_err().contains("overlaps the nonce"), "offset {}", offset);
+        }
+    }
+
+    #[test]
+    fn short_blob_is_rejected() {
+        let mut job = job("");
+        job.blob.truncate(MIN_BLOB_LEN);
+        assert!(job.validate().is_ok());
+        job.blob.truncate(MIN_BLOB_LEN - 1);
+        let error = job.validate().unwrap_err();
+        assert!(error.contains("42 bytes") && error.contains("at least 43"), "{}", error);
+        job.blob.clear();
+        assert!(job.validate().is_err());
+    }
+
+    #[test]
+    fn algo_and_height_are_optional() {
+        let job = job("");
+        assert_eq!(job.algo, None);
+        assert_eq!(job.height, None);
+        assert_eq!(job.foreign_algo(), None);
+    }
+
+    #[test]
+    fn algo_and_height_are_read_when_sent() {
+        let job = job(r#", "algo": "rx/0", "height": 3141592"#);
+        assert_eq!(job.algo.as_deref(), Some("rx/0"));
+        assert_eq!(job.height, Some(3141592));
+    }
+
+    #[test]
+    fn randomx_algo_names_are_not_foreign() {
+        assert_eq!(job(r#", "algo": "rx/0""#).foreign_algo(), None);
+        assert_eq!(job(r#", "algo": "RandomX""#).foreign_algo(), None);
+    }
+
+    #[test]
+    fn other_algos_are_foreign() {
+        assert_eq!(job(r#", "algo": "cn/r""#).foreign_algo(), Some("cn/r"));
+        assert_eq!(job(r#", "algo": "rx/wow""#).foreign_algo(), Some("rx/wow"));
     }
 }
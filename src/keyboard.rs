@@ -0,0 +1,106 @@
+#[cfg(target_os = "linux")]
+use std::sync::Mutex;
+use std::{
+    io::{self, IsTerminal, Read},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// Console-mode key commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// `s`: print the stats report now.
+    Stats,
+    /// `p`: pause or resume hashing.
+    Pause,
+    /// `q`: stop mining and print the summary.
+    Quit,
+}
+
+/// Reads single keys from the terminal while console mode runs. On Linux the
+/// terminal stops waiting for Enter and echoing keys, but output processing is
+/// left alone, so log lines print as usual; elsewhere a key takes effect on Enter.
+/// The terminal is restored when this is dropped, and Ctrl+C shuts down gracefully
+/// meanwhile so that still happens.
+pub struct Keyboard {
+    keys: Receiver<Key>,
+}
+
+// The terminal settings to put back, global so exit paths that skip `Drop`
+// (`process::exit`) can restore them too.
+#[cfg(target_os = "linux")]
+static SAVED_TERMIOS: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+impl Keyboard {
+    /// Starts the reader, or returns `None` when stdin isn't a terminal, so piped
+    /// input and scripts are never read from.
+    pub fn start() -> Option<Self> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(saved) = unbuffer_stdin() {
+            *SAVED_TERMIOS.lock().unwrap() = Some(saved);
+            crate::shutdown::set_graceful_sigint(true);
+        }
+        let (key_tx, keys) = mpsc::channel();
+        thread::spawn(move || {
+            for byte in io::stdin().lock().bytes() {
+                let key = match byte {
+                    Ok(b's' | b'S') => Key::Stats,
+                    Ok(b'p' | b'P') => Key::Pause,
+                    Ok(b'q' | b'Q') => Key::Quit,
+                    Ok(_) => continue,
+                    Err(_) => return,
+                };
+                if key_tx.send(key).is_err() {
+                    return;
+                }
+            }
+        });
+        Some(Self { keys })
+    }
+
+    /// The next key pressed since the last call, without blocking.
+    pub fn try_key(&self) -> Option<Key> {
+        self.keys.try_recv().ok()
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Puts back the terminal settings `Keyboard::start` changed, if it did. Call
+/// before exiting the process without unwinding.
+pub fn restore_terminal() {
+    #[cfg(target_os = "linux")]
+    if let Some(saved) = SAVED_TERMIOS.lock().unwrap().take() {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &saved);
+        }
+        crate::shutdown::set_graceful_sigint(false);
+    }
+}
+
+/// Turns off line buffering and echo on stdin, keeping signals (Ctrl+C) and
+/// output processing. Returns the previous settings to restore.
+#[cfg(target_os = "linux")]
+fn unbuffer_stdin() -> Option<libc::termios> {
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+            return None;
+        }
+        let saved = termios;
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+            return None;
+        }
+        Some(saved)
+    }
+}
@@ -1,3 +1,24 @@
+// Every module below prints through these rather than std's, which panic when
+// stdout or stderr is a closed pipe (`anonminer | head`) or a vanished terminal.
+// See `output`.
+// `print!` and `eprint!` are unused today; defined so future uses can't fall through to std's.
+#[allow(unused_macros)]
+macro_rules! print {
+    ($($arg:tt)*) => { $crate::output::stdout(format_args!($($arg)*), false) };
+}
+macro_rules! println {
+    () => { $crate::output::stdout(format_args!(""), true) };
+    ($($arg:tt)*) => { $crate::output::stdout(format_args!($($arg)*), true) };
+}
+#[allow(unused_macros)]
+macro_rules! eprint {
+    ($($arg:tt)*) => { $crate::output::stderr(format_args!($($arg)*), false) };
+}
+macro_rules! eprintln {
+    () => { $crate::output::stderr(format_args!(""), true) };
+    ($($arg:tt)*) => { $crate::output::stderr(format_args!($($arg)*), true) };
+}
+
 mod display;
 mod gui_data;
 mod hash_rate;
@@ -7,14 +28,51 @@ mod stratum;
 pub mod worker;
 mod gui;
 mod hash_logger;
+mod share_logger;
+mod self_test;
+mod throttle;
+mod stats;
+mod cpu_topology;
+mod affinity;
+mod diagnostics;
+mod solo;
+mod hash_analysis;
+mod benchmark_csv;
+mod api;
+mod donation;
+mod hashrate_alert;
+mod webhook;
+mod color;
+mod validate;
+mod argon2;
+mod shutdown;
+mod target;
+mod dual;
+mod exit_code;
+mod offline;
+mod suspend;
+mod clock;
+mod output;
+mod event_log;
+mod power;
+mod keyboard;
+mod randomx;
+mod dataset_cache;
+#[cfg(test)]
+mod alloc_counter;
 
-use crate::{display::Display, gui_data::GuiData, hash_rate::init_hash_rate_tracker, stratum::Stratum, worker::Worker, gui::Gui};
-use clap::{Parser};
+use crate::{display::{BannerStyle, Display, HashRateUnit}, gui_data::GuiData, hash_rate::{init_hash_rate_tracker, WarmupCriteria}, stratum::{connect::{self, ConnectOptions, HttpProxy}, RejectRecovery, ShareOutcome, Stratum}, worker::{NonceEndian, OnDisconnect, Worker}, keyboard::{Key, Keyboard}, color::ColorWhen, argon2::Argon2Impl, gui::{Gui, GuiCommand}};
+use clap::{Parser, ValueEnum};
+use once_cell::sync::Lazy;
+use serde_json::json;
 use tracing::Level;
-use owo_colors::OwoColorize;
+use crate::color::Colorize;
 use std::{
     io::{self},
+    net::{IpAddr, SocketAddr},
     num::NonZeroUsize,
+    path::PathBuf,
+    process::ExitCode,
     sync::mpsc,
     thread,
     time::{Duration, Instant},
@@ -23,10 +81,9 @@ use std::{
 const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(60);
 const HASH_RATE_REPORT_INTERVAL: Duration = Duration::from_secs(30);
 const INITIAL_WARMUP_DURATION: Duration = Duration::from_secs(45);
+const DEFAULT_DONATE_LEVEL: u8 = 1;
 const DONATION_POOL_URL: &str = "gulf.moneroocean.stream:10032";
 const DONATION_WALLET_ADDRESS: &str = "41p5Kuj5V4qbkxZ6385kFyWgmwFF3EC5FjmL5JyGoVLbi8wSJBFZPi83cAf5moRrkehu8Bk7dtm9UcsT1662U7Wt7vsysCx";
-const CYCLE_DURATION: Duration = Duration::from_secs(100 * 60); // 100 minutes
-const DONATION_START_OFFSET: Duration = Duration::from_secs(50 * 60); // 50 minutes
 
 #[derive(Parser)]
 struct Args {
@@ -43,9 +100,9 @@ struct Args {
     /// Worker name
     #[arg(short, long, default_value = "x")]
     pass: String,
-    /// Number of CPU threads
-    #[arg(short, long)]
-    threads: Option<NonZeroUsize>,
+    /// Number of CPU threads, or a percentage of the CPUs such as 75%
+    #[arg(short, long, value_parser = parse_thread_count)]
+    threads: Option<ThreadCount>,
     /// Switch to light mode
     #[arg(long)]
     light: bool,
@@ -58,20 +115,363 @@ struct Args {
     /// Enable hash value logging without other debug output
     #[arg(long)]
     debug_hash_log: bool,
+    /// Only log every Nth hash to the hash log (shares are always logged)
+    #[arg(long, default_value_t = 1, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    hash_log_sample: u64,
+    /// Size the automatic thread count to all online CPUs even if a cgroup CPU quota allows fewer
+    #[arg(long)]
+    ignore_cgroup_quota: bool,
+    /// Serve a read-only JSON API (/stats) and web dashboard (/) on this address, e.g. 127.0.0.1:8080
+    #[arg(long, value_name = "ADDR:PORT")]
+    api_bind: Option<SocketAddr>,
+    /// Seconds to wait before reconnecting when the pool says it has banned or rate-limited us
+    #[arg(long, default_value_t = 300, value_name = "SECS")]
+    ban_cooldown: u64,
+    /// Order nonces are iterated in (big- or little-endian counting); doesn't affect which shares the pool accepts
+    #[arg(long, value_enum, default_value_t = NonceEndian::Be)]
+    nonce_endian: NonceEndian,
     /// Set the log level (trace, debug, info, warn, error)
     #[arg(long, default_value_t = Level::WARN, value_name = "LEVEL")]
     log_level: Level,
     /// Developer donation level (percentage, minimum 1%)
-    #[arg(long, default_value_t = 1)]
+    #[arg(long, default_value_t = DEFAULT_DONATE_LEVEL)]
     donate_level: u8,
+    /// Append every found share to this file as JSON lines
+    #[arg(long, value_name = "PATH")]
+    shares_file: Option<PathBuf>,
+    /// Verify RandomX against known test vectors before mining
+    #[arg(long)]
+    self_test: bool,
+    /// In light mode, adapt the per-batch sleep to hold system CPU usage near this percentage
+    #[arg(long, value_name = "PERCENT", requires = "light", value_parser = clap::value_parser!(u8).range(1..=100))]
+    target_cpu: Option<u8>,
+    /// Keep a standby connection to the donation pool so donation windows switch without reconnecting
+    #[arg(long)]
+    keep_donation_connection: bool,
+    /// Write tracing output to this file (rotated) instead of stderr
+    #[arg(long, env = "ANONMINER_LOG_FILE", value_name = "PATH")]
+    log_file: Option<PathBuf>,
+    /// How often to start a new log file when --log-file is set
+    #[arg(long, value_enum, default_value_t = LogRotation::Daily, env = "ANONMINER_LOG_ROTATION")]
+    log_rotation: LogRotation,
+    /// Local IP address to make pool connections from (selects the outgoing interface)
+    #[arg(long, value_name = "IP")]
+    bind_address: Option<IpAddr>,
+    /// Log in, send METHOD with PARAMS_JSON, print the raw reply and exit
+    #[arg(long, hide = true, value_name = "METHOD:PARAMS_JSON", value_parser = parse_rpc_probe)]
+    rpc_probe: Option<RpcProbe>,
+    /// Print a single self-rewriting status line instead of the console output (for tmux/status bars)
+    #[arg(long, conflicts_with = "gui")]
+    status_line: bool,
+    /// Keep this many CPU cores (starting at core 0) free for the OS; workers never run on them
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    reserve_cores: usize,
+    /// Only submit shares at or above this difficulty, even if the pool's target is lower
+    #[arg(long, value_name = "DIFFICULTY", value_parser = clap::value_parser!(u64).range(1..))]
+    min_submit_difficulty: Option<u64>,
+    /// Print CPU, memory, huge page, MSR and RandomX diagnostics as JSON and exit
+    #[arg(long)]
+    diagnostics_json: bool,
+    /// Solo mine against a monerod JSON-RPC endpoint (HOST:PORT) instead of a pool; blocks pay to --user
+    #[arg(long, value_name = "HOST:PORT", conflicts_with_all = ["gui", "status_line", "keep_donation_connection"])]
+    solo: Option<String>,
+    /// Summarize a hash log written by --debug-hash-log (hash/target histogram, min hash, shares) and exit
+    #[arg(long, value_name = "PATH")]
+    analyze: Option<PathBuf>,
+    /// Record (elapsed_secs, hashrate) samples every 5 seconds to this CSV file for plotting
+    #[arg(long, value_name = "PATH")]
+    benchmark_csv: Option<PathBuf>,
+    /// Worker name sent to the donation pool during donation windows (defaults to --pass)
+    #[arg(long, value_name = "NAME")]
+    donation_worker: Option<String>,
+    /// Run worker threads with real-time (SCHED_FIFO) priority. Dedicated rigs only: can starve the system. Reserves at least one core
+    #[arg(long)]
+    realtime: bool,
+    /// Queue shares to a sender thread instead of writing them to the pool from the main loop
+    #[arg(long)]
+    async_submit: bool,
+    /// Reconnect when the pool has sent no new job for this many seconds (0 disables)
+    #[arg(long, default_value_t = 600, value_name = "SECS")]
+    job_timeout: u64,
+    /// Local time range (HH:MM-HH:MM) to keep donation out of; the minutes are donated after it instead
+    #[arg(long, value_name = "HH:MM-HH:MM", value_parser = donation::parse_avoid_window)]
+    donate_window_avoid: Option<donation::AvoidWindow>,
+    /// Print the CPU features, huge pages and recommended RandomX flags for this machine and exit
+    #[arg(long)]
+    list_cpu_flags: bool,
+    /// Warn when the last minute's hash rate is this many percent below the 15 minute average
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(1..=99))]
+    alert_drop_percent: Option<u8>,
+    /// POST hash rate alerts as JSON to this http:// URL
+    #[arg(long, value_name = "URL", requires = "alert_drop_percent", value_parser = webhook::parse_webhook)]
+    alert_webhook: Option<webhook::Webhook>,
+    /// POST JSON events (startup, first accepted share, reconnects, stalled hashing, shutdown) to this http:// URL
+    #[arg(long, value_name = "URL", value_parser = webhook::parse_webhook)]
+    webhook: Option<webhook::Webhook>,
+    /// What the workers do while reconnecting to the pool: keep hashing the last job, or pause to save power
+    #[arg(long, value_enum, default_value_t = OnDisconnect::KeepHashing)]
+    on_disconnect: OnDisconnect,
+    /// Idle seconds before TCP keepalive probes start on the pool connection (0 disables)
+    #[arg(long, default_value_t = 60, value_name = "SECS")]
+    tcp_keepalive_secs: u64,
+    /// Append the full hashed blob, nonce, hash and difficulty of every accepted share to this file as JSON lines
+    #[arg(long, value_name = "PATH")]
+    share_blobs_file: Option<PathBuf>,
+    /// When to color console output: auto (only on a terminal, unless NO_COLOR is set), always or never
+    #[arg(long, visible_alias = "color-when", value_enum, default_value_t = ColorWhen::Auto, value_name = "WHEN")]
+    color: ColorWhen,
+    /// Mine on one logical CPU per physical core, skipping SMT (hyperthread) siblings
+    #[arg(long)]
+    physical_only: bool,
+    /// Warn when more than this fraction (0-1) of the last 20 share results were rejected
+    #[arg(long, value_name = "RATIO", value_parser = parse_ratio)]
+    max_reject_ratio: Option<f64>,
+    /// Pool (URL:PORT) to fail over to once when --max-reject-ratio is exceeded; same wallet and worker name
+    #[arg(long, value_name = "URL", requires = "max_reject_ratio")]
+    backup_pool: Option<String>,
+    /// Write the debug hash log gzip-compressed, to hashes.log.gz
+    #[arg(long)]
+    hash_log_gzip: bool,
+    /// Mine on the pool until one share is found, submit it, print the pool's verdict and exit (non-zero if rejected)
+    #[arg(long, conflicts_with = "solo")]
+    validate_pool: bool,
+    /// Argon2 implementation for RandomX cache builds; auto uses what randomx-rs detects
+    #[arg(long, value_enum, default_value_t = Argon2Impl::Auto)]
+    argon2: Argon2Impl,
+    /// Also require this many H/s, sustained over 10s, before warmup ends and hash rate reporting starts
+    #[arg(long, value_name = "H/S")]
+    warmup_min_hashrate: Option<f64>,
+    /// Also require every mining thread to have produced a hash before warmup ends
+    #[arg(long)]
+    warmup_all_threads: bool,
+    /// Append every Stratum line sent and received, timestamped and with direction markers, to this file
+    #[arg(long, value_name = "PATH")]
+    capture_traffic: Option<PathBuf>,
+    /// Keep wallets, login ids, the pool password and the rig id in the --capture-traffic file instead of redacting them
+    #[arg(long, requires = "capture_traffic")]
+    capture_unredacted: bool,
+    /// Mine for a second pool (URL:PORT) at the same time, same wallet and worker: jobs alternate every 30s and each share goes to the pool whose job it solved
+    #[arg(long, value_name = "URL", conflicts_with_all = ["solo", "validate_pool", "backup_pool"])]
+    dual_pool: Option<String>,
+    /// Fixed unit for every displayed hash rate (console, GUI, status line) instead of auto-scaling
+    #[arg(long, value_enum, default_value_t = HashRateUnit::Auto)]
+    hashrate_unit: HashRateUnit,
+    /// Restart a mining thread whose progress stalls for this many seconds while it should be hashing (0 disables)
+    #[arg(long, default_value_t = 120, value_name = "SECS")]
+    thread_stall_timeout: u64,
+    /// Benchmark offline on this RandomX seed hash (64 hex digits) instead of mining on a pool; needs --fixed-blob
+    #[arg(long, value_name = "HEX", value_parser = offline::parse_seed, requires = "fixed_blob", conflicts_with_all = ["solo", "validate_pool", "dual_pool"])]
+    fixed_seed: Option<[u8; 32]>,
+    /// Hashing blob (hex) the --fixed-seed benchmark hashes, with the nonce at byte 39
+    #[arg(long, value_name = "HEX", value_parser = offline::parse_blob, requires = "fixed_seed")]
+    fixed_blob: Option<offline::FixedBlob>,
+    /// Ring the terminal bell on every accepted share (the GUI flashes its status panel instead); silent during warmup
+    #[arg(long)]
+    bell_on_share: bool,
+    /// Give up waiting for the pool's first job after this many seconds and log in again (0 waits forever)
+    #[arg(long, default_value_t = 60, value_name = "SECS")]
+    startup_timeout: u64,
+    /// Logins to retry after --startup-timeout before exiting with an error (0 exits at the first timeout)
+    #[arg(long, default_value_t = 2, value_name = "N")]
+    startup_retries: u32,
+    /// Feed the pool side of a --capture-traffic file through the protocol handling offline, print what it dispatches and exit
+    #[arg(long, value_name = "PATH")]
+    replay: Option<PathBuf>,
+    /// Hashes between checks for a new job, so a new job cuts the current batch of 100 short (values above 100 check once per batch)
+    #[arg(long, default_value_t = worker::BATCH_SIZE, value_name = "HASHES", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    job_poll_interval: usize,
+    /// Send the hash rate to the pool every 60s for its per-worker dashboard, if the pool supports it
+    #[arg(long)]
+    report_hashrate: bool,
+    /// Decimal places in every displayed hash rate (console, GUI, alerts)
+    #[arg(long, default_value_t = 2, value_name = "N", value_parser = clap::value_parser!(u8).range(0..=6))]
+    hashrate_precision: u8,
+    /// Follow each scaled hash rate with the exact H/s value, e.g. "1.23 KH/s (1234.56 H/s)"
+    #[arg(long)]
+    hashrate_raw: bool,
+    /// Startup decoration: auto (full on a terminal, compact otherwise), full, compact (one line) or none
+    #[arg(long, value_enum, default_value_t = BannerStyle::Auto, value_name = "STYLE")]
+    banner: BannerStyle,
+    /// Identifier for this miner instance, sent with the login so the pool can tell instances on one account apart
+    #[arg(long, value_name = "ID")]
+    rig_id: Option<String>,
+    /// Also tag every submitted share with --rig-id; only for pools that accept the extra field
+    #[arg(long, requires = "rig_id")]
+    rig_id_in_submit: bool,
+    /// Append job, share, connection and donation events as JSON lines to this file (e.g. /dev/fd/3), alongside the GUI or console output
+    #[arg(long, value_name = "PATH")]
+    events_file: Option<PathBuf>,
+    /// Drop to light mode (fewer threads, pausing between batches) while on battery power, and back to full speed on AC
+    #[arg(long, conflicts_with = "light")]
+    auto_light_on_battery: bool,
+    /// Also write hashes within this factor of the share target (e.g. 10 for a tenth of the difficulty) to --shares-file, marked "near_miss"
+    #[arg(long, value_name = "FACTOR", value_parser = parse_near_miss_factor, requires = "shares_file")]
+    log_near_misses: Option<f64>,
+    /// Tunnel pool connections through this HTTP proxy with CONNECT
+    #[arg(long, value_name = "HOST:PORT")]
+    http_proxy: Option<String>,
+    /// Basic credentials for --http-proxy
+    #[arg(long, value_name = "USER:PASS", env = "ANONMINER_HTTP_PROXY_AUTH", hide_env_values = true, requires = "http_proxy")]
+    http_proxy_auth: Option<String>,
+    /// Restart the hash rate window after each reconnect, so time spent disconnected doesn't drag the reported rate down
+    #[arg(long)]
+    restart_hashrate_on_reconnect: bool,
+    /// Show the wallet address as a QR code at startup, to check it against a wallet app
+    #[arg(long)]
+    show_wallet_qr: bool,
+    /// Threads that build the RandomX dataset between them, each taking a range of its items (default: all CPUs)
+    #[arg(long, value_name = "N")]
+    dataset_init_threads: Option<NonZeroUsize>,
+    /// Save each built RandomX dataset (about 2 GiB) here and load it on the next start with the same seed, after checking it
+    #[arg(long, value_name = "DIR")]
+    dataset_cache_dir: Option<PathBuf>,
+    /// End a thread's batch of hashes as soon as it finds a share and yield its CPU, so the share is submitted sooner at a small cost in hash rate
+    #[arg(long)]
+    end_batch_on_share: bool,
+}
+
+fn parse_ratio(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(ratio) if (0.0..1.0).contains(&ratio) => Ok(ratio),
+        _ => Err(format!("'{}' is not a ratio from 0 up to (not including) 1", s)),
+    }
+}
+
+fn parse_near_miss_factor(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(factor) if factor > 1.0 && factor.is_finite() => Ok(factor),
+        _ => Err(format!("'{}' is not a factor above 1", s)),
+    }
+}
+
+#[derive(Clone, Debug)]
+struct RpcProbe {
+    method: String,
+    params: serde_json::Value,
+}
+
+fn parse_rpc_probe(s: &str) -> Result<RpcProbe, String> {
+    let (method, params) = s.split_once(':').unwrap_or((s, ""));
+    if method.is_empty() {
+        return Err("method must not be empty".into());
+    }
+    let params = if params.trim().is_empty() {
+        serde_json::Value::Object(Default::default())
+    } else {
+        serde_json::from_str(params).map_err(|e| format!("params are not valid JSON: {}", e))?
+    };
+    Ok(RpcProbe { method: method.into(), params })
+}
+
+/// `--threads` as given: an absolute count or a share of the CPUs.
+#[derive(Clone, Copy, Debug)]
+enum ThreadCount {
+    Count(NonZeroUsize),
+    Percent(u8),
+}
+
+impl ThreadCount {
+    /// Rounds a percentage to the nearest whole thread, keeping at least one.
+    fn resolve(self, cpus: NonZeroUsize) -> NonZeroUsize {
+        match self {
+            ThreadCount::Count(count) => count,
+            ThreadCount::Percent(percent) => {
+                let threads = (cpus.get() * percent as usize + 50) / 100;
+                NonZeroUsize::new(threads.min(cpus.get())).unwrap_or(NonZeroUsize::MIN)
+            }
+        }
+    }
+}
+
+fn parse_thread_count(s: &str) -> Result<ThreadCount, String> {
+    match s.trim().strip_suffix('%') {
+        Some(percent) => match percent.trim().parse::<u8>() {
+            Ok(percent @ 1..=100) => Ok(ThreadCount::Percent(percent)),
+            _ => Err(format!("'{}' is not a percentage between 1% and 100%", s)),
+        },
+        None => s.trim().parse().map(ThreadCount::Count).map_err(|e| format!("'{}' is not a thread count: {}", s, e)),
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+// Rotated log files kept on disk before the oldest is deleted.
+const MAX_LOG_FILES: usize = 7;
+
+fn log_file_appender(path: &std::path::Path, rotation: LogRotation) -> io::Result<tracing_appender::rolling::RollingFileAppender> {
+    use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| std::path::Path::new("."));
+    let prefix = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid log file path: {}", path.display())))?
+        .to_string_lossy()
+        .into_owned();
+    let rotation = match rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+
+    RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(prefix)
+        .max_log_files(MAX_LOG_FILES)
+        .build(dir)
+        .map_err(io::Error::other)
 }
 
 fn all_threads() -> NonZeroUsize {
-    std::thread::available_parallelism().expect("Failed to determine available parallelism")
+    static ALL_THREADS: Lazy<NonZeroUsize> = Lazy::new(|| parallelism_or_fallback(std::thread::available_parallelism()));
+    *ALL_THREADS
+}
+
+/// `available_parallelism` fails under some cgroup setups; mine on one thread
+/// instead of aborting. `--threads` still overrides this.
+fn parallelism_or_fallback(detected: io::Result<NonZeroUsize>) -> NonZeroUsize {
+    detected.unwrap_or_else(|e| {
+        eprintln!(
+            "{} Could not determine the number of CPUs ({}); defaulting to 1 thread. Use --threads to set it.",
+            "⚠️".yellow(),
+            e
+        );
+        NonZeroUsize::MIN
+    })
+}
+
+/// Containers with a CPU quota would otherwise be oversubscribed by an
+/// automatically chosen thread count.
+fn cap_to_cgroup_quota(threads: NonZeroUsize) -> NonZeroUsize {
+    let Some(limit) = cpu_topology::cgroup_cpu_limit() else {
+        return threads;
+    };
+    let detected = cpu_topology::online_cpus().unwrap_or(threads.get());
+    if limit < detected {
+        println!(
+            "{} CPU quota allows {} of {} cores; using at most {} threads (--ignore-cgroup-quota to override)",
+            "ℹ️".cyan(),
+            limit,
+            detected,
+            limit
+        );
+    }
+    threads.min(NonZeroUsize::new(limit).unwrap_or(NonZeroUsize::MIN))
 }
 
-fn light_threads() -> NonZeroUsize {
-    let all = all_threads().get();
+/// Light mode's share of `all` CPUs: what fits the L3 caches, or half.
+fn light_threads(all: NonZeroUsize) -> NonZeroUsize {
+    let all = all.get();
+    let domains = cpu_topology::l3_domains();
+    if let Some(fit) = cpu_topology::cache_fit_threads(&domains) {
+        let threads = fit.min(all);
+        Display::cache_topology(&domains, threads);
+        return NonZeroUsize::new(threads).unwrap();
+    }
     if all == 1 {
         NonZeroUsize::new(1).unwrap()
     } else {
@@ -79,14 +479,162 @@ fn light_threads() -> NonZeroUsize {
     }
 }
 
-fn main() -> io::Result<()> {
+/// Switches the workers for `--auto-light-on-battery`: full speed with the
+/// configured threads on AC, light mode with the light thread count on battery.
+fn apply_power_source(worker: &Worker, source: power::PowerSource, (ac_threads, battery_threads): (usize, usize)) -> String {
+    let (light, requested) = match source {
+        power::PowerSource::Ac => (false, ac_threads),
+        power::PowerSource::Battery => (true, battery_threads),
+    };
+    worker.set_light_mode(light);
+    let threads = worker.set_thread_count(requested);
+    tracing::info!(?source, threads, "Power source changed");
+    event_log::emit("power_source", &json!({ "source": if light { "battery" } else { "ac" }, "threads": threads }));
+    match source {
+        power::PowerSource::Ac => format!("{} On AC power: full speed, {} threads", "🔌".green(), threads),
+        power::PowerSource::Battery => format!("{} On battery: light mode, {} threads", "🔋".yellow(), threads),
+    }
+}
+
+/// Drops the hashes from before a reconnect from the rate window. The window spans
+/// the time since its first sample, so with threads paused while disconnected the
+/// outage would otherwise count as time hashed at zero and dip the rate until it
+/// ages out.
+fn restart_hash_rate_window() {
+    hash_rate::get_hash_rate_tracker().lock().unwrap().restart_window();
+    tracing::debug!("Hash rate window restarted after reconnect");
+}
+
+/// Optional lines appended to the periodic hash rate report.
+fn report_details(worker: &Worker) -> Vec<(&'static str, String)> {
+    let mut details = Vec::new();
+    if stats::is_rebuilding_dataset() {
+        details.push(("Dataset", Display::REBUILDING_DATASET.to_string()));
+    }
+    details.extend([
+        ("Shares", Display::format_luck(stats::accepted_shares(), worker.get_expected_shares())),
+        ("Threads", Display::format_thread_modes(&worker.get_thread_modes())),
+        ("Difficulty", Display::format_difficulty(&stats::difficulty_history())),
+    ]);
+    if let Some(latency) = stats::average_share_latency() {
+        details.push(("Share latency", Display::format_share_latency(latency)));
+    }
+    if let Some(latency) = stats::average_submit_latency() {
+        details.push(("Submit latency", Display::format_submit_latency(latency)));
+    }
+    if let Some(build_time) = worker.get_dataset_build_time() {
+        details.push(("Dataset build", format!("{:.1}s (argon2 {})", build_time.as_secs_f64(), argon2::effective())));
+    }
+    let pools = stats::pool_stats();
+    if pools.len() > 1 {
+        details.push(("Pools", Display::format_pool_stats(&pools)));
+    }
+    let reject_reasons = stats::reject_reasons();
+    if !reject_reasons.is_empty() {
+        details.push(("Rejected", Display::format_reject_reasons(&reject_reasons)));
+    }
+    if stats::resyncs() + stats::relogins() > 0 {
+        details.push(("Recoveries", format!("{} job resyncs, {} re-logins", stats::resyncs(), stats::relogins())));
+    }
+    if stats::thread_restarts() > 0 {
+        details.push(("Thread restarts", stats::thread_restarts().to_string()));
+    }
+    if let Some(target) = throttle::target_percent() {
+        details.push(("Throttle", Display::format_throttle(throttle::batch_sleep(), target)));
+    }
+    details
+}
+
+/// The message a thread panicked with, for logging the panic after `join`.
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panicked".to_string())
+}
+
+/// Why the joined GUI thread failed, or `None` if the user quit it. Quitting the GUI
+/// ends the miner; a GUI that failed only ends the display.
+fn gui_failure(joined: thread::Result<io::Result<()>>) -> Option<String> {
+    match joined {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(panic) => Some(panic_message(&*panic)),
+    }
+}
+
+/// The recent reject ratio, once it is above `max`. Only the user's own pool is
+/// judged; donation shares are not counted against it.
+fn reject_ratio_exceeded(max: Option<f64>, is_donating: bool) -> Option<f64> {
+    let max = max.filter(|_| !is_donating)?;
+    stats::recent_reject_ratio().filter(|&ratio| ratio > max)
+}
+
+/// Makes the pool at `url` the active connection. If a standby connection is held, the
+/// active and standby connections are swapped; otherwise (or if the standby can't be
+/// revived) a fresh login replaces the active one, which becomes the new standby when
+/// `keep_standby` is set.
+fn switch_pool(
+    active: &mut Stratum,
+    standby: &mut Option<Stratum>,
+    keep_standby: bool,
+    url: &str,
+    user: &str,
+    pass: &str,
+    options: &ConnectOptions,
+) -> io::Result<()> {
+    if let Some(other) = standby.as_mut() {
+        if other.is_connected() || other.reconnect().is_ok() {
+            std::mem::swap(active, other);
+            return Ok(());
+        }
+    }
+    let previous = std::mem::replace(active, Stratum::login(url, user, pass, options)?);
+    if keep_standby {
+        *standby = Some(previous);
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{} {}", "❌".red(), e);
+            exit_code::for_error(&e)
+        }
+    }
+}
+
+fn run() -> io::Result<()> {
     let args = Args::parse();
+    color::init(args.color);
+    Display::set_hash_rate_unit(args.hashrate_unit);
+    Display::set_hash_rate_precision(args.hashrate_precision, args.hashrate_raw);
+    Display::set_bell_on_share(args.bell_on_share);
 
-    // Initialize tracing subscriber to write to stderr to avoid interfering with TUI on stdout
-    tracing_subscriber::fmt()
-        .with_writer(std::io::stderr)
-        .with_max_level(args.log_level)
-        .init();
+    // Initialize tracing subscriber to write to stderr to avoid interfering with TUI on stdout,
+    // or to a rotating file for headless rigs. The guard flushes the file writer on exit.
+    let _log_guard = match &args.log_file {
+        Some(path) => {
+            let (writer, guard) = tracing_appender::non_blocking(log_file_appender(path, args.log_rotation)?);
+            tracing_subscriber::fmt()
+                .with_writer(writer)
+                .with_ansi(false)
+                .with_max_level(args.log_level)
+                .init();
+            Some(guard)
+        }
+        None => {
+            tracing_subscriber::fmt()
+                .with_writer(|| output::Stderr)
+                .with_ansi(color::enabled())
+                .with_max_level(args.log_level)
+                .init();
+            None
+        }
+    };
     
     let Args {
         url,
@@ -97,32 +645,467 @@ fn main() -> io::Result<()> {
         gui,
         debug_all,
         debug_hash_log,
-        log_level: _, // log_level is used by tracing_subscriber
+        log_level: _, // log_level, log_file and log_rotation are used by tracing_subscriber
+        log_file: _,
+        log_rotation: _,
         donate_level,
+        shares_file,
+        self_test,
+        target_cpu,
+        keep_donation_connection,
+        bind_address,
+        rpc_probe,
+        status_line,
+        reserve_cores,
+        min_submit_difficulty,
+        diagnostics_json,
+        solo,
+        analyze,
+        benchmark_csv,
+        donation_worker,
+        realtime,
+        async_submit,
+        hash_log_sample,
+        ignore_cgroup_quota,
+        api_bind,
+        ban_cooldown,
+        nonce_endian,
+        job_timeout,
+        donate_window_avoid,
+        list_cpu_flags,
+        alert_drop_percent,
+        alert_webhook,
+        webhook,
+        on_disconnect,
+        tcp_keepalive_secs,
+        share_blobs_file,
+        color: _,
+        physical_only,
+        max_reject_ratio,
+        mut backup_pool,
+        hash_log_gzip,
+        validate_pool,
+        argon2,
+        warmup_min_hashrate,
+        warmup_all_threads,
+        capture_traffic,
+        capture_unredacted,
+        dual_pool,
+        hashrate_unit,
+        thread_stall_timeout,
+        fixed_seed,
+        fixed_blob,
+        bell_on_share,
+        startup_timeout,
+        startup_retries,
+        replay,
+        job_poll_interval,
+        report_hashrate,
+        hashrate_precision,
+        hashrate_raw,
+        banner,
+        rig_id,
+        rig_id_in_submit,
+        events_file,
+        auto_light_on_battery,
+        log_near_misses,
+        http_proxy,
+        http_proxy_auth,
+        restart_hashrate_on_reconnect,
+        show_wallet_qr,
+        dataset_init_threads,
+        dataset_cache_dir,
+        end_batch_on_share,
     } = args;
+    let job_timeout = Duration::from_secs(job_timeout);
+    let ban_cooldown = Duration::from_secs(ban_cooldown);
 
-    let donate_level = donate_level.max(1);
+    let donate_level = if cfg!(feature = "no-donation") {
+        tracing::info!("Donation disabled by the no-donation build feature");
+        println!("{} Donation disabled (built with the no-donation feature)", "ℹ️".cyan());
+        if donate_level != DEFAULT_DONATE_LEVEL {
+            tracing::warn!(donate_level, "--donate-level ignored: donation is disabled at build time");
+            println!("{} --donate-level {} is ignored: this build never donates", "⚠️".yellow(), donate_level);
+        }
+        0
+    } else {
+        donate_level.max(1)
+    };
 
-    let thread_count = if light {
-        threads.unwrap_or_else(light_threads)
+    // The donation pool sees this and the developer's wallet; never the user's wallet.
+    let donation_pass = donation_worker.unwrap_or_else(|| pass.clone());
+    let donation_pass = if !user.is_empty() && donation_pass.contains(user.as_str()) {
+        println!("{} Donation worker name contains your wallet address; sending \"x\" to the donation pool instead.", "⚠️".yellow());
+        "x".to_string()
     } else {
-        threads.unwrap_or_else(all_threads)
+        donation_pass
     };
 
+    if let Some(path) = replay {
+        return stratum::replay::run(&path);
+    }
+
+    if let Some(path) = analyze {
+        Display::hash_log_summary(&hash_analysis::analyze(&path)?);
+        return Ok(());
+    }
+
+    if list_cpu_flags {
+        let recommended = diagnostics::flag_names(randomx_rs::RandomXFlag::get_recommended_flags());
+        Display::cpu_flags(&diagnostics::cpu_features(), diagnostics::huge_page_counts(), &recommended);
+        return Ok(());
+    }
+
+    if diagnostics_json {
+        println!("{}", serde_json::to_string_pretty(&diagnostics::collect()).map_err(io::Error::other)?);
+        return Ok(());
+    }
+
+    let threads = threads.map(|threads| threads.resolve(all_threads()));
+    // With --ignore-cgroup-quota, automatic sizing (light mode included) starts
+    // from every online CPU rather than the quota-capped parallelism.
+    let cpu_count = if ignore_cgroup_quota {
+        cpu_topology::online_cpus().and_then(NonZeroUsize::new).unwrap_or_else(all_threads)
+    } else {
+        all_threads()
+    };
+    let mut thread_count = match threads {
+        Some(threads) => threads,
+        None if ignore_cgroup_quota => {
+            if light {
+                light_threads(cpu_count)
+            } else {
+                cpu_count
+            }
+        }
+        None => cap_to_cgroup_quota(if light { light_threads(cpu_count) } else { cpu_count }),
+    };
+
+    // Real-time workers must never get every core, or nothing else can run.
+    let reserve_cores = if realtime && reserve_cores == 0 {
+        println!("{} --realtime: reserving core 0 for the OS", "ℹ️".cyan());
+        1
+    } else {
+        reserve_cores
+    };
+    if realtime {
+        affinity::enable_realtime();
+    }
+
+    if reserve_cores > 0 {
+        let total_cpus = all_threads().get();
+        let Some(reserved) = affinity::reserve_cores(reserve_cores, total_cpus) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("--reserve-cores {} leaves no cores to mine on ({} available)", reserve_cores, total_cpus),
+            ));
+        };
+        let mining_cpus = total_cpus - reserved.len();
+        if thread_count.get() > mining_cpus {
+            if threads.is_some() {
+                println!("{} --threads {} exceeds the {} unreserved cores; using {}", "⚠️".yellow(), thread_count, mining_cpus, mining_cpus);
+            }
+            thread_count = NonZeroUsize::new(mining_cpus).unwrap();
+        }
+        Display::reserved_cores(&reserved, thread_count.get());
+    }
+
+    if physical_only {
+        match cpu_topology::physical_cores() {
+            Some(cores) => {
+                let mining_cores = affinity::restrict_to(&cores);
+                let Some(mining_cores) = NonZeroUsize::new(mining_cores) else {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "--physical-only leaves no cores to mine on"));
+                };
+                // One thread per physical core unless --threads asked for fewer.
+                thread_count = match threads {
+                    Some(threads) => threads.min(mining_cores),
+                    None => mining_cores,
+                };
+                Display::physical_cores(cores.len(), all_threads().get(), thread_count.get());
+            }
+            None => println!(
+                "{} --physical-only: CPU topology is unavailable; mining on all logical CPUs",
+                "⚠️".yellow()
+            ),
+        }
+    }
+
+    if let Some(ip) = bind_address {
+        connect::check_bind_address(ip)?;
+    }
+    let connect_options = ConnectOptions {
+        bind_address,
+        async_submit,
+        tcp_keepalive: (tcp_keepalive_secs > 0).then(|| Duration::from_secs(tcp_keepalive_secs)),
+        rig_id: rig_id.clone(),
+        tag_shares: rig_id_in_submit,
+        http_proxy: http_proxy.clone().map(|address| HttpProxy { address, credentials: http_proxy_auth.clone() }),
+    };
+    if let Some(path) = &capture_traffic {
+        stratum::capture::init(path, !capture_unredacted)?;
+        Display::traffic_capture(path, !capture_unredacted);
+    }
+
+    if let Some(RpcProbe { method, params }) = rpc_probe {
+        println!("{}", Stratum::probe(&url, &user, &pass, &connect_options, &method, params)?);
+        return Ok(());
+    }
+
+    if argon2.is_available() {
+        argon2::select(argon2);
+    } else {
+        println!("{} --argon2 {}: not supported by this CPU; using auto-detection", "⚠️".yellow(), argon2);
+    }
+    Display::argon2(argon2::effective(), argon2 != Argon2Impl::Auto && argon2.is_available());
+
+    if self_test {
+        Display::self_test_start();
+        self_test::run()?;
+        Display::self_test_passed();
+    }
+
+    worker::set_job_poll_interval(job_poll_interval);
+    worker::set_end_batch_on_share(end_batch_on_share);
+    if let Some(threads) = dataset_init_threads {
+        randomx::set_init_threads(threads.get());
+    }
+    if let Some(dir) = &dataset_cache_dir {
+        dataset_cache::set_dir(dir.clone());
+    }
     worker::enable_huge_pages(thread_count);
     worker::apply_msr_mods();
 
-    Display::banner();
-    Display::startup_info(thread_count.get(), if light { "Light" } else { "Fast" });
-    Display::connection_info(&url, &user);
+    Display::startup(banner, thread_count.get(), if light { "Light" } else { "Fast" });
+    if fixed_seed.is_none() {
+        Display::connection_info(solo.as_deref().unwrap_or(&url), &user);
+        if show_wallet_qr {
+            Display::wallet_qr(&user);
+        }
+    }
+
+    init_hash_rate_tracker(
+        debug_all,
+        WarmupCriteria { min_hash_rate: warmup_min_hashrate, threads: warmup_all_threads.then_some(thread_count.get()) },
+    );
+    stats::mark_started();
+    suspend::start_detector();
+    if debug_all || debug_hash_log {
+        crate::hash_logger::set_sample_interval(hash_log_sample);
+        crate::hash_logger::init_hash_logger(hash_log_gzip);
+    }
+    if let Some(path) = &shares_file {
+        crate::share_logger::init_share_logger(path);
+        if let Some(factor) = log_near_misses {
+            crate::share_logger::set_near_miss_factor(factor);
+        }
+    }
+    if let Some(path) = &events_file {
+        event_log::init(path).map_err(|e| io::Error::new(e.kind(), format!("cannot open events file {}: {}", path.display(), e)))?;
+    }
+    if let Some(path) = &share_blobs_file {
+        crate::share_logger::init_share_blob_logger(path);
+    }
+    if let Some(target) = target_cpu {
+        throttle::start_adaptive(target);
+    }
+    if let Some(path) = &benchmark_csv {
+        let mut flags = argon2::apply(randomx_rs::RandomXFlag::get_recommended_flags());
+        flags.insert(randomx_rs::RandomXFlag::FLAG_LARGE_PAGES | randomx_rs::RandomXFlag::FLAG_FULL_MEM);
+        let header = format!(
+            "anonminer {} benchmark, started {}\nthreads={} mode={} requested_flags={}",
+            env!("CARGO_PKG_VERSION"),
+            chrono::Utc::now().to_rfc3339(),
+            thread_count,
+            if light { "light" } else { "fast" },
+            diagnostics::flag_names(flags).join("+"),
+        );
+        // Pins the results to the epoch they were measured on.
+        let header = match &fixed_seed {
+            Some(seed) => format!("{}\nseed={}", header, hex::encode(seed)),
+            None => header,
+        };
+        benchmark_csv::start(path, &header)?;
+    }
+    // Everything already resolved (thread count after quotas and reservations, defaults
+    // filled in) as served by the API's /config; webhook URLs and the pool password are
+    // only reported as set.
+    let effective_config = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "pool": {
+            "url": solo.as_deref().unwrap_or(&url),
+            "solo": solo.is_some(),
+            "dual_pool": dual_pool,
+            "fixed_seed": fixed_seed.map(hex::encode),
+            "rig_id": rig_id,
+            "user": user,
+            "pass_set": !pass.is_empty(),
+        },
+        "donation": {
+            "level": donate_level,
+            "disabled_at_build": cfg!(feature = "no-donation"),
+            "pool": DONATION_POOL_URL,
+            "worker": donation_pass,
+            "keep_connection": keep_donation_connection,
+            "avoid_window": donate_window_avoid.map(|window| window.to_string()),
+        },
+        "threads": {
+            "count": thread_count.get(),
+            "mode": if light { "light" } else { "fast" },
+            "auto_light_on_battery": auto_light_on_battery,
+            "reserve_cores": reserve_cores,
+            "physical_only": physical_only,
+            "allowed_cpus": affinity::allowed_cpu_count(),
+            "realtime": realtime,
+            "target_cpu": target_cpu,
+            "nonce_endian": nonce_endian.to_possible_value().map(|v| v.get_name().to_string()),
+            "min_submit_difficulty": min_submit_difficulty,
+            "argon2": argon2::effective().to_string(),
+            "job_poll_interval": job_poll_interval,
+            "dataset_init_threads": dataset_init_threads,
+            "dataset_cache_dir": dataset_cache_dir,
+            "end_batch_on_share": end_batch_on_share,
+            "stall_timeout_secs": thread_stall_timeout,
+        },
+        "connection": {
+            "bind_address": bind_address,
+            "http_proxy": http_proxy,
+            "http_proxy_auth": http_proxy_auth.is_some(),
+            "async_submit": async_submit,
+            "rig_id_in_submit": rig_id_in_submit,
+            "tcp_keepalive_secs": tcp_keepalive_secs,
+            "job_timeout_secs": job_timeout.as_secs(),
+            "ban_cooldown_secs": ban_cooldown.as_secs(),
+            "startup_timeout_secs": startup_timeout,
+            "startup_retries": startup_retries,
+            "report_hashrate": report_hashrate,
+            "on_disconnect": on_disconnect.to_possible_value().map(|v| v.get_name().to_string()),
+        },
+        "intervals": {
+            "keep_alive_secs": KEEP_ALIVE_INTERVAL.as_secs(),
+            "hash_rate_report_secs": HASH_RATE_REPORT_INTERVAL.as_secs(),
+            "warmup_secs": INITIAL_WARMUP_DURATION.as_secs(),
+            "warmup_min_hashrate": warmup_min_hashrate,
+            "warmup_all_threads": warmup_all_threads,
+        },
+        "output": {
+            "gui": gui,
+            "status_line": status_line,
+            "shares_file": shares_file,
+            "log_near_misses": log_near_misses,
+            "share_blobs_file": share_blobs_file,
+            "events_file": events_file,
+            "benchmark_csv": benchmark_csv,
+            "debug_all": debug_all,
+            "debug_hash_log": debug_hash_log,
+            "hash_log_sample": hash_log_sample,
+            "hash_log_gzip": hash_log_gzip,
+            "capture_traffic": capture_traffic,
+            "capture_unredacted": capture_unredacted,
+            "hashrate_unit": hashrate_unit.to_possible_value().map(|v| v.get_name().to_string()),
+            "hashrate_precision": hashrate_precision,
+            "hashrate_raw": hashrate_raw,
+            "restart_hashrate_on_reconnect": restart_hashrate_on_reconnect,
+            "banner": banner.to_possible_value().map(|v| v.get_name().to_string()),
+            "show_wallet_qr": show_wallet_qr,
+            "bell_on_share": bell_on_share,
+        },
+        "alerts": {
+            "drop_percent": alert_drop_percent,
+            "alert_webhook": alert_webhook.is_some(),
+            "webhook": webhook.is_some(),
+        },
+        "api_bind": api_bind,
+    });
+    if let Some(webhook) = webhook {
+        webhook::init(webhook, &pass);
+    }
+    webhook::notify(
+        "startup",
+        json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "pool": solo.as_deref().unwrap_or(&url),
+            "threads": thread_count.get(),
+            "mode": if light { "light" } else { "fast" },
+        }),
+    );
+    let alert_rx = alert_drop_percent.map(|percent| hashrate_alert::start(percent, alert_webhook));
+    // Thread counts for full speed on AC and light mode on battery.
+    let power_threads = auto_light_on_battery.then(|| (thread_count.get(), light_threads(cpu_count).min(thread_count).get()));
+    let power_rx = if auto_light_on_battery { power::start_monitor() } else { None };
+    if auto_light_on_battery && power_rx.is_none() {
+        println!("{} --auto-light-on-battery: no battery or power source to watch; mining at full speed", "⚠️".yellow());
+    }
+    if let Some(addr) = api_bind {
+        api::start(addr, effective_config)?;
+        println!("{} Dashboard at http://{}/ (JSON at /stats and /config)", "📊".cyan(), addr);
+        if !addr.ip().is_loopback() {
+            println!("{} --api-bind {} is reachable from the network and has no authentication", "⚠️".yellow(), addr);
+        }
+    }
+
+    // Shared by every mode that keeps mining, so wedged threads get replaced in all of them.
+    let with_stall_monitor = |worker: Worker| {
+        if thread_stall_timeout > 0 {
+            worker.start_stall_monitor(Duration::from_secs(thread_stall_timeout));
+        }
+        worker
+    };
+
+    if let (Some(seed), Some(blob)) = (fixed_seed, fixed_blob) {
+        let result = offline::run(seed, blob, |job| {
+            with_stall_monitor(Worker::init(job, thread_count, !light, debug_all, debug_hash_log, None, nonce_endian))
+        });
+        if debug_all || debug_hash_log {
+            crate::hash_logger::flush_hash_log();
+        }
+        return result;
+    }
+
+    if validate_pool {
+        return validate::run(&url, &user, &pass, &connect_options, thread_count, !light, nonce_endian);
+    }
+
+    if let Some(second) = dual_pool {
+        let donation = dual::Donation {
+            schedule: donation::DonationSchedule::new(donate_level, donate_window_avoid),
+            url: DONATION_POOL_URL,
+            wallet: DONATION_WALLET_ADDRESS,
+            pass: &donation_pass,
+        };
+        let result = dual::run([&url, &second], &user, &pass, &connect_options, donation, |job| {
+            with_stall_monitor(Worker::init(job, thread_count, !light, debug_all, debug_hash_log, min_submit_difficulty, nonce_endian))
+        });
+        if debug_all || debug_hash_log {
+            crate::hash_logger::flush_hash_log();
+        }
+        return result;
+    }
+
+    if let Some(daemon) = solo {
+        let result = solo::run(&daemon, &user, &connect_options, |job| {
+            with_stall_monitor(Worker::init(job, thread_count, !light, debug_all, debug_hash_log, None, NonceEndian::default()))
+        });
+        if debug_all || debug_hash_log {
+            crate::hash_logger::flush_hash_log();
+        }
+        return result;
+    }
 
-    let original_url = url.clone();
+    let mut original_url = url.clone();
     let original_user = user.clone();
 
-    let mut stratum = Stratum::login(&url, &user, &pass)?;
-    // We need to wait for the first job to initialize the worker
+
+    let mut stratum = Stratum::login(&url, &user, &pass, &connect_options)?;
+    if let Ok(peer) = stratum.peer_addr() {
+        Display::pool_endpoint(&peer);
+    }
+    // We need to wait for the first job to initialize the worker. A pool that accepts
+    // the login but never sends one gets logged in to again, then given up on.
+    let mut waiting_since = Instant::now();
+    let mut startup_attempts = 0;
     let initial_job = loop {
-        if let Ok(job) = stratum.try_recv_job() {
+        if let Ok(job) = stratum.recv_job_timeout(Duration::from_millis(100)) {
             if debug_all {
                 let job_id_int = u64::from_str_radix(&job.id, 16).unwrap_or(0);
                 eprintln!("DEBUG: Initial job received, id={} (0x{}), blob length: {}, seed length: {}", 
@@ -130,39 +1113,69 @@ fn main() -> io::Result<()> {
             }
             break job;
         }
-        std::thread::sleep(Duration::from_millis(100)); // Wait a bit for the job
+        if startup_timeout == 0 || waiting_since.elapsed() < Duration::from_secs(startup_timeout) {
+            continue;
+        }
+        if startup_attempts >= startup_retries {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} accepted the login but sent no job within {}s ({} retries); check the pool address and port", url, startup_timeout, startup_retries),
+            ));
+        }
+        startup_attempts += 1;
+        tracing::warn!(url = %url, startup_timeout, "No job after login, logging in again");
+        eprintln!("{} No job from {} within {}s of login; logging in again ({}/{})", "⚠️".yellow(), url, startup_timeout, startup_attempts, startup_retries);
+        if let Err(e) = stratum.reconnect() {
+            eprintln!("{} Login retry failed: {}", "❌".red(), e);
+        }
+        waiting_since = Instant::now();
     };
 
-    init_hash_rate_tracker(debug_all);
-    if debug_all || debug_hash_log {
-        crate::hash_logger::init_hash_logger();
-    }
-    let worker = Worker::init(initial_job, thread_count, !light, debug_all, debug_hash_log);
+    // The standby always holds the pool that is *not* currently active; `switch_pool` swaps them.
+    let mut standby_stratum = if keep_donation_connection && donate_level > 0 {
+        match Stratum::login(DONATION_POOL_URL, DONATION_WALLET_ADDRESS, &donation_pass, &connect_options) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                eprintln!("{} Could not open standby donation connection ({}); will connect at switch time.", "⚠️".yellow(), e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let worker = with_stall_monitor(Worker::init(initial_job, thread_count, !light, debug_all, debug_hash_log, min_submit_difficulty, nonce_endian));
+    tracing::info!(?on_disconnect, "Worker behaviour while reconnecting");
+    shutdown::install_sigterm_handler();
     
     let mut keep_alive_timer = Instant::now();
     let mut hash_rate_timer = Instant::now();
     let mut share_count = 0;
-    let cycle_start_time = Instant::now();
+    let mut donation_schedule = donation::DonationSchedule::new(donate_level, donate_window_avoid);
+    let mut reconnect_backoff = stratum::ReconnectBackoff::new(ban_cooldown);
+    // Which pool `stratum` is connected to; set as soon as a switch connects so a
+    // reconnect (which always goes back to `stratum`'s own pool) keeps it accurate.
     let mut is_donating = false;
+    // Set when a pool-switch job wait swallowed the listener's reconnect signal.
+    let mut reconnect_pending = false;
+    let mut thread_modes_reported = false;
+    let mut was_rebuilding_dataset = false;
+    // Set when mining cannot go on, e.g. every thread failed to set up RandomX; returned after cleanup.
+    let mut fatal: Option<io::Error> = None;
 
     println!("{} {}", "🚀".green(), "Mining started!".green().bold());
     println!("{} {}", "🔥".yellow(), "Warming up, starting mining...".yellow());
     println!();
 
+    let mut run_console = !gui;
     if gui {
         // Create channels for sending logs and data to the GUI thread
         let (log_tx, log_rx) = mpsc::channel::<String>();
         let (gui_data_tx, gui_data_rx) = mpsc::channel::<GuiData>();
+        let (command_tx, command_rx) = mpsc::channel::<GuiCommand>();
 
         // Spawn the GUI thread
-        let gui_handle = thread::spawn(move || {
-            let mut gui_app = Gui::new(log_rx, gui_data_rx);
-            if let Err(e) = gui_app.run() {
-                // This eprintln will go to the actual stderr, as it's outside the redirected scope.
-                // It's useful for debugging GUI crashes.
-                eprintln!("GUI thread exited with error: {}", e);
-            }
-        });
+        let gui_handle = thread::spawn(move || Gui::new(log_rx, gui_data_rx, command_tx).run());
 
         // Send initial messages to GUI log
         let _ = log_tx.send(format!("{} {}", "🚀".green(), "Mining started!".green().bold()));
@@ -171,18 +1184,50 @@ fn main() -> io::Result<()> {
 
         let mut last_gui_data_send = Instant::now();
         const GUI_DATA_SEND_INTERVAL: Duration = Duration::from_millis(500); // Update GUI stats 2 times per second
+        let mut gui_data = GuiData::default();
+        // A share that arrived while the loop was idling; submitted on the next pass.
+        let mut found_share = None;
 
         loop {
+            if shutdown::requested() {
+                let _ = log_tx.send(Display::SHUTDOWN_REQUESTED.to_string());
+                break;
+            }
             // --- Mining Logic (adapted from console mode) ---
-            if let Ok(_) = stratum.try_reconnect_signal() {
-                let _ = log_tx.send(format!("{} Connection lost. Attempting to reconnect...", "⚠️".red()));
+            stats::set_active_pool(stratum.is_connected().then(|| stratum.url()));
+            while let Some(alert) = alert_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+                let message = Display::format_hash_rate_alert(&alert);
+                let _ = log_tx.send(message.clone());
+                gui_data.hash_rate_alert = matches!(alert, hashrate_alert::HashRateAlert::Dropped { .. }).then_some(message);
+            }
+            while let Some(source) = power_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+                let _ = log_tx.send(apply_power_source(&worker, source, power_threads.unwrap()));
+            }
+            if stratum.job_timeout_expired(job_timeout) {
+                let _ = log_tx.send(format!("{} No new job from the pool in {}s; the connection looks stalled, reconnecting", "⏱".yellow(), job_timeout.as_secs()));
+                reconnect_pending = true;
+            }
+            if reconnect_pending || stratum.try_reconnect_signal().is_ok() {
+                reconnect_pending = false;
+                let _ = log_tx.send(Display::format_connection_lost(on_disconnect));
+                event_log::emit("disconnected", &json!({ "pool": stratum.url() }));
+                worker.set_suspended(on_disconnect == OnDisconnect::Pause);
+                gui_data.is_reconnecting = true;
+                let _ = gui_data_tx.send(gui_data.clone());
                 loop {
+                    if shutdown::requested() {
+                        break;
+                    }
                     match stratum.reconnect() {
                         Ok(()) => {
                             let _ = log_tx.send(format!("{} Reconnected successfully! Waiting for new job...", "✅".green()));
+                            webhook::notify("reconnected", json!({ "pool": stratum.url() }));
                             // Wait for the first job after reconnection to ensure worker state is synced
                             let mut new_job_after_reconnect: Option<crate::job::Job> = None;
                             'job_wait_loop: loop {
+                                if shutdown::requested() {
+                                    break 'job_wait_loop;
+                                }
                                 if let Ok(job) = stratum.try_recv_job() {
                                     let _ = log_tx.send(format!("New job received after reconnect: {}", job.id));
                                     new_job_after_reconnect = Some(job);
@@ -197,21 +1242,65 @@ fn main() -> io::Result<()> {
                             }
 
                             if let Some(job_to_work) = new_job_after_reconnect {
+                                let stale = worker.discard_pending_shares() + usize::from(found_share.take().is_some());
+                                if stale > 0 {
+                                    let _ = log_tx.send(format!("Discarded {} share(s) found while disconnected", stale));
+                                }
                                 worker.work(job_to_work);
+                                worker.set_suspended(false);
+                                if restart_hashrate_on_reconnect {
+                                    restart_hash_rate_window();
+                                }
                                 break; // Break out of the reconnection loop only if job was received
                             }
                             // If new_job_after_reconnect is None, it means we broke due to another reconnect signal.
                             // The outer loop's `match stratum.reconnect()` will run again.
                         }
                         Err(e) => {
-                            let _ = log_tx.send(format!("{} Reconnection failed: {}. Retrying in 5 seconds...", "❌".red(), e));
-                            std::thread::sleep(Duration::from_secs(5));
+                            let delay = reconnect_backoff.failed(&e);
+                            if stratum::is_ban(&e) {
+                                let _ = log_tx.send(Display::format_pool_ban(&e, delay));
+                            } else {
+                                let _ = log_tx.send(format!("{} Reconnection failed: {}. Retrying in {} seconds...", "❌".red(), e, delay.as_secs()));
+                            }
+                            reconnect_backoff.wait();
                         }
                     }
                 }
             }
 
+            let rebuilding_dataset = stats::is_rebuilding_dataset();
+            if rebuilding_dataset != was_rebuilding_dataset {
+                was_rebuilding_dataset = rebuilding_dataset;
+                let _ = log_tx.send(Display::format_dataset_rebuild(rebuilding_dataset));
+            }
+
+            if !thread_modes_reported {
+                let modes = worker.get_thread_modes();
+                if !modes.contains(&worker::ThreadMode::Starting) {
+                    if let Some(e) = all_threads_failed(&modes) {
+                        fatal = Some(e);
+                        // Also stops the GUI thread, so the join below returns.
+                        shutdown::request();
+                        break;
+                    }
+                    let _ = log_tx.send(Display::format_thread_modes(&modes));
+                    if let Some(build_time) = worker.get_dataset_build_time() {
+                        let _ = log_tx.send(Display::format_dataset_build_time(build_time));
+                    }
+                    let degraded = Display::cache_only_threads(&modes);
+                    if degraded > 0 {
+                        let _ = log_tx.send(format!("{} {} thread(s) could not allocate a dataset and are hashing cache-only at reduced speed", "⚠️".yellow(), degraded));
+                    }
+                    thread_modes_reported = true;
+                }
+            }
+
             if let Ok(job) = stratum.try_recv_job() {
+                event_log::emit("job", &json!({ "id": job.id, "difficulty": job.pool_difficulty(), "height": job.height }));
+                if let Some(height) = job.height.filter(|&height| worker.current_job().height != Some(height)) {
+                    let _ = log_tx.send(format!("New block at height {}", height));
+                }
                 let _ = log_tx.send(format!("New job received: {}", job.id));
                 if debug_all {
                     let job_id_int = u64::from_str_radix(&job.id, 16).unwrap_or(0);
@@ -222,11 +1311,72 @@ fn main() -> io::Result<()> {
                 worker.work(job);
             }
             
-            if let Ok(share) = worker.try_recv_share() {
-                share_count += 1;
-                let _ = log_tx.send(format!("Share #{} found for job {}", share_count, share.job_id));
-                if let Err(e) = stratum.submit(share) {
-                     let _ = log_tx.send(format!("Failed to submit share: {}", e));
+            if let Some(share) = found_share.take().or_else(|| worker.try_recv_share().ok()) {
+                match stratum.submit(&share) {
+                    Ok(()) => {
+                        share_count += 1;
+                        crate::share_logger::log_share(&share, "submitted", None);
+                        let _ = log_tx.send(format!("Share #{} found for job {}", share_count, share.job_id));
+                    }
+                    Err(e) => {
+                        crate::share_logger::log_share(&share, "not_submitted", Some(&e.to_string()));
+                        let _ = log_tx.send(format!("{} Share for job {} not submitted: {}", "⚠️".yellow(), share.job_id, e));
+                    }
+                }
+            }
+
+            while let Ok(result) = stratum.try_recv_share_result() {
+                stats::record_share_result(&result.outcome);
+                match &result.outcome {
+                    ShareOutcome::Accepted => {
+                        stats::record_share_latency(result.latency);
+                        crate::share_logger::log_share(&result.share, "accepted", None);
+                        let _ = log_tx.send(format!("Share for job {} accepted ({} accepted)", result.share.job_id, stats::accepted_shares()));
+                        if stats::accepted_shares() == 1 {
+                            if let Some(after) = stats::time_to_first_share() {
+                                let _ = log_tx.send(Display::format_first_share(after));
+                                webhook::notify("first_share", json!({ "after_secs": after.as_secs() }));
+                            }
+                        }
+                    }
+                    ShareOutcome::Rejected(reason) => {
+                        crate::share_logger::log_share(&result.share, "rejected", Some(reason));
+                        let _ = log_tx.send(format!("{} Share for job {} rejected: {}", "❌".red(), result.share.job_id, reason));
+                    }
+                }
+                let recovery = result.outcome.recovery();
+                stats::record_recovery(recovery);
+                match recovery {
+                    RejectRecovery::None => {}
+                    RejectRecovery::Resync => {
+                        let _ = log_tx.send("Pool lost track of our job; requesting a fresh one".to_string());
+                        if let Err(e) = stratum.request_job() {
+                            let _ = log_tx.send(format!("Job resync request failed: {}", e));
+                        }
+                    }
+                    RejectRecovery::Relogin => {
+                        let _ = log_tx.send("Pool no longer recognizes our login; logging in again".to_string());
+                        reconnect_pending = true;
+                    }
+                }
+            }
+
+            if let Some(ratio) = reject_ratio_exceeded(max_reject_ratio, is_donating) {
+                stats::clear_recent_rejects();
+                let _ = log_tx.send(Display::format_reject_ratio(ratio, max_reject_ratio.unwrap_or_default(), stratum.url()));
+                webhook::notify("reject_ratio", json!({ "pool": stratum.url(), "ratio": ratio }));
+                if let Some(backup) = backup_pool.take() {
+                    let _ = log_tx.send(format!("{} Failing over to backup pool {}", "🔀".yellow(), backup));
+                    match Stratum::login(&backup, &original_user, &pass, &connect_options) {
+                        Ok(backup_stratum) => {
+                            stratum = backup_stratum;
+                            original_url = backup;
+                            worker.discard_pending_shares(); // Shares for the rejecting pool's job
+                        }
+                        Err(e) => {
+                            let _ = log_tx.send(format!("{} Backup pool login failed: {}; staying on {}", "❌".red(), e, stratum.url()));
+                        }
+                    }
                 }
             }
             
@@ -235,36 +1385,46 @@ fn main() -> io::Result<()> {
                 if let Err(e) = stratum.keep_alive() {
                     let _ = log_tx.send(format!("Keep alive failed: {}", e));
                 }
+                if let Some(standby) = standby_stratum.as_mut() {
+                    if let Err(e) = standby.keep_alive() {
+                        let _ = log_tx.send(format!("Standby keep alive failed: {}", e));
+                    }
+                }
+                if report_hashrate && worker.is_warmed_up() {
+                    if let Err(e) = stratum.report_hash_rate(worker.get_hash_rate()) {
+                        let _ = log_tx.send(format!("Hash rate report failed: {}", e));
+                    }
+                }
             }
             
             if hash_rate_timer.elapsed() >= HASH_RATE_REPORT_INTERVAL {
                 hash_rate_timer = Instant::now();
                 let elapsed = worker.get_elapsed_time();
                 
-                if elapsed >= INITIAL_WARMUP_DURATION {
+                if worker.is_warmed_up() {
                     let hash_rate = worker.get_hash_rate();
-                    let report = Display::format_hash_rate_report(hash_rate, elapsed);
+                    let report = Display::format_hash_rate_report(hash_rate, elapsed, &report_details(&worker));
                     let _ = log_tx.send(report);
                 }
             }
 
-            let elapsed_total = cycle_start_time.elapsed();
-            let current_cycle_time = elapsed_total.as_secs() % CYCLE_DURATION.as_secs();
-            let donation_duration = Duration::from_secs(donate_level as u64 * 60);
-
-            let should_be_donating = current_cycle_time >= DONATION_START_OFFSET.as_secs() &&
-                                     current_cycle_time < (DONATION_START_OFFSET + donation_duration).as_secs();
+            let should_be_donating = donation_schedule.should_donate();
 
             if should_be_donating && !is_donating {
                 let msg = format!("{} Switching to donation pool...", "🎁".purple());
                 let _ = log_tx.send(msg);
-                match Stratum::login(DONATION_POOL_URL, DONATION_WALLET_ADDRESS, &pass) {
-                    Ok(s) => {
-                        stratum = s;
+                match switch_pool(&mut stratum, &mut standby_stratum, keep_donation_connection, DONATION_POOL_URL, DONATION_WALLET_ADDRESS, &donation_pass, &connect_options) {
+                    Ok(()) => {
                         let _ = log_tx.send(format!("{} Connected to donation pool. Waiting for new job...", "✅".purple()));
+                        is_donating = true;
+                        donation_schedule.start_donating();
+                        event_log::emit("donation_started", &json!({ "pool": DONATION_POOL_URL }));
                         // Wait for the first job from the donation pool
                         let mut donation_job: Option<crate::job::Job> = None;
                         'donation_job_wait_loop: loop {
+                            if shutdown::requested() {
+                                break 'donation_job_wait_loop;
+                            }
                             if let Ok(job) = stratum.try_recv_job() {
                                 let _ = log_tx.send(format!("New job received from donation pool: {}", job.id));
                                 donation_job = Some(job);
@@ -272,15 +1432,16 @@ fn main() -> io::Result<()> {
                             }
                             // Check for reconnect signal while waiting for the job
                             if stratum.try_reconnect_signal().is_ok() {
-                                let _ = log_tx.send(format!("{} Reconnect signal while waiting for donation job. Aborting donation switch.", "⚠️".yellow()));
+                                let _ = log_tx.send(format!("{} Reconnect signal while waiting for donation job. Reconnecting to donation pool.", "⚠️".yellow()));
+                                reconnect_pending = true;
                                 break 'donation_job_wait_loop;
                             }
                             thread::sleep(Duration::from_millis(100));
                         }
                         if let Some(job_to_work) = donation_job {
+                            worker.discard_pending_shares(); // Shares for the previous pool's job
                             worker.work(job_to_work);
-                            is_donating = true; // Only set is_donating to true if job was received
-                        } // If donation_job is None, it means we broke due to reconnect signal, is_donating remains false
+                        }
                     },
                     Err(e) => {
                         let _ = log_tx.send(format!("Failed to connect to donation pool: {}", e));
@@ -289,13 +1450,18 @@ fn main() -> io::Result<()> {
             } else if !should_be_donating && is_donating {
                 let msg = format!("{} Switching back to original pool...", "🏡".blue());
                 let _ = log_tx.send(msg);
-                 match Stratum::login(&original_url, &original_user, &pass) {
-                    Ok(s) => {
-                        stratum = s;
+                 match switch_pool(&mut stratum, &mut standby_stratum, keep_donation_connection, &original_url, &original_user, &pass, &connect_options) {
+                    Ok(()) => {
                         let _ = log_tx.send(format!("{} Reconnected to original pool. Waiting for new job...", "✅".blue()));
+                        is_donating = false;
+                        donation_schedule.stop_donating();
+                        event_log::emit("donation_finished", &json!({ "pool": stratum.url() }));
                         // Wait for the first job from the original pool
                         let mut original_job_after_donation: Option<crate::job::Job> = None;
                         'original_job_wait_loop: loop {
+                            if shutdown::requested() {
+                                break 'original_job_wait_loop;
+                            }
                             if let Ok(job) = stratum.try_recv_job() {
                                 let _ = log_tx.send(format!("New job received from original pool: {}", job.id));
                                 original_job_after_donation = Some(job);
@@ -303,15 +1469,16 @@ fn main() -> io::Result<()> {
                             }
                             // Check for reconnect signal while waiting for the job
                             if stratum.try_reconnect_signal().is_ok() {
-                                let _ = log_tx.send(format!("{} Reconnect signal while waiting for original job. Aborting pool switch.", "⚠️".yellow()));
+                                let _ = log_tx.send(format!("{} Reconnect signal while waiting for original job. Reconnecting to original pool.", "⚠️".yellow()));
+                                reconnect_pending = true;
                                 break 'original_job_wait_loop;
                             }
                             thread::sleep(Duration::from_millis(100));
                         }
                         if let Some(job_to_work) = original_job_after_donation {
+                            worker.discard_pending_shares(); // Shares for the previous pool's job
                             worker.work(job_to_work);
-                            is_donating = false; // Only set is_donating to false if job was received
-                        } // If original_job_after_donation is None, it means we broke due to reconnect signal, is_donating remains true
+                        }
                     },
                     Err(e) => {
                         let _ = log_tx.send(format!("Failed to reconnect to original pool: {}", e));
@@ -319,47 +1486,131 @@ fn main() -> io::Result<()> {
                 }
             }
 
+            let mut threads_changed = false;
+            while let Ok(command) = command_rx.try_recv() {
+                let current = worker.get_thread_count();
+                let requested = match command {
+                    GuiCommand::AddThread => current + 1,
+                    GuiCommand::RemoveThread => current.saturating_sub(1),
+                };
+                let count = worker.set_thread_count(requested);
+                if count != current {
+                    let _ = log_tx.send(format!("Worker threads: {} -> {}", current, count));
+                    threads_changed = true;
+                }
+            }
+
             // --- Send data to GUI ---
-            if last_gui_data_send.elapsed() >= GUI_DATA_SEND_INTERVAL {
+            if threads_changed || last_gui_data_send.elapsed() >= GUI_DATA_SEND_INTERVAL {
                 last_gui_data_send = Instant::now();
                 let elapsed = worker.get_elapsed_time();
-                let gui_data = GuiData {
-                    hash_rate: worker.get_hash_rate(),
-                    total_hashes: worker.get_total_hashes(),
-                    elapsed_time: elapsed,
-                    shares_found: share_count as usize, // Cast u64 to usize
-                    is_warming_up: elapsed < INITIAL_WARMUP_DURATION,
-                };
-                if gui_data_tx.send(gui_data).is_err() {
-                    let _ = log_tx.send("GUI data channel closed. Mining loop will exit.".to_string());
+                gui_data.hash_rate = worker.get_hash_rate();
+                gui_data.total_hashes = worker.get_total_hashes();
+                gui_data.elapsed_time = elapsed;
+                gui_data.shares_found = share_count as usize; // Cast u64 to usize
+                gui_data.shares_accepted = stats::accepted_shares();
+                gui_data.shares_rejected = stats::rejected_shares();
+                gui_data.luck = Display::format_luck(gui_data.shares_accepted, worker.get_expected_shares());
+                gui_data.thread_modes = Display::format_thread_modes(&worker.get_thread_modes());
+                gui_data.thread_count = worker.get_thread_count();
+                gui_data.difficulty = Display::format_difficulty(&stats::difficulty_history());
+                gui_data.share_latency = stats::average_share_latency().map(Display::format_share_latency);
+                gui_data.first_share = stats::time_to_first_share();
+                let job = worker.current_job();
+                gui_data.job_id = job.id.clone();
+                gui_data.job_difficulty = job.pool_difficulty();
+                gui_data.job_height = job.height;
+                gui_data.seed_hash = hex::encode(&job.seed[..job.seed.len().min(8)]);
+                gui_data.seed_changing = stats::is_rebuilding_dataset();
+                gui_data.is_warming_up = !worker.is_warmed_up();
+                gui_data.is_reconnecting = !stratum.is_connected();
+                gui_data.throttle = throttle::target_percent()
+                    .map(|target| Display::format_throttle(throttle::batch_sleep(), target));
+                if gui_data_tx.send(gui_data.clone()).is_err() {
                     break;
                 }
             }
 
             // Check if GUI thread is still alive
             if gui_handle.is_finished() {
-                let _ = log_tx.send("GUI thread has terminated. Mining loop will exit.".to_string());
                 break; 
             }
             
-            thread::sleep(Duration::from_millis(10)); // Small sleep to prevent busy loop
+            // Idle until the next pass, but wake for a share so it goes out without waiting.
+            found_share = worker.recv_share_timeout(Duration::from_millis(10));
         }
         
-        // Wait for the GUI thread to finish
-        let _ = gui_handle.join();
+        if let Some(reason) = gui_failure(gui_handle.join()) {
+            eprintln!("{} GUI failed ({}); continuing to mine in console mode", "⚠️".yellow(), reason);
+            tracing::error!("GUI failed: {}", reason);
+            run_console = fatal.is_none();
+        }
+    }
 
-    } else {
+    if run_console {
         // Run console mode
+        let keyboard = Keyboard::start();
+        if keyboard.is_some() {
+            println!("{} Keys: {} stats, {} pause/resume, {} quit", "⌨".cyan(), "s".bold(), "p".bold(), "q".bold());
+        }
+        let mut paused = false;
         loop {
-            if let Ok(_) = stratum.try_reconnect_signal() {
-                println!("{} Connection lost. Attempting to reconnect...", "⚠️".red());
+            if shutdown::requested() {
+                println!("{}", Display::SHUTDOWN_REQUESTED);
+                break;
+            }
+            match keyboard.as_ref().and_then(Keyboard::try_key) {
+                Some(Key::Stats) => {
+                    let mut details = report_details(&worker);
+                    details.insert(0, ("Pool", stratum.url().to_string()));
+                    Display::hash_rate_report(worker.get_hash_rate(), worker.get_elapsed_time(), &details);
+                }
+                Some(Key::Pause) => {
+                    paused = !paused;
+                    worker.set_suspended(paused);
+                    tracing::info!(paused, "Mining paused or resumed from the keyboard");
+                    if paused {
+                        println!("{} Paused; press {} to resume", "⏸".yellow(), "p".bold());
+                    } else {
+                        println!("{} Resumed", "▶".green());
+                    }
+                }
+                Some(Key::Quit) => {
+                    println!("{} Quitting…", "🛑".red());
+                    break;
+                }
+                None => {}
+            }
+            stats::set_active_pool(stratum.is_connected().then(|| stratum.url()));
+            while let Some(alert) = alert_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+                println!("{}", Display::format_hash_rate_alert(&alert));
+            }
+            while let Some(source) = power_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+                println!("{}", apply_power_source(&worker, source, power_threads.unwrap()));
+            }
+            if stratum.job_timeout_expired(job_timeout) {
+                println!("{} No new job from the pool in {}s; the connection looks stalled, reconnecting", "⏱".yellow(), job_timeout.as_secs());
+                reconnect_pending = true;
+            }
+            if reconnect_pending || stratum.try_reconnect_signal().is_ok() {
+                reconnect_pending = false;
+                println!("{}", Display::format_connection_lost(on_disconnect));
+                event_log::emit("disconnected", &json!({ "pool": stratum.url() }));
+                worker.set_suspended(paused || on_disconnect == OnDisconnect::Pause);
                 loop {
+                    if shutdown::requested() {
+                        break;
+                    }
                     match stratum.reconnect() {
                         Ok(()) => {
                             println!("{} Reconnected successfully! Waiting for new job...", "✅".green());
+                            webhook::notify("reconnected", json!({ "pool": stratum.url() }));
                             // Wait for the first job after reconnection to ensure worker state is synced
                             let mut new_job_after_reconnect: Option<crate::job::Job> = None;
                             'console_job_wait_loop: loop {
+                                if shutdown::requested() {
+                                    break 'console_job_wait_loop;
+                                }
                                 if let Ok(job) = stratum.try_recv_job() {
                                     println!("New job received after reconnect: {}", job.id);
                                     new_job_after_reconnect = Some(job);
@@ -374,22 +1625,66 @@ fn main() -> io::Result<()> {
                             }
 
                             if let Some(job_to_work) = new_job_after_reconnect {
+                                let stale = worker.discard_pending_shares();
+                                if stale > 0 {
+                                    println!("Discarded {} share(s) found while disconnected", stale);
+                                }
                                 worker.work(job_to_work);
+                                worker.set_suspended(paused);
+                                if restart_hashrate_on_reconnect {
+                                    restart_hash_rate_window();
+                                }
                                 break; // Break out of the reconnection loop only if job was received
                             }
                             // If new_job_after_reconnect is None, it means we broke due to another reconnect signal.
                             // The outer loop's `match stratum.reconnect()` will run again.
                         }
                         Err(e) => {
-                            eprintln!("{} Reconnection failed: {}. Retrying in 5 seconds...", "❌".red(), e);
-                            std::thread::sleep(Duration::from_secs(5));
+                            let delay = reconnect_backoff.failed(&e);
+                            if stratum::is_ban(&e) {
+                                eprintln!("{}", Display::format_pool_ban(&e, delay));
+                            } else {
+                                eprintln!("{} Reconnection failed: {}. Retrying in {} seconds...", "❌".red(), e, delay.as_secs());
+                            }
+                            reconnect_backoff.wait();
+                        }
+                    }
+                }
+            }
+
+            let rebuilding_dataset = stats::is_rebuilding_dataset();
+            if rebuilding_dataset != was_rebuilding_dataset {
+                was_rebuilding_dataset = rebuilding_dataset;
+                if !status_line {
+                    println!("{}", Display::format_dataset_rebuild(rebuilding_dataset));
+                }
+            }
+
+            if !thread_modes_reported {
+                let modes = worker.get_thread_modes();
+                if !modes.contains(&worker::ThreadMode::Starting) {
+                    if let Some(e) = all_threads_failed(&modes) {
+                        fatal = Some(e);
+                        break;
+                    }
+                    if !status_line {
+                        Display::thread_modes(&modes);
+                        if let Some(build_time) = worker.get_dataset_build_time() {
+                            Display::dataset_build_time(build_time);
                         }
                     }
+                    thread_modes_reported = true;
                 }
             }
 
             if let Ok(job) = stratum.try_recv_job() {
-                Display::job_received(&job.id);
+                event_log::emit("job", &json!({ "id": job.id, "difficulty": job.pool_difficulty(), "height": job.height }));
+                if !status_line {
+                    if let Some(height) = job.height.filter(|&height| worker.current_job().height != Some(height)) {
+                        Display::new_block(height);
+                    }
+                    Display::job_received(&job.id);
+                }
                 if debug_all {
                     let job_id_int = u64::from_str_radix(&job.id, 16).unwrap_or(0);
                     eprintln!("DEBUG: Received new job: id={} (0x{}), blob_len={}, seed_len={}", 
@@ -399,43 +1694,128 @@ fn main() -> io::Result<()> {
             }
             
             if let Ok(share) = worker.try_recv_share() {
-                share_count += 1;
-                Display::share_found(&share.job_id, share_count);
-                stratum.submit(share)?;
+                match stratum.submit(&share) {
+                    Ok(()) => {
+                        share_count += 1;
+                        crate::share_logger::log_share(&share, "submitted", None);
+                        if !status_line {
+                            Display::share_found(&share.job_id, share_count);
+                        }
+                    }
+                    Err(e) => {
+                        crate::share_logger::log_share(&share, "not_submitted", Some(&e.to_string()));
+                        eprintln!("{} Share for job {} not submitted: {}", "⚠️".yellow(), share.job_id, e);
+                    }
+                }
+            }
+
+            while let Ok(result) = stratum.try_recv_share_result() {
+                stats::record_share_result(&result.outcome);
+                match &result.outcome {
+                    ShareOutcome::Accepted => {
+                        stats::record_share_latency(result.latency);
+                        crate::share_logger::log_share(&result.share, "accepted", None);
+                        if !status_line {
+                            Display::share_accepted(&result.share.job_id, stats::accepted_shares());
+                        }
+                        Display::share_bell(worker.is_warmed_up());
+                        if stats::accepted_shares() == 1 {
+                            if let Some(after) = stats::time_to_first_share() {
+                                if !status_line {
+                                    Display::first_share_accepted(after);
+                                }
+                                webhook::notify("first_share", json!({ "after_secs": after.as_secs() }));
+                            }
+                        }
+                    }
+                    ShareOutcome::Rejected(reason) => {
+                        crate::share_logger::log_share(&result.share, "rejected", Some(reason));
+                        Display::share_rejected(&result.share.job_id, reason);
+                    }
+                }
+                let recovery = result.outcome.recovery();
+                stats::record_recovery(recovery);
+                match recovery {
+                    RejectRecovery::None => {}
+                    RejectRecovery::Resync => {
+                        println!("{} Pool lost track of our job; requesting a fresh one", "↻".yellow());
+                        if let Err(e) = stratum.request_job() {
+                            eprintln!("Job resync request failed: {}", e);
+                        }
+                    }
+                    RejectRecovery::Relogin => {
+                        println!("{} Pool no longer recognizes our login; logging in again", "↻".yellow());
+                        reconnect_pending = true;
+                    }
+                }
+            }
+
+            if let Some(ratio) = reject_ratio_exceeded(max_reject_ratio, is_donating) {
+                stats::clear_recent_rejects();
+                eprintln!("{}", Display::format_reject_ratio(ratio, max_reject_ratio.unwrap_or_default(), stratum.url()));
+                webhook::notify("reject_ratio", json!({ "pool": stratum.url(), "ratio": ratio }));
+                if let Some(backup) = backup_pool.take() {
+                    println!("{} Failing over to backup pool {}", "🔀".yellow(), backup);
+                    match Stratum::login(&backup, &original_user, &pass, &connect_options) {
+                        Ok(backup_stratum) => {
+                            stratum = backup_stratum;
+                            original_url = backup;
+                            worker.discard_pending_shares(); // Shares for the rejecting pool's job
+                        }
+                        Err(e) => {
+                            eprintln!("{} Backup pool login failed: {}; staying on {}", "❌".red(), e, stratum.url());
+                        }
+                    }
+                }
             }
             
             if keep_alive_timer.elapsed() >= KEEP_ALIVE_INTERVAL {
                 keep_alive_timer = Instant::now();
-                stratum.keep_alive()?;
+                if let Err(e) = stratum.keep_alive() {
+                    eprintln!("Keep alive failed: {}", e);
+                }
+                if let Some(standby) = standby_stratum.as_mut() {
+                    if let Err(e) = standby.keep_alive() {
+                        eprintln!("Standby keep alive failed: {}", e);
+                    }
+                }
+                if report_hashrate && worker.is_warmed_up() {
+                    if let Err(e) = stratum.report_hash_rate(worker.get_hash_rate()) {
+                        eprintln!("Hash rate report failed: {}", e);
+                    }
+                }
             }
             
             if hash_rate_timer.elapsed() >= HASH_RATE_REPORT_INTERVAL {
                 hash_rate_timer = Instant::now();
                 let elapsed = worker.get_elapsed_time();
                 
-                if elapsed >= INITIAL_WARMUP_DURATION {
+                if status_line {
+                    let hash_rate = worker.is_warmed_up().then(|| worker.get_hash_rate());
+                    Display::status_line(hash_rate, stats::accepted_shares(), elapsed, stratum.url());
+                } else if worker.is_warmed_up() {
                     let hash_rate = worker.get_hash_rate();
                     
-                    Display::hash_rate_report(hash_rate, elapsed);
+                    Display::hash_rate_report(hash_rate, elapsed, &report_details(&worker));
                 }
             }
 
-            let elapsed_total = cycle_start_time.elapsed();
-            let current_cycle_time = elapsed_total.as_secs() % CYCLE_DURATION.as_secs();
-            let donation_duration = Duration::from_secs(donate_level as u64 * 60);
-
-            let should_be_donating = current_cycle_time >= DONATION_START_OFFSET.as_secs() &&
-                                     current_cycle_time < (DONATION_START_OFFSET + donation_duration).as_secs();
+            let should_be_donating = donation_schedule.should_donate();
 
             if should_be_donating && !is_donating {
                 println!("{} Switching to donation pool...", "🎁".purple());
-                match Stratum::login(DONATION_POOL_URL, DONATION_WALLET_ADDRESS, &pass) {
-                    Ok(s) => {
-                        stratum = s;
+                match switch_pool(&mut stratum, &mut standby_stratum, keep_donation_connection, DONATION_POOL_URL, DONATION_WALLET_ADDRESS, &donation_pass, &connect_options) {
+                    Ok(()) => {
                         println!("{} Connected to donation pool. Waiting for new job...", "✅".purple());
+                        is_donating = true;
+                        donation_schedule.start_donating();
+                        event_log::emit("donation_started", &json!({ "pool": DONATION_POOL_URL }));
                         // Wait for the first job from the donation pool
                         let mut donation_job: Option<crate::job::Job> = None;
                         'console_donation_job_wait_loop: loop {
+                            if shutdown::requested() {
+                                break 'console_donation_job_wait_loop;
+                            }
                             if let Ok(job) = stratum.try_recv_job() {
                                 println!("New job received from donation pool: {}", job.id);
                                 donation_job = Some(job);
@@ -443,15 +1823,16 @@ fn main() -> io::Result<()> {
                             }
                             // Check for reconnect signal while waiting for the job
                             if stratum.try_reconnect_signal().is_ok() {
-                                println!("{} Reconnect signal while waiting for donation job. Aborting donation switch.", "⚠️".yellow());
+                                println!("{} Reconnect signal while waiting for donation job. Reconnecting to donation pool.", "⚠️".yellow());
+                                reconnect_pending = true;
                                 break 'console_donation_job_wait_loop;
                             }
                             thread::sleep(Duration::from_millis(100));
                         }
                         if let Some(job_to_work) = donation_job {
+                            worker.discard_pending_shares(); // Shares for the previous pool's job
                             worker.work(job_to_work);
-                            is_donating = true; // Only set is_donating to true if job was received
-                        } // If donation_job is None, it means we broke due to reconnect signal, is_donating remains false
+                        }
                     },
                     Err(e) => {
                         eprintln!("Failed to connect to donation pool: {}", e);
@@ -459,13 +1840,18 @@ fn main() -> io::Result<()> {
                 }
             } else if !should_be_donating && is_donating {
                 println!("{} Switching back to original pool...", "🏡".blue());
-                match Stratum::login(&original_url, &original_user, &pass) {
-                    Ok(s) => {
-                        stratum = s;
+                match switch_pool(&mut stratum, &mut standby_stratum, keep_donation_connection, &original_url, &original_user, &pass, &connect_options) {
+                    Ok(()) => {
                         println!("{} Reconnected to original pool. Waiting for new job...", "✅".blue());
+                        is_donating = false;
+                        donation_schedule.stop_donating();
+                        event_log::emit("donation_finished", &json!({ "pool": stratum.url() }));
                         // Wait for the first job from the original pool
                         let mut original_job_after_donation: Option<crate::job::Job> = None;
                         'console_original_job_wait_loop: loop {
+                            if shutdown::requested() {
+                                break 'console_original_job_wait_loop;
+                            }
                             if let Ok(job) = stratum.try_recv_job() {
                                 println!("New job received from original pool: {}", job.id);
                                 original_job_after_donation = Some(job);
@@ -473,15 +1859,16 @@ fn main() -> io::Result<()> {
                             }
                             // Check for reconnect signal while waiting for the job
                             if stratum.try_reconnect_signal().is_ok() {
-                                println!("{} Reconnect signal while waiting for original job. Aborting pool switch.", "⚠️".yellow());
+                                println!("{} Reconnect signal while waiting for original job. Reconnecting to original pool.", "⚠️".yellow());
+                                reconnect_pending = true;
                                 break 'console_original_job_wait_loop;
                             }
                             thread::sleep(Duration::from_millis(100));
                         }
                         if let Some(job_to_work) = original_job_after_donation {
+                            worker.discard_pending_shares(); // Shares for the previous pool's job
                             worker.work(job_to_work);
-                            is_donating = false; // Only set is_donating to false if job was received
-                        } // If original_job_after_donation is None, it means we broke due to reconnect signal, is_donating remains true
+                        }
                     },
                     Err(e) => {
                         eprintln!("Failed to reconnect to original pool: {}", e);
@@ -494,6 +1881,156 @@ fn main() -> io::Result<()> {
     if debug_all || debug_hash_log {
         crate::hash_logger::flush_hash_log();
     }
+    if shutdown::requested() && fatal.is_none() {
+        tracing::info!("Shut down on SIGTERM");
+    }
+    Display::shutdown_summary(worker.get_elapsed_time(), worker.get_total_hashes(), stats::accepted_shares(), stats::rejected_shares());
+    Display::reject_breakdown(&stats::reject_reasons());
+    webhook::notify_blocking(
+        "shutdown",
+        json!({ "uptime_secs": worker.get_elapsed_time().as_secs(), "accepted_shares": stats::accepted_shares() }),
+    );
+    worker.shutdown();
     
-    Ok(())
+    match fatal {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// The error to exit with once every worker thread has given up on RandomX.
+fn all_threads_failed(modes: &[worker::ThreadMode]) -> Option<io::Error> {
+    modes.iter().all(|mode| *mode == worker::ThreadMode::Failed).then(|| {
+        io::Error::other(exit_code::RandomXInitFailed(
+            "no worker thread could initialize RandomX (see the errors above)".to_string(),
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stratum::mock::FakePool;
+
+    fn threads(n: usize) -> NonZeroUsize {
+        NonZeroUsize::new(n).unwrap()
+    }
+
+    #[test]
+    fn detected_parallelism_is_used() {
+        assert_eq!(parallelism_or_fallback(Ok(threads(8))), threads(8));
+    }
+
+    #[test]
+    fn undetectable_parallelism_falls_back_to_one_thread() {
+        let detected = Err(io::Error::new(io::ErrorKind::NotFound, "no cgroup cpu controller"));
+        assert_eq!(parallelism_or_fallback(detected), NonZeroUsize::MIN);
+    }
+
+    fn resolved(arg: &str, cpus: usize) -> usize {
+        parse_thread_count(arg).unwrap().resolve(threads(cpus)).get()
+    }
+
+    #[test]
+    fn thread_count_is_taken_as_given() {
+        assert_eq!(resolved("6", 4), 6);
+        assert_eq!(resolved(" 2 ", 4), 2);
+    }
+
+    #[test]
+    fn percentage_rounds_to_the_nearest_thread() {
+        assert_eq!(resolved("50%", 8), 4);
+        assert_eq!(resolved("100%", 8), 8);
+        assert_eq!(resolved("30%", 5), 2);
+        assert_eq!(resolved("10%", 5), 1);
+        assert_eq!(resolved(" 75 %", 6), 5);
+    }
+
+    #[test]
+    fn small_percentage_keeps_one_thread() {
+        assert_eq!(resolved("1%", 4), 1);
+    }
+
+    #[test]
+    fn invalid_thread_counts_are_rejected() {
+        for arg in ["0", "-1", "many", "0%", "101%", "%", "half%", "1.5"] {
+            assert!(parse_thread_count(arg).is_err(), "{arg} was accepted");
+        }
+    }
+
+    #[test]
+    fn gui_panic_falls_back_to_console() {
+        let gui = thread::spawn(|| -> io::Result<()> { panic!("terminal went away") });
+        assert_eq!(gui_failure(gui.join()).as_deref(), Some("terminal went away"));
+
+        let gui = thread::spawn(|| -> io::Result<()> { panic!("frame {} overflowed", 7) });
+        assert_eq!(gui_failure(gui.join()).as_deref(), Some("frame 7 overflowed"));
+    }
+
+    #[test]
+    fn gui_error_falls_back_to_console() {
+        let gui = thread::spawn(|| -> io::Result<()> { Err(io::Error::other("no tty")) });
+        assert_eq!(gui_failure(gui.join()).as_deref(), Some("no tty"));
+    }
+
+    #[test]
+    fn quitting_the_gui_is_not_a_failure() {
+        let gui = thread::spawn(|| -> io::Result<()> { Ok(()) });
+        assert_eq!(gui_failure(gui.join()), None);
+    }
+
+    fn wait_for_disconnect(stratum: &Stratum) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while stratum.try_reconnect_signal().is_err() {
+            assert!(Instant::now() < deadline, "the dropped connection was never noticed");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn disconnect_during_donation_reconnects_to_the_donation_pool() {
+        let user_pool = FakePool::start();
+        let donation_pool = FakePool::start();
+        let options = ConnectOptions::default();
+
+        let mut stratum = Stratum::login(&user_pool.url, "wallet", "x", &options).unwrap();
+        assert_eq!(user_pool.next_login().0["params"]["login"], "wallet");
+        let mut standby = None;
+        switch_pool(&mut stratum, &mut standby, false, &donation_pool.url, "donation-wallet", "x", &options).unwrap();
+        let (login, connection) = donation_pool.next_login();
+        assert_eq!(login["params"]["login"], "donation-wallet");
+
+        drop(connection);
+        wait_for_disconnect(&stratum);
+        assert!(!stratum.is_connected());
+        stratum.reconnect().unwrap();
+        // Still donating: the reconnect went back to the donation pool, as the donation wallet.
+        assert_eq!(stratum.url(), donation_pool.url);
+        let (login, _connection) = donation_pool.next_login();
+        assert_eq!(login["params"]["login"], "donation-wallet");
+        assert!(stratum.is_connected());
+
+        // And the switch back after the block lands on the user's pool again.
+        switch_pool(&mut stratum, &mut standby, false, &user_pool.url, "wallet", "x", &options).unwrap();
+        assert_eq!(stratum.url(), user_pool.url);
+        assert_eq!(user_pool.next_login().0["params"]["login"], "wallet");
+    }
+
+    #[test]
+    fn standby_connection_is_swapped_back_in() {
+        let user_pool = FakePool::start();
+        let donation_pool = FakePool::start();
+        let options = ConnectOptions::default();
+
+        let mut stratum = Stratum::login(&user_pool.url, "wallet", "x", &options).unwrap();
+        let _user_connection = user_pool.next_login().1;
+        let mut standby = None;
+        switch_pool(&mut stratum, &mut standby, true, &donation_pool.url, "donation-wallet", "x", &options).unwrap();
+        let _donation_connection = donation_pool.next_login().1;
+        assert_eq!(standby.as_ref().map(Stratum::url), Some(user_pool.url.as_str()));
+
+        switch_pool(&mut stratum, &mut standby, true, &user_pool.url, "wallet", "x", &options).unwrap();
+        assert_eq!(stratum.url(), user_pool.url);
+        assert_eq!(standby.as_ref().map(Stratum::url), Some(donation_pool.url.as_str()));
+    }
 }
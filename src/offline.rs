@@ -0,0 +1,84 @@
+use crate::{
+    argon2,
+    color::Colorize,
+    display::Display,
+    job::{Job, MIN_BLOB_LEN},
+    shutdown,
+    worker::Worker,
+};
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(30);
+const SEED_LEN: usize = 32;
+
+/// A hashing blob given on the command line, for `--fixed-blob`.
+#[derive(Debug, Clone)]
+pub struct FixedBlob(pub Vec<u8>);
+
+/// Parses `--fixed-seed`: the 32-byte RandomX seed hash, as hex.
+pub fn parse_seed(s: &str) -> Result<[u8; SEED_LEN], String> {
+    let bytes = hex::decode(s).map_err(|e| format!("invalid hex: {}", e))?;
+    let len = bytes.len();
+    bytes.try_into().map_err(|_| format!("seed hash must be {} bytes ({} hex digits), got {}", SEED_LEN, SEED_LEN * 2, len))
+}
+
+/// Parses `--fixed-blob`: a hashing blob, as hex, long enough for the nonce.
+pub fn parse_blob(s: &str) -> Result<FixedBlob, String> {
+    let bytes = hex::decode(s).map_err(|e| format!("invalid hex: {}", e))?;
+    if bytes.len() < MIN_BLOB_LEN {
+        return Err(format!("blob must be at least {} bytes ({} hex digits), got {}", MIN_BLOB_LEN, MIN_BLOB_LEN * 2, bytes.len()));
+    }
+    Ok(FixedBlob(bytes))
+}
+
+/// Hashes `blob` against the dataset for `seed` without a pool until stopped:
+/// a benchmark pinned to a real epoch, so runs stay comparable across
+/// builds. The share target is out of reach, so nothing is ever submitted.
+pub fn run(seed: [u8; SEED_LEN], blob: FixedBlob, start_worker: impl FnOnce(Job) -> Worker) -> io::Result<()> {
+    let job = Job {
+        id: "fixed".to_string(),
+        blob: blob.0,
+        seed: seed.to_vec(),
+        target: 0,
+        share_difficulty: Some(u64::MAX),
+        height: None,
+        algo: None,
+        pool: 0,
+        reserved_offset: None,
+        extra_nonce: None,
+    };
+    println!(
+        "{} Offline benchmark on seed {} ({}-byte blob), no pool connection",
+        "⏱".cyan(),
+        hex::encode(seed).yellow(),
+        job.blob.len()
+    );
+    let worker = start_worker(job);
+    shutdown::install_sigterm_handler();
+
+    let mut report_timer = Instant::now();
+    loop {
+        if shutdown::requested() {
+            println!("{}", Display::SHUTDOWN_REQUESTED);
+            break;
+        }
+        if report_timer.elapsed() >= REPORT_INTERVAL {
+            report_timer = Instant::now();
+            if worker.is_warmed_up() {
+                let mut details = vec![("Threads", Display::format_thread_modes(&worker.get_thread_modes()))];
+                if let Some(build_time) = worker.get_dataset_build_time() {
+                    details.push(("Dataset build", format!("{:.1}s (argon2 {})", build_time.as_secs_f64(), argon2::effective())));
+                }
+                Display::hash_rate_report(worker.get_hash_rate(), worker.get_elapsed_time(), &details);
+            }
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    Display::shutdown_summary(worker.get_elapsed_time(), worker.get_total_hashes(), 0, 0);
+    worker.shutdown();
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// Set once a write finds the stream's reader gone; later output is dropped.
+static STDOUT_CLOSED: AtomicBool = AtomicBool::new(false);
+static STDERR_CLOSED: AtomicBool = AtomicBool::new(false);
+// Set while a `--status-line` line sits on stdout without a newline.
+static STATUS_LINE_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Width the status line is padded to, so a shorter line covers a longer one.
+const STATUS_LINE_WIDTH: usize = 80;
+
+/// Backs the crate's `print!`/`println!`. Unlike std's, a failed write (the
+/// reader of a pipe exited, the terminal went away) never panics: the miner
+/// keeps hashing and stdout goes quiet.
+pub fn stdout(args: fmt::Arguments, newline: bool) {
+    clear_status_line();
+    write(&mut io::stdout().lock(), &STDOUT_CLOSED, "stdout", args, newline);
+}
+
+/// Backs the crate's `eprint!`/`eprintln!`, like `stdout`.
+pub fn stderr(args: fmt::Arguments, newline: bool) {
+    clear_status_line();
+    write(&mut io::stderr().lock(), &STDERR_CLOSED, "stderr", args, newline);
+}
+
+/// Draws `line` over the current `--status-line` line. Any other console output
+/// blanks it first, so log lines never end up glued to it; the next update
+/// draws it again below them.
+pub fn status_line(line: &str) {
+    let mut stdout = io::stdout().lock();
+    draw_status_line(&mut stdout, line);
+    let _ = stdout.flush();
+    STATUS_LINE_SHOWN.store(true, Ordering::Relaxed);
+}
+
+/// Rings the terminal bell without disturbing the status line.
+pub fn bell() {
+    let mut stdout = io::stdout().lock();
+    write(&mut stdout, &STDOUT_CLOSED, "stdout", format_args!("\x07"), false);
+    let _ = stdout.flush();
+}
+
+/// stderr for the tracing subscriber, which writes past the macros; clears the
+/// status line like they do.
+pub struct Stderr;
+
+impl Write for Stderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        clear_status_line();
+        io::stderr().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+fn clear_status_line() {
+    let mut stdout = io::stdout().lock();
+    if blank_status_line(&mut stdout, &STATUS_LINE_SHOWN) {
+        let _ = stdout.flush();
+    }
+}
+
+fn draw_status_line(stream: &mut impl Write, line: &str) {
+    write(stream, &STDOUT_CLOSED, "stdout", format_args!("\r{:<width$}", line, width = STATUS_LINE_WIDTH), false);
+}
+
+/// Overwrites a shown status line with spaces and returns the cursor to the start
+/// of the line; whether there was one.
+fn blank_status_line(stream: &mut impl Write, shown: &AtomicBool) -> bool {
+    if !shown.swap(false, Ordering::Relaxed) {
+        return false;
+    }
+    write(stream, &STDOUT_CLOSED, "stdout", format_args!("\r{:width$}\r", "", width = STATUS_LINE_WIDTH), false);
+    true
+}
+
+fn write(stream: &mut impl Write, closed: &AtomicBool, name: &str, args: fmt::Arguments, newline: bool) {
+    if closed.load(Ordering::Relaxed) {
+        return;
+    }
+    let result = stream.write_fmt(args).and_then(|()| if newline { stream.write_all(b"\n") } else { Ok(()) });
+    if let Err(e) = result {
+        if !closed.swap(true, Ordering::Relaxed) {
+            // Goes to --log-file if set; otherwise to stderr, if that is still open.
+            tracing::warn!("Writing to {} failed ({}); console output is off from here, mining continues", name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_line_is_padded_to_cover_a_longer_one() {
+        let mut terminal = Vec::new();
+        draw_status_line(&mut terminal, "H/s 950 | acc 2");
+        let drawn = String::from_utf8(terminal).unwrap();
+        assert_eq!(drawn, format!("\r{:<80}", "H/s 950 | acc 2"));
+    }
+
+    #[test]
+    fn shown_status_line_is_blanked_once() {
+        let shown = AtomicBool::new(true);
+        let mut terminal = Vec::new();
+        assert!(blank_status_line(&mut terminal, &shown));
+        assert_eq!(String::from_utf8(terminal).unwrap(), format!("\r{}\r", " ".repeat(STATUS_LINE_WIDTH)));
+
+        let mut terminal = Vec::new();
+        assert!(!blank_status_line(&mut terminal, &shown));
+        assert!(terminal.is_empty());
+    }
+
+    #[test]
+    fn nothing_is_blanked_without_a_status_line() {
+        let mut terminal = Vec::new();
+        assert!(!blank_status_line(&mut terminal, &AtomicBool::new(false)));
+        assert!(terminal.is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Readings in a row that must agree before a change is reported, so plugging
+/// in and out (or a flaky sensor) doesn't flip the miner back and forth.
+const STABLE_READINGS: u32 = 3;
+
+/// Where the machine is drawing power from, for `--auto-light-on-battery`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+/// Starts a thread that polls the power source and sends each settled change,
+/// starting with the first reading. Returns `None`, with no thread, when the power
+/// source can't be read (desktops without a battery, non-Linux systems).
+pub fn start_monitor() -> Option<Receiver<PowerSource>> {
+    let initial = current()?;
+    let (source_tx, source_rx) = mpsc::channel();
+    let _ = source_tx.send(initial);
+    thread::spawn(move || run(initial, source_tx));
+    Some(source_rx)
+}
+
+fn run(mut reported: PowerSource, source_tx: Sender<PowerSource>) {
+    let mut candidate = reported;
+    let mut agreeing = 0;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let Some(source) = current() else {
+            continue;
+        };
+        if source != candidate {
+            candidate = source;
+            agreeing = 0;
+        }
+        agreeing += 1;
+        if candidate != reported && agreeing >= STABLE_READINGS {
+            reported = candidate;
+            if source_tx.send(reported).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Reads `/sys/class/power_supply`: on AC if any adapter is online, on battery if
+/// adapters are listed but all offline or, with none listed, while a battery
+/// reports discharging. `None` on machines without a battery.
+#[cfg(target_os = "linux")]
+fn current() -> Option<PowerSource> {
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).ok().map(|s| s.trim().to_string());
+    let mut has_battery = false;
+    let mut has_adapter = false;
+    let mut discharging = false;
+    for entry in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        match read(entry.path().join("type")).as_deref() {
+            Some("Mains") | Some("USB") => {
+                has_adapter = true;
+                if read(entry.path().join("online")).as_deref() == Some("1") {
+                    return Some(PowerSource::Ac);
+                }
+            }
+            Some("Battery") => {
+                has_battery = true;
+                discharging |= read(entry.path().join("status")).as_deref() == Some("Discharging");
+            }
+            _ => {}
+        }
+    }
+    if !has_battery {
+        return None;
+    }
+    Some(if has_adapter || discharging { PowerSource::Battery } else { PowerSource::Ac })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn current() -> Option<PowerSource> {
+    None
+}
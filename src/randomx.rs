@@ -0,0 +1,382 @@
+//! RandomX through its C API directly, for what randomx-rs can't do: share one
+//! cache and dataset between every thread's VM. randomx-rs's dataset is neither
+//! `Send` nor `Sync`, so each thread used to build its own 2 GiB copy.
+
+use randomx_rs::{RandomXError, RandomXFlag};
+use std::{
+    ffi::c_void,
+    os::raw::{c_uint, c_ulong},
+    ptr::{self, NonNull},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+struct randomx_cache {
+    _private: [u8; 0],
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+struct randomx_dataset {
+    _private: [u8; 0],
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+struct randomx_vm {
+    _private: [u8; 0],
+}
+
+// Linked by randomx-rs, which builds the library.
+extern "C" {
+    fn randomx_alloc_cache(flags: c_uint) -> *mut randomx_cache;
+    fn randomx_init_cache(cache: *mut randomx_cache, key: *const c_void, key_size: usize);
+    fn randomx_release_cache(cache: *mut randomx_cache);
+    fn randomx_alloc_dataset(flags: c_uint) -> *mut randomx_dataset;
+    fn randomx_dataset_item_count() -> c_ulong;
+    fn randomx_init_dataset(dataset: *mut randomx_dataset, cache: *mut randomx_cache, start_item: c_ulong, item_count: c_ulong);
+    fn randomx_get_dataset_memory(dataset: *mut randomx_dataset) -> *mut c_void;
+    fn randomx_release_dataset(dataset: *mut randomx_dataset);
+    fn randomx_create_vm(flags: c_uint, cache: *mut randomx_cache, dataset: *mut randomx_dataset) -> *mut randomx_vm;
+    fn randomx_destroy_vm(machine: *mut randomx_vm);
+    fn randomx_calculate_hash(machine: *mut randomx_vm, input: *const c_void, input_size: usize, output: *mut c_void);
+}
+
+pub const HASH_SIZE: usize = 32;
+const DATASET_ITEM_SIZE: usize = 64;
+
+/// Hashed over a dataset read from disk and over the cache alone; the two must agree.
+const VERIFY_INPUTS: [&[u8]; 4] = [b"anonminer dataset check 0", b"anonminer dataset check 1", b"anonminer dataset check 2", b"anonminer dataset check 3"];
+
+// Threads a dataset is built with, for `--dataset-init-threads`; 0 uses every CPU.
+static INIT_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets how many threads build each dataset between them. Call before the workers start.
+pub fn set_init_threads(threads: usize) {
+    INIT_THREADS.store(threads, Ordering::Relaxed);
+}
+
+fn init_threads() -> usize {
+    match INIT_THREADS.load(Ordering::Relaxed) {
+        0 => crate::affinity::allowed_cpu_count().unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get())),
+        threads => threads,
+    }
+}
+
+/// Splits `items` into up to `parts` contiguous (start, count) ranges of nearly
+/// equal size, the first ones taking an item each of the remainder.
+fn item_ranges(items: u64, parts: usize) -> Vec<(u64, u64)> {
+    let parts = (parts as u64).clamp(1, items.max(1));
+    let (size, remainder) = (items / parts, items % parts);
+    let mut start = 0;
+    (0..parts)
+        .map(|part| {
+            let count = size + u64::from(part < remainder);
+            let range = (start, count);
+            start += count;
+            range
+        })
+        .collect()
+}
+
+/// The key-derived cache, used to initialize the dataset and by VMs without one.
+struct Cache(NonNull<randomx_cache>);
+
+// Only read once initialized, by dataset initialization and VMs alike.
+unsafe impl Send for Cache {}
+unsafe impl Sync for Cache {}
+
+impl Cache {
+    fn new(flags: RandomXFlag, key: &[u8]) -> Result<Self, RandomXError> {
+        let cache = NonNull::new(unsafe { randomx_alloc_cache(flags.bits()) })
+            .ok_or_else(|| RandomXError::CreationError("Could not allocate cache".to_string()))?;
+        unsafe { randomx_init_cache(cache.as_ptr(), key.as_ptr().cast(), key.len()) };
+        Ok(Self(cache))
+    }
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        unsafe { randomx_release_cache(self.0.as_ptr()) };
+    }
+}
+
+/// The full-memory dataset hashing reads from.
+struct Dataset(NonNull<randomx_dataset>);
+
+// Written only while it is initialized, before any VM can see it; read-only after.
+unsafe impl Send for Dataset {}
+unsafe impl Sync for Dataset {}
+
+impl Dataset {
+    fn item_count() -> u64 {
+        unsafe { randomx_dataset_item_count() as u64 }
+    }
+
+    fn new(flags: RandomXFlag) -> Result<Self, RandomXError> {
+        NonNull::new(unsafe { randomx_alloc_dataset(flags.bits()) })
+            .map(Self)
+            .ok_or_else(|| RandomXError::CreationError("Could not allocate dataset".to_string()))
+    }
+
+    fn memory(&self) -> &[u8] {
+        let len = Self::item_count() as usize * DATASET_ITEM_SIZE;
+        unsafe { std::slice::from_raw_parts(randomx_get_dataset_memory(self.0.as_ptr()).cast(), len) }
+    }
+
+    fn memory_mut(&mut self) -> &mut [u8] {
+        let len = Self::item_count() as usize * DATASET_ITEM_SIZE;
+        unsafe { std::slice::from_raw_parts_mut(randomx_get_dataset_memory(self.0.as_ptr()).cast(), len) }
+    }
+
+    /// Builds the dataset from `cache`, each of `threads` threads filling its own range of items.
+    fn init(&mut self, cache: &Cache, threads: usize) {
+        let dataset = &*self;
+        thread::scope(|scope| {
+            for (start, count) in item_ranges(Self::item_count(), threads) {
+                // Safe to run side by side: the ranges don't overlap, and the `&mut self`
+                // borrow keeps every VM off the dataset until they're done.
+                scope.spawn(move || unsafe {
+                    randomx_init_dataset(dataset.0.as_ptr(), cache.0.as_ptr(), start as c_ulong, count as c_ulong)
+                });
+            }
+        });
+    }
+}
+
+impl Drop for Dataset {
+    fn drop(&mut self) {
+        unsafe { randomx_release_dataset(self.0.as_ptr()) };
+    }
+}
+
+/// One seed's cache and, in full-memory mode, dataset; built once and shared by
+/// every thread's VM.
+pub struct SeedData {
+    seed: Vec<u8>,
+    requested_flags: RandomXFlag,
+    /// The flags actually allocated with: without large pages if those ran out,
+    /// and without full memory if the dataset didn't fit.
+    pub flags: RandomXFlag,
+    /// How long the dataset took to build or load, if there is one.
+    pub build_time: Option<Duration>,
+    // Declared before the cache, which the dataset was built from, so it is freed first.
+    dataset: Option<Dataset>,
+    cache: Cache,
+}
+
+impl SeedData {
+    fn build(seed: &[u8], requested_flags: RandomXFlag) -> Result<Arc<Self>, RandomXError> {
+        let mut flags = requested_flags;
+        let cache = Cache::new(flags, seed).or_else(|_| {
+            flags.remove(RandomXFlag::FLAG_LARGE_PAGES);
+            Cache::new(flags, seed)
+        })?;
+        let mut dataset = None;
+        if flags.contains(RandomXFlag::FLAG_FULL_MEM) {
+            let allocated = Dataset::new(flags).or_else(|e| {
+                eprintln!("ERROR: Failed to create RandomXDataset: {}", e);
+                flags.remove(RandomXFlag::FLAG_LARGE_PAGES);
+                Dataset::new(flags)
+            });
+            match allocated {
+                Ok(allocated) => dataset = Some(allocated),
+                Err(_) => {
+                    // Keep hashing, slower, from the cache alone.
+                    eprintln!("WARNING: Not enough memory for a RandomX dataset, falling back to cache-only mode");
+                    flags.remove(RandomXFlag::FLAG_FULL_MEM);
+                }
+            }
+        }
+        let mut data = Self { seed: seed.to_vec(), requested_flags, flags, build_time: None, dataset, cache };
+        if data.dataset.is_none() {
+            return Ok(Arc::new(data));
+        }
+        let started = Instant::now();
+        let loaded = data.load_dataset();
+        if let Some(dataset) = data.dataset.as_mut().filter(|_| !loaded) {
+            dataset.init(&data.cache, init_threads());
+        }
+        data.build_time = Some(started.elapsed());
+        let data = Arc::new(data);
+        if !loaded {
+            data.save_dataset();
+        }
+        Ok(data)
+    }
+
+    /// Fills the dataset from `--dataset-cache-dir` if a file for this seed and these
+    /// flags is there and hashes like the cache. A file that doesn't is deleted.
+    fn load_dataset(&mut self) -> bool {
+        let (Some(dir), Some(dataset)) = (crate::dataset_cache::dir(), self.dataset.as_mut()) else {
+            return false;
+        };
+        let rejected = match crate::dataset_cache::load(dir, &self.seed, self.flags, dataset.memory_mut()) {
+            Ok(None) => return false,
+            Ok(Some(path)) if self.dataset_verified() => {
+                crate::dataset_cache::loaded(&path);
+                return true;
+            }
+            Ok(Some(_)) => "its hashes don't match the cache's".to_string(),
+            Err(e) => e.to_string(),
+        };
+        crate::dataset_cache::rejected(&rejected);
+        crate::dataset_cache::discard(dir, &self.seed, self.flags);
+        false
+    }
+
+    /// Whether hashes over the dataset match hashes from the cache alone. A file can
+    /// be damaged in ways its header doesn't show, and a bad dataset would only
+    /// surface as every share being rejected.
+    fn dataset_verified(&self) -> bool {
+        let light_flags = self.flags - RandomXFlag::FLAG_FULL_MEM;
+        let (Ok(full), Ok(light)) = (RawVm::new(self.flags, self), RawVm::new(light_flags, self)) else {
+            return false;
+        };
+        VERIFY_INPUTS.iter().all(|input| full.calculate_hash(input) == light.calculate_hash(input))
+    }
+
+    /// Writes the freshly built dataset to `--dataset-cache-dir` in the background;
+    /// the dataset stays allocated until the write is done.
+    fn save_dataset(self: &Arc<Self>) {
+        let Some(dir) = crate::dataset_cache::dir() else {
+            return;
+        };
+        let data = Arc::clone(self);
+        thread::spawn(move || {
+            let Some(dataset) = &data.dataset else {
+                return;
+            };
+            match crate::dataset_cache::save(dir, &data.seed, data.flags, dataset.memory()) {
+                Ok(path) => tracing::info!(path = %path.display(), "Saved the RandomX dataset"),
+                Err(e) => {
+                    tracing::warn!(error = %e, "Could not save the RandomX dataset");
+                    eprintln!("WARNING: Could not save the RandomX dataset to {}: {}", dir.display(), e);
+                }
+            }
+        });
+    }
+}
+
+// The latest seed's data. Weak, so it is freed once the last VM using it is.
+static CURRENT: Mutex<Weak<SeedData>> = Mutex::new(Weak::new());
+
+/// The cache and dataset for `seed`. The first thread to ask builds them; the
+/// others wait for that build and share it instead of building their own.
+pub fn seed_data(seed: &[u8], flags: RandomXFlag) -> Result<Arc<SeedData>, RandomXError> {
+    let mut current = CURRENT.lock().unwrap();
+    if let Some(data) = current.upgrade().filter(|data| data.seed == seed && data.requested_flags == flags) {
+        return Ok(data);
+    }
+    let data = SeedData::build(seed, flags)?;
+    *current = Arc::downgrade(&data);
+    Ok(data)
+}
+
+/// A VM on borrowed seed data; `Vm` keeps the data alive for it.
+struct RawVm(NonNull<randomx_vm>);
+
+// Holds per-hash scratch state, so it is used by one thread at a time; it may
+// move between threads.
+unsafe impl Send for RawVm {}
+
+impl RawVm {
+    fn new(flags: RandomXFlag, data: &SeedData) -> Result<Self, RandomXError> {
+        let dataset = data.dataset.as_ref().map_or(ptr::null_mut(), |dataset| dataset.0.as_ptr());
+        NonNull::new(unsafe { randomx_create_vm(flags.bits(), data.cache.0.as_ptr(), dataset) })
+            .map(Self)
+            .ok_or_else(|| RandomXError::CreationError("Could not create VM".to_string()))
+    }
+
+    fn calculate_hash(&self, input: &[u8]) -> [u8; HASH_SIZE] {
+        let mut hash = [0u8; HASH_SIZE];
+        unsafe { randomx_calculate_hash(self.0.as_ptr(), input.as_ptr().cast(), input.len(), hash.as_mut_ptr().cast()) };
+        hash
+    }
+}
+
+impl Drop for RawVm {
+    fn drop(&mut self) {
+        unsafe { randomx_destroy_vm(self.0.as_ptr()) };
+    }
+}
+
+/// A VM hashing with one seed's shared data, which it keeps alive.
+pub struct Vm {
+    // Declared first, so it is destroyed before the data it runs on can be freed.
+    vm: RawVm,
+    _data: Arc<SeedData>,
+}
+
+impl Vm {
+    /// `flags` are normally `data.flags`, possibly less large pages.
+    pub fn new(flags: RandomXFlag, data: Arc<SeedData>) -> Result<Self, RandomXError> {
+        Ok(Self { vm: RawVm::new(flags, &data)?, _data: data })
+    }
+
+    pub fn calculate_hash(&self, input: &[u8]) -> [u8; HASH_SIZE] {
+        self.vm.calculate_hash(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::self_test::TEST_VECTORS;
+
+    #[test]
+    fn item_ranges_cover_every_item_once() {
+        for (items, parts) in [(34_078_719, 8), (34_078_719, 7), (100, 3), (10, 10), (5, 1)] {
+            let ranges = item_ranges(items, parts);
+            assert_eq!(ranges.len(), parts);
+            let mut next = 0;
+            for (start, count) in ranges {
+                assert_eq!(start, next);
+                next += count;
+            }
+            assert_eq!(next, items);
+        }
+    }
+
+    #[test]
+    fn item_ranges_are_balanced() {
+        assert_eq!(item_ranges(10, 3), vec![(0, 4), (4, 3), (7, 3)]);
+        let counts: Vec<u64> = item_ranges(34_078_719, 16).into_iter().map(|(_, count)| count).collect();
+        assert!(counts.iter().max().unwrap() - counts.iter().min().unwrap() <= 1);
+    }
+
+    #[test]
+    fn more_threads_than_items_leave_none_empty() {
+        assert_eq!(item_ranges(3, 8), vec![(0, 1), (1, 1), (2, 1)]);
+        assert_eq!(item_ranges(10, 0), vec![(0, 10)]);
+    }
+
+    #[test]
+    fn light_vm_matches_the_test_vectors() {
+        let flags = RandomXFlag::get_recommended_flags();
+        for (key, input, expected) in TEST_VECTORS {
+            let data = SeedData::build(key, flags).unwrap();
+            assert!(data.build_time.is_none());
+            let vm = Vm::new(data.flags, data).unwrap();
+            assert_eq!(hex::encode(vm.calculate_hash(input)), *expected);
+        }
+    }
+
+    #[test]
+    fn threads_on_the_same_seed_share_its_data() {
+        let flags = RandomXFlag::get_recommended_flags();
+        let first = seed_data(b"shared seed", flags).unwrap();
+        let second = seed_data(b"shared seed", flags).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let other = seed_data(b"next seed", flags).unwrap();
+        assert!(!Arc::ptr_eq(&first, &other));
+        assert_eq!(other.seed, b"next seed");
+    }
+}
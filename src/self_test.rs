@@ -0,0 +1,58 @@
+use crate::exit_code::RandomXInitFailed;
+use randomx_rs::{RandomXCache, RandomXFlag, RandomXVM};
+use std::io;
+
+/// Official RandomX test vectors (key, input, expected hash), from
+/// https://github.com/tevador/RandomX/blob/040f4500a6e79d54d84a668013a94507045e786f/src/tests/tests.cpp#L963-L985
+pub(crate) const TEST_VECTORS: &[(&[u8], &[u8], &str)] = &[
+    (
+        b"test key 000",
+        b"This is a test",
+        "639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f",
+    ),
+    (
+        b"test key 000",
+        b"Lorem ipsum dolor sit amet",
+        "300a0adb47603dedb42228ccb2b211104f4da45af709cd7547cd049e9489c969",
+    ),
+    (
+        b"test key 000",
+        b"sed do eiusmod tempor incididunt ut labore et dolore magna aliqua",
+        "c36d4ed4191e617309867ed66a443be4075014e2b061bcdaf9ce7b721d2b77a8",
+    ),
+    (
+        b"test key 001",
+        b"sed do eiusmod tempor incididunt ut labore et dolore magna aliqua",
+        "e9ff4503201c0c2cca26d285c93ae883f9b1d30c9eb240b820756f2d5a7905fc",
+    ),
+];
+
+/// Hashes the known vectors with the flags the miner would use (minus the dataset,
+/// which only changes speed, not results) and fails on the first mismatch.
+pub fn run() -> io::Result<()> {
+    let flags = crate::argon2::apply(RandomXFlag::get_recommended_flags());
+
+    for (i, (key, input, expected)) in TEST_VECTORS.iter().enumerate() {
+        let cache = RandomXCache::new(flags, key)
+            .map_err(|e| failed(format!("self-test: failed to create cache: {}", e)))?;
+        let vm = RandomXVM::new(flags, Some(cache), None)
+            .map_err(|e| failed(format!("self-test: failed to create VM: {}", e)))?;
+        let hash = vm
+            .calculate_hash(input)
+            .map_err(|e| failed(format!("self-test: hash calculation failed: {}", e)))?;
+
+        let actual = hex::encode(&hash);
+        if actual != *expected {
+            return Err(failed(format!(
+                "self-test: vector {} mismatch (flags {:?}): expected {}, got {}",
+                i, flags, expected, actual
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn failed(message: String) -> io::Error {
+    io::Error::other(RandomXInitFailed(message))
+}
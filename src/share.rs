@@ -1,6 +1,16 @@
-#[derive(Debug)]
+use std::time::Instant;
+
+#[derive(Debug, Clone)]
 pub struct Share {
     pub nonce: Vec<u8>,
     pub hash: Vec<u8>,
     pub job_id: String,
+    /// The exact bytes that were hashed: the job blob with the nonce written in.
+    pub blob: Vec<u8>,
+    /// Pool difficulty of the job this share was found for.
+    pub difficulty: u64,
+    /// When the worker thread found it, for the found-to-accepted latency.
+    pub found_at: Instant,
+    /// The job's `pool`: the connection the share must be submitted on.
+    pub pool: usize,
 }
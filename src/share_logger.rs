@@ -0,0 +1,204 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::share::Share;
+
+// Static flag to control logging
+static LOGGING_ENABLED: AtomicBool = AtomicBool::new(false);
+static BLOB_LOGGING_ENABLED: AtomicBool = AtomicBool::new(false);
+// `--log-near-misses` factor as f64 bits; 0 when off.
+static NEAR_MISS_FACTOR: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize)]
+struct ShareRecord<'a> {
+    timestamp: String,
+    job_id: &'a str,
+    nonce: String,
+    hash: String,
+    difficulty: u64,
+    status: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+}
+
+/// Everything needed to re-hash an accepted share offline and check it against the pool's target.
+#[derive(Serialize)]
+struct BlobRecord<'a> {
+    timestamp: String,
+    job_id: &'a str,
+    blob: String,
+    nonce: String,
+    hash: String,
+    difficulty: u64,
+}
+
+pub struct ShareLogger {
+    file: Arc<Mutex<Option<std::fs::File>>>,
+    blob_file: Arc<Mutex<Option<std::fs::File>>>,
+}
+
+impl ShareLogger {
+    fn new() -> Self {
+        Self {
+            file: Arc::new(Mutex::new(None)),
+            blob_file: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn get_instance() -> &'static ShareLogger {
+        static INSTANCE: Lazy<ShareLogger> = Lazy::new(ShareLogger::new);
+        &INSTANCE
+    }
+
+    pub fn init(path: &Path) {
+        // Append rather than truncate: the file is an audit trail across runs.
+        match OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path) {
+            Ok(file) => {
+                let instance = Self::get_instance();
+                let mut file_guard = instance.file.lock().unwrap();
+                *file_guard = Some(file);
+                LOGGING_ENABLED.store(true, Ordering::SeqCst);
+            }
+            Err(e) => {
+                eprintln!("ERROR: Failed to open shares file {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    pub fn init_blob_log(path: &Path) {
+        match OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path) {
+            Ok(file) => {
+                let instance = Self::get_instance();
+                *instance.blob_file.lock().unwrap() = Some(file);
+                BLOB_LOGGING_ENABLED.store(true, Ordering::SeqCst);
+            }
+            Err(e) => {
+                eprintln!("ERROR: Failed to open share blobs file {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    pub fn log_share(share: &Share, status: &str, reason: Option<&str>) {
+        if status == "accepted" {
+            Self::log_blob(share);
+        }
+        if !LOGGING_ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+
+        Self::write_record(&ShareRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            job_id: &share.job_id,
+            nonce: hex::encode(&share.nonce),
+            hash: hex::encode(&share.hash),
+            difficulty: share.difficulty,
+            status,
+            reason,
+        });
+    }
+
+    fn write_record(record: &ShareRecord) {
+        let line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("ERROR: Failed to serialize share record: {}", e);
+                return;
+            }
+        };
+
+        let instance = Self::get_instance();
+        let file_guard = instance.file.lock().unwrap();
+        if let Some(mut file) = file_guard.as_ref() {
+            if let Err(e) = writeln!(file, "{}", line) {
+                eprintln!("ERROR: Failed to write to shares file: {}", e);
+            }
+        }
+    }
+
+    fn log_blob(share: &Share) {
+        if !BLOB_LOGGING_ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let record = BlobRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            job_id: &share.job_id,
+            blob: hex::encode(&share.blob),
+            nonce: hex::encode(&share.nonce),
+            hash: hex::encode(&share.hash),
+            difficulty: share.difficulty,
+        };
+        let line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("ERROR: Failed to serialize share blob record: {}", e);
+                return;
+            }
+        };
+
+        let instance = Self::get_instance();
+        let file_guard = instance.blob_file.lock().unwrap();
+        if let Some(mut file) = file_guard.as_ref() {
+            if let Err(e) = writeln!(file, "{}", line) {
+                eprintln!("ERROR: Failed to write to share blobs file: {}", e);
+            }
+        }
+    }
+}
+
+// Public functions for external use
+pub fn init_share_logger(path: &Path) {
+    ShareLogger::init(path);
+}
+
+/// Set before the workers start; each thread reads it once.
+pub fn set_near_miss_factor(factor: f64) {
+    NEAR_MISS_FACTOR.store(factor.to_bits(), Ordering::SeqCst);
+}
+
+/// How far above the share threshold a hash may be and still be logged as a near miss.
+pub fn near_miss_factor() -> Option<f64> {
+    Some(f64::from_bits(NEAR_MISS_FACTOR.load(Ordering::SeqCst))).filter(|&factor| factor > 0.0)
+}
+
+/// Records a hash that came within the near-miss factor of the target without
+/// meeting it, as a `near_miss` line in the shares file. Not sent to the events file.
+pub fn log_near_miss(job_id: &str, nonce: &[u8], hash: &[u8], difficulty: u64) {
+    ShareLogger::write_record(&ShareRecord {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        job_id,
+        nonce: hex::encode(nonce),
+        hash: hex::encode(hash),
+        difficulty,
+        status: "near_miss",
+        reason: None,
+    });
+}
+
+/// Records the hashed blob of every accepted share, for verification against monerod.
+pub fn init_share_blob_logger(path: &Path) {
+    ShareLogger::init_blob_log(path);
+}
+
+pub fn log_share(share: &Share, status: &str, reason: Option<&str>) {
+    crate::event_log::emit(
+        "share",
+        &serde_json::json!({ "status": status, "job_id": share.job_id, "difficulty": share.difficulty, "reason": reason }),
+    );
+    ShareLogger::log_share(share, status, reason);
+}
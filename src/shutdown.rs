@@ -0,0 +1,105 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Set by the SIGTERM handler; the mining loops poll it and wind down.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Longest a `sleep` goes without checking for a shutdown request.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[cfg(target_os = "linux")]
+extern "C" fn on_sigterm(_signal: libc::c_int) {
+    // Only an atomic store: anything more is not async-signal-safe.
+    REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Turns SIGTERM (what systemd's `stop` sends) into a graceful shutdown instead of
+/// an immediate kill. Install once the mining loops, which poll `requested`, are about to run.
+pub fn install_sigterm_handler() {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        libc::signal(libc::SIGTERM, on_sigterm as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+/// While enabled, Ctrl+C (SIGINT) winds down like SIGTERM instead of killing the
+/// process, so state only a graceful exit restores (the terminal's line mode) is
+/// put back. Disabling returns Ctrl+C to its default.
+pub fn set_graceful_sigint(enabled: bool) {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let handler = if enabled { on_sigterm as extern "C" fn(libc::c_int) as libc::sighandler_t } else { libc::SIG_DFL };
+        libc::signal(libc::SIGINT, handler);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = enabled;
+}
+
+/// Asks the loops to wind down as if SIGTERM had arrived, e.g. after a fatal error.
+pub fn request() {
+    REQUESTED.store(true, Ordering::Relaxed);
+}
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Relaxed)
+}
+
+/// `thread::sleep` that returns early once shutdown is requested, so long
+/// back-offs don't hold up a supervisor's stop.
+pub fn sleep(duration: Duration) {
+    sleep_until_set(&REQUESTED, duration);
+}
+
+fn sleep_until_set(flag: &AtomicBool, duration: Duration) {
+    let deadline = Instant::now() + duration;
+    while !flag.load(Ordering::Relaxed) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        thread::sleep(left.min(POLL_INTERVAL));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn sleep_lasts_the_full_duration() {
+        let flag = AtomicBool::new(false);
+        let started = Instant::now();
+        sleep_until_set(&flag, Duration::from_millis(250));
+        assert!(started.elapsed() >= Duration::from_millis(250));
+    }
+
+    #[test]
+    fn sleep_returns_at_once_when_already_requested() {
+        let flag = AtomicBool::new(true);
+        let started = Instant::now();
+        sleep_until_set(&flag, Duration::from_secs(60));
+        assert!(started.elapsed() < POLL_INTERVAL);
+    }
+
+    #[test]
+    fn sleep_wakes_within_a_poll_interval_of_a_request() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let requester = {
+            let flag = Arc::clone(&flag);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(150));
+                flag.store(true, Ordering::Relaxed);
+            })
+        };
+        let started = Instant::now();
+        sleep_until_set(&flag, Duration::from_secs(60));
+        let slept = started.elapsed();
+        requester.join().unwrap();
+        assert!(slept >= Duration::from_millis(150));
+        assert!(slept < Duration::from_millis(150) + POLL_INTERVAL * 5, "slept {slept:?}");
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{
+    display::Display,
+    job::{Job, MIN_BLOB_LEN, NONCE_OFFSET},
+    share::Share,
+    stats,
+    stratum::{self, connect::{self, ConnectOptions}, ShareOutcome},
+    worker::Worker,
+};
+use crate::color::Colorize;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::{hash_map::RandomState, VecDeque},
+    hash::{BuildHasher, Hasher},
+    io::{self, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often the daemon is asked for a fresh template to notice new blocks.
+const TEMPLATE_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+const REPORT_INTERVAL: Duration = Duration::from_secs(30);
+// Templates kept so a share found just before a refresh can still be submitted.
+const KEPT_TEMPLATES: usize = 4;
+
+#[derive(Debug, Deserialize)]
+struct BlockTemplate {
+    blocktemplate_blob: String,
+    blockhashing_blob: String,
+    difficulty: u64,
+    height: u64,
+    prev_hash: String,
+    seed_hash: String,
+}
+
+/// Solo mining against a monerod daemon's JSON-RPC, producing the same `Job`s
+/// and consuming the same `Share`s as the stratum backend.
+pub struct Solo {
+    daemon: String,
+    wallet: String,
+    options: ConnectOptions,
+    prev_hash: String,
+    // (job id, full block template) for the most recent templates.
+    templates: VecDeque<(String, Vec<u8>)>,
+    extra_nonce: [u8; 8],
+}
+
+/// Random bytes for the template's reserved area. Miners paying the same wallet
+/// from the same daemon would otherwise get identical templates and hash the same
+/// 32-bit nonce space over each other.
+fn instance_extra_nonce() -> [u8; 8] {
+    // `RandomState` is keyed from the OS's random source for each process.
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    hasher.finish().to_le_bytes()
+}
+
+impl Solo {
+    pub fn new(daemon: &str, wallet: &str, options: &ConnectOptions) -> Self {
+        Self {
+            daemon: daemon.into(),
+            wallet: wallet.into(),
+            options: options.clone(),
+            prev_hash: String::new(),
+            templates: VecDeque::new(),
+            extra_nonce: instance_extra_nonce(),
+        }
+    }
+
+    /// Fetches a block template and returns a job for it if the chain tip moved
+    /// since the last one.
+    pub fn poll_job(&mut self) -> io::Result<Option<Job>> {
+        // The daemon writes our extra nonce into the miner tx and computes the
+        // hashing blob for it, so it never has to be patched in here.
+        let template: BlockTemplate = serde_json::from_value(self.call(
+            "get_block_template",
+            json!({ "wallet_address": self.wallet, "extra_nonce": hex::encode(self.extra_nonce) }),
+        )?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if template.prev_hash == self.prev_hash {
+            return Ok(None);
+        }
+        self.prev_hash = template.prev_hash.clone();
+
+        let decode = |hex_str: &str| hex::decode(hex_str).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+        let job = Job {
+            id: format!("{:x}", template.height),
+            blob: decode(&template.blockhashing_blob)?,
+            seed: decode(&template.seed_hash)?,
+            target: u32::MAX,
+            share_difficulty: Some(template.difficulty),
+            height: Some(template.height),
+            algo: None,
+            pool: 0,
+            reserved_offset: None,
+            extra_nonce: None,
+        };
+        job.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let block = decode(&template.blocktemplate_blob)?;
+        if block.len() < MIN_BLOB_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "block template too short for the nonce"));
+        }
+
+        self.templates.push_back((job.id.clone(), block));
+        if self.templates.len() > KEPT_TEMPLATES {
+            self.templates.pop_front();
+        }
+        Ok(Some(job))
+    }
+
+    /// Submits the block the share completes; only hashes meeting network
+    /// difficulty are shares in solo mode.
+    pub fn submit(&mut self, share: &Share) -> io::Result<ShareOutcome> {
+        let Some(block) = self.block_for(share) else {
+            return Ok(ShareOutcome::Rejected("stale block template".into()));
+        };
+        match self.call("submit_block", json!([hex::encode(block)])) {
+            Ok(result) if result["status"] == "OK" => {
+                // Our own block moves the tip; fetch the next template right away.
+                self.prev_hash.clear();
+                Ok(ShareOutcome::Accepted)
+            }
+            Ok(result) => Ok(ShareOutcome::Rejected(result["status"].to_string())),
+            Err(e) if e.kind() == io::ErrorKind::Other => Ok(ShareOutcome::Rejected(e.to_string())),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The share's nonce in the newest template for its height: when the tip is
+    /// replaced at the same height, the job id stays the same but the workers have
+    /// moved on to the new template.
+    fn block_for(&self, share: &Share) -> Option<Vec<u8>> {
+        let (_, template) = self.templates.iter().rev().find(|(id, _)| *id == share.job_id)?;
+        let mut block = template.clone();
+        block[NONCE_OFFSET..MIN_BLOB_LEN].copy_from_slice(&share.nonce);
+        Some(block)
+    }
+
+    /// One JSON-RPC call over a fresh HTTP/1.1 connection; monerod closes it afterwards anyway.
+    fn call(&self, method: &str, params: Value) -> io::Result<Value> {
+        let body = json!({ "jsonrpc": "2.0", "id": "0", "method": method, "params": params }).to_string();
+        let mut stream = connect::connect(&self.daemon, &self.options).map_err(|e| stratum::pool_unreachable(&self.daemon, e))?;
+        stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+        write!(
+            stream,
+            "POST /json_rpc HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.daemon,
+            body.len(),
+            body
+        )?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        let (head, payload) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed HTTP response from daemon"))?;
+        if !head.starts_with("HTTP/1.1 200") && !head.starts_with("HTTP/1.0 200") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("daemon answered {}", head.lines().next().unwrap_or_default()),
+            ));
+        }
+        let mut reply: Value = serde_json::from_str(payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if let Some(error) = reply.get("error") {
+            return Err(io::Error::other(format!("{} failed: {}", method, error["message"])));
+        }
+        Ok(reply["result"].take())
+    }
+}
+
+/// Mines against `daemon` until an unrecoverable error, paying blocks to `wallet`.
+pub fn run(
+    daemon: &str,
+    wallet: &str,
+    options: &ConnectOptions,
+    start_worker: impl FnOnce(Job) -> Worker,
+) -> io::Result<()> {
+    let mut solo = Solo::new(daemon, wallet, options);
+    let initial_job = solo
+        .poll_job()?
+        .ok_or_else(|| io::Error::other("daemon returned no block template"))?;
+    println!("{} Solo mining on {} at height {} (difficulty {})", "⛏".cyan(), daemon.yellow(), u64::from_str_radix(&initial_job.id, 16).unwrap_or(0), initial_job.pool_difficulty());
+    println!();
+    let worker = start_worker(initial_job);
+    crate::shutdown::install_sigterm_handler();
+
+    let mut refresh_timer = Instant::now();
+    let mut refresh_now = false;
+    let mut report_timer = Instant::now();
+    loop {
+        if crate::shutdown::requested() {
+            println!("{}", Display::SHUTDOWN_REQUESTED);
+            break;
+        }
+        if refresh_now || refresh_timer.elapsed() >= TEMPLATE_REFRESH_INTERVAL {
+            refresh_timer = Instant::now();
+            refresh_now = false;
+            match solo.poll_job() {
+                Ok(Some(job)) => {
+                    Display::job_received(&job.id);
+                    worker.work(job);
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("{} Failed to fetch block template: {}", "⚠️".yellow(), e),
+            }
+        }
+
+        while let Ok(share) = worker.try_recv_share() {
+            match solo.submit(&share) {
+                Ok(outcome) => {
+                    stats::record_share_result(&outcome);
+                    match outcome {
+                        ShareOutcome::Accepted => {
+                            crate::share_logger::log_share(&share, "accepted", None);
+                            println!("{} {}", "🎉".green(), format!("Block found at height {}!", u64::from_str_radix(&share.job_id, 16).unwrap_or(0)).green().bold());
+                            Display::share_bell(worker.is_warmed_up());
+                            refresh_now = true;
+                        }
+                        ShareOutcome::Rejected(reason) => {
+                            crate::share_logger::log_share(&share, "rejected", Some(&reason));
+                            Display::share_rejected(&share.job_id, &reason);
+                        }
+                    }
+                }
+                Err(e) => {
+                    crate::share_logger::log_share(&share, "not_submitted", Some(&e.to_string()));
+                    eprintln!("{} Block for height {} not submitted: {}", "⚠️".yellow(), share.job_id, e);
+                }
+            }
+        }
+
+        if report_timer.elapsed() >= REPORT_INTERVAL {
+            report_timer = Instant::now();
+            let elapsed = worker.get_elapsed_time();
+            if worker.is_warmed_up() {
+                let details = vec![
+                    ("Blocks", Display::format_luck(stats::accepted_shares(), worker.get_expected_shares())),
+                    ("Threads", Display::format_thread_modes(&worker.get_thread_modes())),
+                ];
+                Display::hash_rate_report(worker.get_hash_rate(), elapsed, &details);
+            }
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    Display::shutdown_summary(worker.get_elapsed_time(), worker.get_total_hashes(), stats::accepted_shares(), stats::rejected_shares());
+    Display::reject_breakdown(&stats::reject_reasons());
+    worker.shutdown();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instances_get_their_own_extra_nonce() {
+        let options = ConnectOptions::default();
+        let first = Solo::new("127.0.0.1:18081", "wallet", &options);
+        let second = Solo::new("127.0.0.1:18081", "wallet", &options);
+        assert_ne!(first.extra_nonce, second.extra_nonce);
+    }
+
+    fn share(job_id: &str) -> Share {
+        Share {
+            nonce: vec![0xde, 0xad, 0xbe, 0xef],
+            hash: vec![0; 32],
+            job_id: job_id.into(),
+            blob: Vec::new(),
+            difficulty: 1,
+            found_at: Instant::now(),
+            pool: 0,
+        }
+    }
+
+    #[test]
+    fn block_is_built_on_the_newest_template_for_its_height() {
+        let mut solo = Solo::new("127.0.0.1:18081", "wallet", &ConnectOptions::default());
+        // Height 0x10 seen twice: the first tip was orphaned and replaced.
+        for (id, fill) in [("f", 1), ("10", 2), ("10", 3)] {
+            solo.templates.push_back((id.into(), vec![fill; MIN_BLOB_LEN + 8]));
+        }
+        let block = solo.block_for(&share("10")).unwrap();
+        assert_eq!(block[0], 3);
+        assert_eq!(&block[NONCE_OFFSET..MIN_BLOB_LEN], &[0xde, 0xad, 0xbe, 0xef]);
+        assert!(solo.block_for(&share("e")).is_none());
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::OnceCell;
+
+use crate::stratum::{RejectReason, RejectRecovery, ShareOutcome};
+
+/// Difficulty changes kept for the vardiff history.
+const DIFFICULTY_HISTORY_LEN: usize = 16;
+/// Accepted shares the rolling latency average is taken over.
+const SHARE_LATENCY_WINDOW: usize = 20;
+/// Share results the rolling reject ratio is taken over.
+const REJECT_WINDOW: usize = 20;
+
+// Session-wide share counters, fed from the pool's responses to our submits.
+static ACCEPTED_SHARES: AtomicU64 = AtomicU64::new(0);
+static REJECTED_SHARES: AtomicU64 = AtomicU64::new(0);
+// Rejections by `RejectReason`, indexed by its position in `RejectReason::ALL`.
+static REJECT_REASONS: Mutex<[u64; RejectReason::ALL.len()]> = Mutex::new([0; RejectReason::ALL.len()]);
+// Rejections that made us resync the job or log in again.
+static RESYNCS: AtomicU64 = AtomicU64::new(0);
+static RELOGINS: AtomicU64 = AtomicU64::new(0);
+// Worker threads the stall monitor replaced.
+static THREAD_RESTARTS: AtomicU64 = AtomicU64::new(0);
+// (when, difficulty) for each change in the difficulty jobs arrive with, oldest first.
+static STARTED: OnceCell<Instant> = OnceCell::new();
+static FIRST_ACCEPTED: OnceCell<Duration> = OnceCell::new();
+static DIFFICULTY_HISTORY: Mutex<VecDeque<(Instant, u64)>> = Mutex::new(VecDeque::new());
+static SHARE_LATENCIES: Mutex<VecDeque<Duration>> = Mutex::new(VecDeque::new());
+// Found-to-sent times of the most recent submits.
+static SUBMIT_LATENCIES: Mutex<VecDeque<Duration>> = Mutex::new(VecDeque::new());
+// Whether each of the most recent share results was a rejection.
+static RECENT_REJECTS: Mutex<VecDeque<bool>> = Mutex::new(VecDeque::new());
+// Worker threads currently rebuilding their cache/dataset for a new seed.
+static REBUILDING_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Marks the start of mining; time to first share is measured from here.
+pub fn mark_started() {
+    let _ = STARTED.set(Instant::now());
+}
+
+pub fn record_share_result(outcome: &ShareOutcome) {
+    record_pool_share(None, outcome);
+    record_totals(outcome);
+}
+
+/// Like `record_share_result`, for a result from `url` while another pool may be
+/// active (dual-pool mode).
+pub fn record_pool_share_result(url: &str, outcome: &ShareOutcome) {
+    record_pool_share(Some(url), outcome);
+    record_totals(outcome);
+}
+
+fn record_totals(outcome: &ShareOutcome) {
+    {
+        let mut recent = RECENT_REJECTS.lock().unwrap();
+        if recent.len() == REJECT_WINDOW {
+            recent.pop_front();
+        }
+        recent.push_back(matches!(outcome, ShareOutcome::Rejected(_)));
+    }
+    match outcome {
+        ShareOutcome::Accepted => {
+            if ACCEPTED_SHARES.fetch_add(1, Ordering::Relaxed) == 0 {
+                if let Some(started) = STARTED.get() {
+                    let _ = FIRST_ACCEPTED.set(started.elapsed());
+                }
+            }
+        }
+        ShareOutcome::Rejected(message) => {
+            REJECTED_SHARES.fetch_add(1, Ordering::Relaxed);
+            let reason = RejectReason::classify(message);
+            if let Some(index) = RejectReason::ALL.iter().position(|&r| r == reason) {
+                REJECT_REASONS.lock().unwrap()[index] += 1;
+            }
+        }
+    }
+}
+
+/// Rejections so far per category, leaving out categories with none.
+pub fn reject_reasons() -> Vec<(RejectReason, u64)> {
+    let counts = REJECT_REASONS.lock().unwrap();
+    RejectReason::ALL.iter().zip(counts.iter()).filter(|(_, &count)| count > 0).map(|(&reason, &count)| (reason, count)).collect()
+}
+
+/// Fraction of the last `REJECT_WINDOW` share results that were rejected; `None`
+/// until that many results are in, so a single early reject can't trip it.
+pub fn recent_reject_ratio() -> Option<f64> {
+    let recent = RECENT_REJECTS.lock().unwrap();
+    (recent.len() == REJECT_WINDOW).then(|| recent.iter().filter(|&&rejected| rejected).count() as f64 / REJECT_WINDOW as f64)
+}
+
+/// Starts the reject window over, e.g. after switching to another pool.
+pub fn clear_recent_rejects() {
+    RECENT_REJECTS.lock().unwrap().clear();
+}
+
+/// Records the found-to-accepted time of an accepted share.
+pub fn record_share_latency(latency: Duration) {
+    record_latency(&SHARE_LATENCIES, latency);
+}
+
+/// Average found-to-accepted time over the last accepted shares. High values
+/// point at network or pool-side delay, which makes shares go stale.
+pub fn average_share_latency() -> Option<Duration> {
+    average_latency(&SHARE_LATENCIES)
+}
+
+/// Records how long a share took from being found to being written to the pool.
+pub fn record_submit_latency(latency: Duration) {
+    record_latency(&SUBMIT_LATENCIES, latency);
+}
+
+/// Average found-to-sent time over the last submits: the part of the share
+/// latency spent in the miner, which `--end-batch-on-share` aims to cut.
+pub fn average_submit_latency() -> Option<Duration> {
+    average_latency(&SUBMIT_LATENCIES)
+}
+
+fn record_latency(latencies: &Mutex<VecDeque<Duration>>, latency: Duration) {
+    let mut latencies = latencies.lock().unwrap();
+    if latencies.len() == SHARE_LATENCY_WINDOW {
+        latencies.pop_front();
+    }
+    latencies.push_back(latency);
+}
+
+fn average_latency(latencies: &Mutex<VecDeque<Duration>>) -> Option<Duration> {
+    let latencies = latencies.lock().unwrap();
+    let count = u32::try_from(latencies.len()).ok().filter(|&count| count > 0)?;
+    Some(latencies.iter().sum::<Duration>() / count)
+}
+
+/// Called by a worker thread when a seed change makes it rebuild its cache/dataset.
+pub fn dataset_rebuild_started() {
+    REBUILDING_THREADS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Called when that thread is back to hashing.
+pub fn dataset_rebuild_finished() {
+    REBUILDING_THREADS.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Whether any thread is still rebuilding; the hash rate dips to zero meanwhile.
+pub fn is_rebuilding_dataset() -> bool {
+    REBUILDING_THREADS.load(Ordering::Relaxed) > 0
+}
+
+/// Time from `mark_started` to the first accepted share, once there is one.
+pub fn time_to_first_share() -> Option<Duration> {
+    FIRST_ACCEPTED.get().copied()
+}
+
+pub fn accepted_shares() -> u64 {
+    ACCEPTED_SHARES.load(Ordering::Relaxed)
+}
+
+pub fn rejected_shares() -> u64 {
+    REJECTED_SHARES.load(Ordering::Relaxed)
+}
+
+pub fn record_recovery(recovery: RejectRecovery) {
+    match recovery {
+        RejectRecovery::None => {}
+        RejectRecovery::Resync => {
+            RESYNCS.fetch_add(1, Ordering::Relaxed);
+        }
+        RejectRecovery::Relogin => {
+            RELOGINS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+pub fn resyncs() -> u64 {
+    RESYNCS.load(Ordering::Relaxed)
+}
+
+pub fn relogins() -> u64 {
+    RELOGINS.load(Ordering::Relaxed)
+}
+
+pub fn record_thread_restart() {
+    THREAD_RESTARTS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn thread_restarts() -> u64 {
+    THREAD_RESTARTS.load(Ordering::Relaxed)
+}
+
+/// Notes the difficulty of a received job; only changes are kept.
+pub fn record_difficulty(difficulty: u64) {
+    let mut history = DIFFICULTY_HISTORY.lock().unwrap();
+    if history.back().is_some_and(|&(_, last)| last == difficulty) {
+        return;
+    }
+    if history.len() == DIFFICULTY_HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back((Instant::now(), difficulty));
+}
+
+pub fn difficulty_history() -> Vec<(Instant, u64)> {
+    DIFFICULTY_HISTORY.lock().unwrap().iter().copied().collect()
+}
+
+/// Time, shares and hashes while connected to one pool URL.
+#[derive(Debug, Clone, Default)]
+pub struct PoolStats {
+    pub url: String,
+    pub connected: Duration,
+    pub accepted: u64,
+    pub rejected: u64,
+    pub hashes: u64,
+}
+
+impl PoolStats {
+    /// Average hash rate over the time this pool was the active, connected one.
+    pub fn average_hash_rate(&self) -> f64 {
+        let secs = self.connected.as_secs_f64();
+        if secs > 0.0 {
+            self.hashes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+// Index into `pools`, when it became active, and the lifetime hash count then.
+struct ActivePool {
+    index: usize,
+    since: Instant,
+    hashes_at_start: u64,
+}
+
+struct PoolTracker {
+    pools: Vec<PoolStats>,
+    active: Option<ActivePool>,
+}
+
+static POOLS: Mutex<PoolTracker> = Mutex::new(PoolTracker { pools: Vec::new(), active: None });
+
+fn lifetime_hashes() -> u64 {
+    crate::hash_rate::get_hash_rate_tracker().lock().unwrap().get_lifetime_hashes()
+}
+
+/// Makes `url` the pool time, hashes and share results are attributed to; `None`
+/// while disconnected. Cheap to call every loop iteration when nothing changed.
+pub fn set_active_pool(url: Option<&str>) {
+    let mut tracker = POOLS.lock().unwrap();
+    let current = tracker.active.as_ref().map(|active| tracker.pools[active.index].url.as_str());
+    if current == url {
+        return;
+    }
+    let hashes = lifetime_hashes();
+    if let Some(active) = tracker.active.take() {
+        let pool = &mut tracker.pools[active.index];
+        pool.connected += active.since.elapsed();
+        pool.hashes += hashes.saturating_sub(active.hashes_at_start);
+    }
+    let Some(url) = url else {
+        return;
+    };
+    let index = match tracker.pools.iter().position(|pool| pool.url == url) {
+        Some(index) => index,
+        None => {
+            tracker.pools.push(PoolStats { url: url.to_string(), ..PoolStats::default() });
+            tracker.pools.len() - 1
+        }
+    };
+    tracker.active = Some(ActivePool { index, since: Instant::now(), hashes_at_start: hashes });
+}
+
+/// Attributes a share result to `url`, or to the active pool when `None`.
+fn record_pool_share(url: Option<&str>, outcome: &ShareOutcome) {
+    let mut tracker = POOLS.lock().unwrap();
+    let index = match url {
+        Some(url) => tracker.pools.iter().position(|pool| pool.url == url),
+        None => tracker.active.as_ref().map(|active| active.index),
+    };
+    let Some(index) = index else {
+        return;
+    };
+    match outcome {
+        ShareOutcome::Accepted => tracker.pools[index].accepted += 1,
+        ShareOutcome::Rejected(_) => tracker.pools[index].rejected += 1,
+    }
+}
+
+/// Per-pool totals in the order the pools were first used, including the
+/// active pool's current stretch.
+pub fn pool_stats() -> Vec<PoolStats> {
+    let hashes = lifetime_hashes();
+    let tracker = POOLS.lock().unwrap();
+    let mut pools = tracker.pools.clone();
+    if let Some(active) = &tracker.active {
+        let pool = &mut pools[active.index];
+        pool.connected += active.since.elapsed();
+        pool.hashes += hashes.saturating_sub(active.hashes_at_start);
+    }
+    pools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_average_covers_the_last_window() {
+        let latencies = Mutex::new(VecDeque::new());
+        assert_eq!(average_latency(&latencies), None);
+        record_latency(&latencies, Duration::from_millis(1000));
+        for _ in 0..SHARE_LATENCY_WINDOW {
+            record_latency(&latencies, Duration::from_millis(4));
+        }
+        // The first, slow share has dropped out of the window.
+        assert_eq!(average_latency(&latencies), Some(Duration::from_millis(4)));
+    }
+}
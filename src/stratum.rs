@@ -1,19 +1,34 @@
+pub mod capture;
+pub mod connect;
+#[cfg(test)]
+pub mod mock;
+pub mod replay;
 mod rpc;
 
-use crate::{job::Job, share::Share};
+use crate::{clock::{Clock, SystemClock}, job::Job, share::Share, stats};
+use connect::ConnectOptions;
 use rpc::{
-    request::{LoginParams, KeepAlivedParams, Request, SubmitParams},
+    request::{GetJobParams, HashrateParams, LoginParams, KeepAlivedParams, Request, SubmitParams},
     response::{LoginResult, Response, StatusResult, SubscribeResult},
 };
 use serde::Deserialize;
+use serde_json::Value;
 use std::{
+    collections::HashMap,
     io::{self, BufReader, BufWriter, BufRead},
-    net::TcpStream,
-    sync::mpsc::{self, Receiver, TryRecvError},
+    net::{SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, TryRecvError},
+        Arc, Mutex,
+    },
     thread,
+    time::{Duration, Instant},
 };
 
-use rpc::response::{SetDifficultyParams, SetExtranonceParams};
+use rpc::response::{ClientReconnectParams, SetDifficultyParams, SetExtranonceParams};
+use serde::Serialize;
+use std::io::Write;
 
 #[derive(Deserialize, Debug)]
 #[serde(untagged)]
@@ -23,6 +38,10 @@ enum MiningNotifyParams {
         job_id: String,
         blob_hex: String, 
         seed_hash_hex: String,
+        #[serde(default)]
+        algo: Option<String>,
+        #[serde(default)]
+        height: Option<u64>,
     },
 }
 
@@ -30,7 +49,7 @@ impl TryFrom<MiningNotifyParams> for Job {
     type Error = Box<dyn std::error::Error>;
 
     fn try_from(params: MiningNotifyParams) -> Result<Self, Self::Error> {
-        match params {
+        let job = match params {
             MiningNotifyParams::Array(arr) => {
                 if arr.len() < 3 {
                     return Err("mining.notify array must have at least 3 elements".into());
@@ -44,25 +63,55 @@ impl TryFrom<MiningNotifyParams> for Job {
                 let seed_hash_hex = arr[2].as_str()
                     .ok_or("seed_hash_hex must be a string")?;
                 
-                Ok(Job {
+                Job {
                     id: job_id,
-                    blob: hex::decode(blob_hex)?,
-                    seed: hex::decode(seed_hash_hex)?,
+                    blob: crate::job::decode_blob(blob_hex)?,
+                    seed: crate::job::decode_blob(seed_hash_hex)?,
                     target: u32::MAX, 
-                })
+                    share_difficulty: None,
+                    height: None,
+                    algo: None,
+                    pool: 0,
+                    reserved_offset: None,
+                    extra_nonce: None,
+                }
             },
-            MiningNotifyParams::Object { job_id, blob_hex, seed_hash_hex } => {
-                Ok(Job {
+            MiningNotifyParams::Object { job_id, blob_hex, seed_hash_hex, algo, height } => {
+                Job {
                     id: job_id,
-                    blob: hex::decode(blob_hex)?,
-                    seed: hex::decode(seed_hash_hex)?,
+                    blob: crate::job::decode_blob(&blob_hex)?,
+                    seed: crate::job::decode_blob(&seed_hash_hex)?,
                     target: u32::MAX, 
-                })
+                    share_difficulty: None,
+                    height,
+                    algo,
+                    pool: 0,
+                    reserved_offset: None,
+                    extra_nonce: None,
+                }
             }
-        }
+        };
+        job.validate()?;
+        Ok(job)
     }
 }
 
+// The last foreign algorithm warned about, so a pool on the wrong coin is reported
+// once rather than on every job.
+static WARNED_ALGO: Mutex<Option<String>> = Mutex::new(None);
+
+/// Warns when the pool hands out work for an algorithm other than RandomX; the
+/// miner hashes it anyway, but every share will be rejected.
+fn warn_on_foreign_algo(job: &Job) {
+    let Some(algo) = job.foreign_algo() else {
+        return;
+    };
+    let mut warned = WARNED_ALGO.lock().unwrap();
+    if warned.as_deref() != Some(algo) {
+        tracing::warn!(job_id = %job.id, "Pool sent a job for algorithm {:?}, but this miner only hashes {}; shares will be rejected", algo, crate::job::ALGO);
+        *warned = Some(algo.to_string());
+    }
+}
 
 #[derive(Deserialize, Debug)]
 #[serde(untagged)]
@@ -76,24 +125,571 @@ enum PoolMessage {
     Response(Response<StatusResult>), // Simplified response handling, based on working example
 }
 
+/// The pool's verdict on a submitted share, matched to it by request id.
+#[derive(Debug)]
+pub enum ShareOutcome {
+    Accepted,
+    Rejected(String),
+}
+
+/// What a rejection says about our session with the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectRecovery {
+    /// An ordinary bad share; nothing to fix.
+    None,
+    /// The pool no longer knows our job; ask for a fresh one.
+    Resync,
+    /// The pool no longer knows our login; log in again.
+    Relogin,
+}
+
+/// Category of a pool's rejection message, for the per-reason reject counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The hash doesn't meet the pool's target: usually a target or nonce bug.
+    LowDifficulty,
+    /// Found for a job the pool had already moved past: usually latency.
+    Stale,
+    Duplicate,
+    /// The pool doesn't know the job id at all.
+    UnknownJob,
+    Unauthenticated,
+    /// The pool computed a different hash or refused the nonce/result format.
+    Invalid,
+    Other,
+}
+
+impl RejectReason {
+    pub const ALL: [RejectReason; 7] = [
+        RejectReason::LowDifficulty,
+        RejectReason::Stale,
+        RejectReason::Duplicate,
+        RejectReason::UnknownJob,
+        RejectReason::Unauthenticated,
+        RejectReason::Invalid,
+        RejectReason::Other,
+    ];
+
+    /// Maps a pool's free-form rejection message onto a category by keyword.
+    pub fn classify(message: &str) -> Self {
+        let message = message.to_lowercase();
+        let has = |keywords: &[&str]| keywords.iter().any(|keyword| message.contains(keyword));
+        if has(&["unauthenticated", "unauthorized", "not logged in"]) {
+            RejectReason::Unauthenticated
+        } else if has(&["job not found", "unknown job", "invalid job id"]) {
+            RejectReason::UnknownJob
+        } else if has(&["duplicate"]) {
+            RejectReason::Duplicate
+        } else if has(&["stale", "expired", "outdated"]) {
+            RejectReason::Stale
+        } else if has(&["low difficulty", "low diff", "above target"]) {
+            RejectReason::LowDifficulty
+        } else if has(&["invalid", "bad share", "incorrect", "malformed"]) {
+            RejectReason::Invalid
+        } else {
+            RejectReason::Other
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RejectReason::LowDifficulty => "low difficulty",
+            RejectReason::Stale => "stale",
+            RejectReason::Duplicate => "duplicate",
+            RejectReason::UnknownJob => "unknown job",
+            RejectReason::Unauthenticated => "unauthenticated",
+            RejectReason::Invalid => "invalid",
+            RejectReason::Other => "other",
+        }
+    }
+}
+
+impl ShareOutcome {
+    pub fn reject_reason(&self) -> Option<RejectReason> {
+        match self {
+            ShareOutcome::Accepted => None,
+            ShareOutcome::Rejected(message) => Some(RejectReason::classify(message)),
+        }
+    }
+
+    pub fn recovery(&self) -> RejectRecovery {
+        match self.reject_reason() {
+            Some(RejectReason::Unauthenticated) => RejectRecovery::Relogin,
+            Some(RejectReason::UnknownJob) => RejectRecovery::Resync,
+            _ => RejectRecovery::None,
+        }
+    }
+}
+
+/// The pool answered our login with an error instead of a job.
+#[derive(Debug)]
+pub struct LoginRefused {
+    pub message: String,
+}
+
+impl LoginRefused {
+    /// True for a temporary IP ban or rate limit, typically after reconnecting too often.
+    pub fn is_ban(&self) -> bool {
+        let message = self.message.to_lowercase();
+        ["banned", "rate limit", "rate-limit", "too many connections", "too many requests", "temporarily blocked"]
+            .iter()
+            .any(|keyword| message.contains(keyword))
+    }
+}
+
+impl std::fmt::Display for LoginRefused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pool refused login: {}", self.message)
+    }
+}
+
+impl std::error::Error for LoginRefused {}
+
+/// The pool's address could not be resolved or connected to.
+#[derive(Debug)]
+pub struct PoolUnreachable {
+    pub url: String,
+    pub source: io::Error,
+}
+
+impl std::fmt::Display for PoolUnreachable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cannot reach pool {}: {}", self.url, self.source)
+    }
+}
+
+impl std::error::Error for PoolUnreachable {}
+
+/// Wraps a connect error as `PoolUnreachable`, keeping its kind so retry logic matching on it is unaffected.
+pub fn pool_unreachable(url: &str, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), PoolUnreachable { url: url.to_string(), source: e })
+}
+
+/// Whether a failed `login`/`reconnect` was the pool banning or rate-limiting us.
+pub fn is_ban(e: &io::Error) -> bool {
+    e.get_ref()
+        .and_then(|inner| inner.downcast_ref::<LoginRefused>())
+        .is_some_and(LoginRefused::is_ban)
+}
+
+/// Wait before retrying a reconnect that failed for any reason but a ban.
+const RECONNECT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Paces reconnect attempts: after a failure the next one is due `ban_cooldown`
+/// later if the pool banned us, `RECONNECT_RETRY_DELAY` later otherwise.
+#[derive(Debug)]
+pub struct ReconnectBackoff<C: Clock = SystemClock> {
+    clock: C,
+    ban_cooldown: Duration,
+    next_attempt: Option<Instant>,
+}
+
+impl ReconnectBackoff {
+    pub fn new(ban_cooldown: Duration) -> Self {
+        Self::with_clock(ban_cooldown, SystemClock)
+    }
+}
+
+impl<C: Clock> ReconnectBackoff<C> {
+    pub fn with_clock(ban_cooldown: Duration, clock: C) -> Self {
+        Self { clock, ban_cooldown, next_attempt: None }
+    }
+
+    /// Records a failed attempt and returns how long until the next one is due.
+    pub fn failed(&mut self, e: &io::Error) -> Duration {
+        let delay = if is_ban(e) { self.ban_cooldown } else { RECONNECT_RETRY_DELAY };
+        self.next_attempt = Some(self.clock.now() + delay);
+        delay
+    }
+
+    /// Time left until the next attempt is due; zero once it is.
+    pub fn remaining(&self) -> Duration {
+        self.next_attempt.map_or(Duration::ZERO, |at| at.saturating_duration_since(self.clock.now()))
+    }
+
+    /// Blocks until the next attempt is due, or shutdown is requested.
+    pub fn wait(&self) {
+        crate::shutdown::sleep(self.remaining());
+    }
+}
+
+/// When the pool last sent a job on a connection (or we logged in), for the
+/// watchdog that reconnects a connection gone silent without erroring.
+#[derive(Debug)]
+pub struct JobWatchdog<C: Clock = SystemClock> {
+    clock: C,
+    last_job_at: Mutex<Instant>,
+}
+
+impl JobWatchdog {
+    fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock> JobWatchdog<C> {
+    fn with_clock(clock: C) -> Self {
+        Self { last_job_at: Mutex::new(clock.now()), clock }
+    }
+
+    /// Call whenever a job arrives.
+    fn feed(&self) {
+        *self.last_job_at.lock().unwrap() = self.clock.now();
+    }
+
+    fn silence(&self) -> Duration {
+        self.clock.now().saturating_duration_since(*self.last_job_at.lock().unwrap())
+    }
+
+    /// Whether `timeout` has passed without a job; a zero timeout never expires.
+    fn expired(&self, timeout: Duration) -> bool {
+        !timeout.is_zero() && self.silence() >= timeout
+    }
+}
+
+#[derive(Debug)]
+pub struct ShareResult {
+    pub share: Share,
+    pub outcome: ShareOutcome,
+    /// From the worker finding the share to the pool's response arriving.
+    pub latency: Duration,
+}
+
+/// State shared between a `Stratum` and the listener thread of its current connection.
+#[derive(Debug, Clone)]
+struct ListenerShared {
+    connected: Arc<AtomicBool>,
+    pending_submits: Arc<Mutex<HashMap<u32, Share>>>,
+    result_tx: mpsc::Sender<ShareResult>,
+    redirect: Arc<Mutex<Option<Redirect>>>,
+    // Last mining.set_difficulty; applies to the mining.notify jobs that follow it.
+    difficulty: Arc<Mutex<Option<u64>>>,
+    job_watchdog: Arc<JobWatchdog>,
+    // Whether the last login's result listed the "hashrate" extension.
+    supports_hash_rate: Arc<AtomicBool>,
+}
+
+/// Where a `client.reconnect` asked us to go, applied by the next `reconnect`.
+#[derive(Debug)]
+struct Redirect {
+    url: String,
+    delay: Duration,
+}
+
+// Longest wait honoured from a `client.reconnect`, so a pool can't stall us indefinitely.
+const MAX_REDIRECT_DELAY: Duration = Duration::from_secs(60);
+
+impl Redirect {
+    /// `None` for a malformed endpoint. Missing host/port mean the current pool.
+    fn from_params(params: ClientReconnectParams, current_url: &str) -> Option<Self> {
+        let ClientReconnectParams::Array(params) = params;
+        let (current_host, current_port) = current_url.rsplit_once(':')?;
+        let host = match params.first().and_then(Value::as_str) {
+            Some(host) if !host.is_empty() => host,
+            _ => current_host,
+        };
+        let port = match params.get(1) {
+            Some(Value::Number(n)) => n.as_u64()?.to_string(),
+            Some(Value::String(s)) if !s.is_empty() => s.clone(),
+            _ => current_port.to_string(),
+        };
+        let wait = params.get(2).and_then(Value::as_u64).unwrap_or(0);
+
+        let valid_host = host.chars().all(|c| c.is_ascii_alphanumeric() || "-.[]:".contains(c));
+        if !valid_host || !matches!(port.parse::<u16>(), Ok(p) if p > 0) {
+            return None;
+        }
+        let host = if host.contains(':') && !host.starts_with('[') { format!("[{}]", host) } else { host.to_string() };
+        Some(Self {
+            url: format!("{}:{}", host, port),
+            delay: Duration::from_secs(wait).min(MAX_REDIRECT_DELAY),
+        })
+    }
+}
+
+// Submits use ids from here up, so they never collide with login/keepalived (id 1).
+const FIRST_SUBMIT_ID: u32 = 2;
+
+/// A serialized request waiting for the sender thread; `submit_id` is set for shares.
+#[derive(Debug)]
+struct Outgoing {
+    line: String,
+    submit_id: Option<u32>,
+}
+
+/// Starts the sender thread for one connection. It writes queued requests in order
+/// and exits when the `Stratum` drops its sender, e.g. on reconnect. A failed write
+/// takes the connection down the same way the listener does. Generic over the
+/// writer so a slow or failing connection can be simulated.
+fn spawn_sender<W: Write + Send + 'static>(
+    mut writer: W,
+    shared: &ListenerShared,
+    reconnect_tx: &mpsc::Sender<()>,
+) -> mpsc::Sender<Outgoing> {
+    let (outgoing_tx, outgoing_rx) = mpsc::channel::<Outgoing>();
+    let connected = shared.connected.clone();
+    let pending_submits = shared.pending_submits.clone();
+    let reconnect_tx = reconnect_tx.clone();
+    thread::spawn(move || {
+        for Outgoing { line, submit_id } in outgoing_rx {
+            capture::sent(&line);
+            let written = writeln!(writer, "{}", line).and_then(|_| writer.flush());
+            if written.is_ok() {
+                if let Some(found_at) = submit_id.and_then(|id| pending_submits.lock().unwrap().get(&id).map(|share| share.found_at)) {
+                    stats::record_submit_latency(found_at.elapsed());
+                }
+            }
+            if let Err(e) = written {
+                if let Some(share) = submit_id.and_then(|id| pending_submits.lock().unwrap().remove(&id)) {
+                    crate::share_logger::log_share(&share, "not_submitted", Some(&e.to_string()));
+                    tracing::warn!("Share for job {} not submitted: {}", share.job_id, e);
+                }
+                tracing::error!("Sender thread write failed: {}", e);
+                // Only the first side to notice the drop asks for a reconnect.
+                if connected.swap(false, Ordering::SeqCst) {
+                    let _ = reconnect_tx.send(());
+                }
+                break;
+            }
+        }
+    });
+    outgoing_tx
+}
+
+/// Reads pool messages until the connection fails or the pool redirects us,
+/// dispatching jobs, share results and difficulty changes. Generic over the
+/// reader so scripted pool lines can be fed in without a socket.
+fn listen<R: BufRead>(
+    mut reader: R,
+    listener_url: String,
+    shared: ListenerShared,
+    job_tx: mpsc::Sender<Job>,
+    reconnect_tx: mpsc::Sender<()>,
+) {
+    let span = tracing::info_span!("listener");
+    let _enter = span.enter();
+    let ListenerShared { connected, pending_submits, result_tx, redirect, difficulty, job_watchdog, .. } = shared;
+    loop {
+        let mut line = String::new();
+        let read_result = reader.read_line(&mut line);
+        if read_result.is_err() || line.is_empty() {
+            let e = read_result.err().unwrap_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "EOF while reading line"));
+            tracing::error!("Connection error in listener (read_line): {}", e);
+            connected.store(false, Ordering::SeqCst);
+            reconnect_tx.send(()).unwrap();
+            break;
+        }
+        capture::received(&line);
+        tracing::debug!("Raw JSON from pool: {}", line.trim());
+
+        // Attempt to parse the JSON to understand its structure before specific deserialization.
+        // This helps in debugging issues with pool messages that might not conform strictly to expected types.
+        let method = match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(json_value) => {
+                tracing::debug!("Parsed JSON structure: {:#}", json_value);
+
+                // Responses to our submits are matched by id before the generic dispatch below,
+                // which cannot tell a submit result from any other response.
+                if json_value.get("method").is_none() {
+                    let id = json_value.get("id").and_then(|id| id.as_u64()).and_then(|id| u32::try_from(id).ok());
+                    let share = id.and_then(|id| pending_submits.lock().unwrap().remove(&id));
+                    if let Some(share) = share {
+                        let outcome = match json_value.get("error").filter(|e| !e.is_null()) {
+                            Some(err) => ShareOutcome::Rejected(
+                                err.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error").to_string(),
+                            ),
+                            None => ShareOutcome::Accepted,
+                        };
+                        match &outcome {
+                            ShareOutcome::Accepted => tracing::info!("Share accepted by pool."),
+                            ShareOutcome::Rejected(reason) => tracing::warn!("Share rejected by pool: {}", reason),
+                        }
+                        let latency = share.found_at.elapsed();
+                        let _ = result_tx.send(ShareResult { share, outcome, latency });
+                        continue;
+                    }
+
+                    // A `getjob` reply carries the job itself as the result.
+                    if let Some(result) = json_value.get("result").filter(|r| r.get("blob").is_some() && r.get("job_id").is_some()) {
+                        match serde_json::from_value::<Job>(result.clone()) {
+                            Ok(job) if job.validate().is_ok() => {
+                                tracing::info!(job_id = %job.id, "Received job from getjob reply.");
+                                warn_on_foreign_algo(&job);
+                                job_watchdog.feed();
+                                if job_tx.send(job).is_err() {
+                                    break;
+                                }
+                            }
+                            _ => tracing::warn!("Ignoring malformed getjob reply: {}", line.trim()),
+                        }
+                        continue;
+                    }
+                }
+
+                // `client.reconnect` carries array params like mining.notify, so the untagged
+                // dispatch below can't tell them apart; pick it out by method name.
+                if json_value.get("method").and_then(|m| m.as_str()) == Some("client.reconnect") {
+                    let params = json_value.get("params").cloned().unwrap_or(Value::Array(Vec::new()));
+                    match serde_json::from_value::<ClientReconnectParams>(params).ok().and_then(|p| Redirect::from_params(p, &listener_url)) {
+                        Some(target) => {
+                            tracing::warn!("Pool requested reconnect to {} in {:?}", target.url, target.delay);
+                            *redirect.lock().unwrap() = Some(target);
+                            connected.store(false, Ordering::SeqCst);
+                            reconnect_tx.send(()).unwrap();
+                            break;
+                        }
+                        None => tracing::warn!("Ignoring client.reconnect with an invalid endpoint: {}", line.trim()),
+                    }
+                    continue;
+                }
+
+                // Log the method type if present, to aid in understanding message flow.
+                if let Some(method) = json_value.get("method").and_then(|m| m.as_str()) {
+                    tracing::info!("Received method call: {}", method);
+                    // Specific tracing for known methods can be useful for filtering logs.
+                    match method {
+                        "mining.notify" | "job" => {
+                            tracing::debug!("Method '{}' identified, proceeding to specific parsing.", method);
+                        },
+                        _ => {
+                            tracing::debug!("Received unhandled method: {}", method);
+                        }
+                    }
+                }
+                json_value.get("method").and_then(|m| m.as_str()).map(str::to_string)
+            },
+            Err(e) => {
+                tracing::error!("Failed to parse JSON into a generic Value: {}", e);
+                None
+            }
+        };
+
+        // mining.set_difficulty and mining.set_extranonce carry array params like
+        // mining.notify, which the untagged dispatch tries first; pick them by name.
+        let message = match method.as_deref() {
+            Some("mining.set_difficulty") => serde_json::from_str(&line).map(PoolMessage::SetDifficulty),
+            Some("mining.set_extranonce") => serde_json::from_str(&line).map(PoolMessage::SetExtranonce),
+            _ => serde_json::from_str::<PoolMessage>(&line),
+        };
+        match message {
+            Ok(msg) => match msg {
+                PoolMessage::Response(response) => {
+                    if let Some(err) = response.error {
+                        tracing::warn!("{}", err.message);
+                    } else if let Some(status_result) = response.result {
+                        match status_result.status.as_str() {
+                            "OK" => {
+                                tracing::info!("Share accepted by pool.");
+                            },
+                            "KEEPALIVED" => tracing::debug!("keepalived"),
+                            _ => tracing::warn!("Unknown status: {}", status_result.status),
+                        }
+                    } else {
+                        tracing::warn!("Received response with no error and no result.");
+                    }
+                }
+                PoolMessage::ResponseBool(response) => {
+                    if let Some(err) = response.error {
+                        tracing::warn!("{}", err.message);
+                    } else {
+                        tracing::debug!("Received boolean response: {:?}", response.result);
+                    }
+                }
+                PoolMessage::ResponseSubscribe(response) => {
+                    if let Some(err) = response.error {
+                        tracing::warn!("{}", err.message);
+                    } else {
+                        tracing::debug!("Received subscribe response in listener: {:?}", response.result);
+                    }
+                }
+                PoolMessage::NewJob(request) => {
+                    tracing::info!(job_id = %request.params.id, "Received new job from pool (method 'job').");
+                    if let Err(e) = request.params.validate() {
+                        tracing::warn!("Skipping malformed job: {}", e);
+                    } else {
+                        warn_on_foreign_algo(&request.params);
+                        job_watchdog.feed();
+                        if let Err(e) = job_tx.send(request.params) {
+                            tracing::error!("Failed to send job to worker: {}", e);
+                            connected.store(false, Ordering::SeqCst);
+                            reconnect_tx.send(()).unwrap();
+                            break;
+                        }
+                    }
+                }
+                PoolMessage::MiningNotify(request) => {
+                    tracing::info!("Received new job from pool (method 'mining.notify').");
+                    match Job::try_from(request.params) {
+                        Ok(mut job) => {
+                            job.share_difficulty = *difficulty.lock().unwrap();
+                            let job_id = job.id.clone();
+                            tracing::info!(job_id = %job_id, "Successfully parsed mining.notify job.");
+                            warn_on_foreign_algo(&job);
+                            job_watchdog.feed();
+                            if let Err(e) = job_tx.send(job) {
+                                tracing::error!("Failed to send job to worker: {}", e);
+                                connected.store(false, Ordering::SeqCst);
+                                reconnect_tx.send(()).unwrap();
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            tracing::error!(error = %e, "Failed to convert mining.notify params to Job.");
+                        }
+                    }
+                }
+                PoolMessage::SetDifficulty(request) => {
+                    let SetDifficultyParams::Array(params) = request.params;
+                    if let Some(difficulty_value) = params.first().and_then(|d| d.as_u64()).filter(|&d| d > 0) {
+                        tracing::info!("Received mining.set_difficulty in listener: {}", difficulty_value);
+                        *difficulty.lock().unwrap() = Some(difficulty_value);
+                    } else {
+                        tracing::warn!("Invalid difficulty value in mining.set_difficulty in listener.");
+                    }
+                },
+                PoolMessage::SetExtranonce(request) => {
+                    let SetExtranonceParams::Array(params) = request.params;
+                    let extranonce = params.first().and_then(Value::as_str).unwrap_or_default().to_string();
+                    let extranonce_size = params.get(1).and_then(Value::as_u64).unwrap_or_default();
+                    tracing::info!("Received mining.set_extranonce in listener: extranonce={}, size={}", extranonce, extranonce_size);
+                },
+            },
+            Err(e) => {
+                tracing::error!("Connection error in listener: {}", e);
+                connected.store(false, Ordering::SeqCst);
+                reconnect_tx.send(()).unwrap();
+                break;
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Stratum {
     url: String,
     user: String,
     pass: String,
+    options: ConnectOptions,
     login_id: String,
     writer: BufWriter<TcpStream>,
     job_rx: Receiver<Job>,
     reconnect_tx: mpsc::Sender<()>,
     reconnect_rx: Receiver<()>,
+    shared: ListenerShared,
+    result_rx: Receiver<ShareResult>,
+    next_submit_id: u32,
+    // Set with `--async-submit`; requests then go through the connection's sender thread.
+    sender: Option<mpsc::Sender<Outgoing>>,
 }
 
 impl Stratum {
-    #[tracing::instrument]
+    #[tracing::instrument(skip(shared))]
     fn _connect_and_login(
         url: &str,
         user: &str,
         pass: &str,
+        options: &ConnectOptions,
+        shared: ListenerShared,
     ) -> io::Result<(
         String,
         BufWriter<TcpStream>,
@@ -101,7 +697,11 @@ impl Stratum {
         mpsc::Sender<()>,
         Receiver<()>,
     )> {
-        let stream = TcpStream::connect(url)?;
+        let stream = connect::connect(url, options).map_err(|e| pool_unreachable(url, e))?;
+        capture::connected(url);
+        if let (Ok(peer), Ok(local)) = (stream.peer_addr(), stream.local_addr()) {
+            tracing::info!(%peer, %local, "Connected to {}", url);
+        }
         stream.set_read_timeout(None)?;
         let mut reader = BufReader::new(stream.try_clone()?);
         let mut writer = BufWriter::new(stream.try_clone()?);
@@ -113,145 +713,41 @@ impl Stratum {
         let initial_job: Job;
 
         tracing::debug!("Sending login.");
+        capture::redact(user);
         rpc::send(
             &mut writer,
             &Request::new_login(LoginParams {
                 login: user.into(),
                 pass: pass.into(),
+                algo: vec![crate::job::ALGO.into()],
+                rigid: options.rig_id.clone(),
             }),
         )?;
-        let response = rpc::recv::<Response<LoginResult>>(&mut reader)?;
+        let response = rpc::recv::<_, Response<LoginResult>>(&mut reader)?;
         if let Some(result) = response.result {
-            let LoginResult { id, job, .. } = result;
+            let LoginResult { id, job, extensions, .. } = result;
             tracing::debug!("Received initial job from pool: {}", job.id);
+            job.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            warn_on_foreign_algo(&job);
+            let supports_hash_rate = extensions.iter().any(|extension| extension == "hashrate");
+            tracing::debug!(?extensions, supports_hash_rate, "Pool extensions");
+            shared.supports_hash_rate.store(supports_hash_rate, Ordering::SeqCst);
             login_id = id;
             initial_job = job;
         } else {
-            let msg = response.error.unwrap().message;
-            tracing::warn!("{}", msg);
-            return Err(io::Error::other(msg));
+            let message = response.error.unwrap().message;
+            tracing::warn!("{}", message);
+            return Err(io::Error::other(LoginRefused { message }));
         }
 
         job_tx.send(initial_job).unwrap();
-        let reconnect_tx_clone = reconnect_tx.clone();
-        thread::spawn(move || {
-            let span = tracing::info_span!("listener");
-            let _enter = span.enter();
-                loop {
-                    let mut line = String::new();
-                    let read_result = reader.read_line(&mut line);
-                    if read_result.is_err() || line.is_empty() {
-                        let e = read_result.err().unwrap_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "EOF while reading line"));
-                        tracing::error!("Connection error in listener (read_line): {}", e);
-                        reconnect_tx_clone.send(()).unwrap();
-                        break;
-                    }
-                    tracing::debug!("Raw JSON from pool: {}", line.trim());
-                    
-                    // Attempt to parse the JSON to understand its structure before specific deserialization.
-                    // This helps in debugging issues with pool messages that might not conform strictly to expected types.
-                    match serde_json::from_str::<serde_json::Value>(&line) {
-                        Ok(json_value) => {
-                            tracing::debug!("Parsed JSON structure: {:#}", json_value);
-                            
-                            // Log the method type if present, to aid in understanding message flow.
-                            if let Some(method) = json_value.get("method").and_then(|m| m.as_str()) {
-                                tracing::info!("Received method call: {}", method);
-                                // Specific tracing for known methods can be useful for filtering logs.
-                                match method {
-                                    "mining.notify" | "job" => {
-                                        tracing::debug!("Method '{}' identified, proceeding to specific parsing.", method);
-                                    },
-                                    _ => {
-                                        tracing::debug!("Received unhandled method: {}", method);
-                                    }
-                                }
-                            }
-                        },
-                        Err(e) => {
-                            tracing::error!("Failed to parse JSON into a generic Value: {}", e);
-                        }
-                    }
-                    
-                    match serde_json::from_str::<PoolMessage>(&line) {
-                        Ok(msg) => match msg {
-                            PoolMessage::Response(response) => {
-                                if let Some(err) = response.error {
-                                    tracing::warn!("{}", err.message);
-                                } else if let Some(status_result) = response.result {
-                                    match status_result.status.as_str() {
-                                        "OK" => {
-                                            tracing::info!("Share accepted by pool.");
-                                        },
-                                        "KEEPALIVED" => tracing::debug!("keepalived"),
-                                        _ => tracing::warn!("Unknown status: {}", status_result.status),
-                                    }
-                                } else {
-                                    tracing::warn!("Received response with no error and no result.");
-                                }
-                            }
-                            PoolMessage::ResponseBool(response) => {
-                                if let Some(err) = response.error {
-                                    tracing::warn!("{}", err.message);
-                                } else {
-                                    tracing::debug!("Received boolean response: {:?}", response.result);
-                                }
-                            }
-                            PoolMessage::ResponseSubscribe(response) => {
-                                if let Some(err) = response.error {
-                                    tracing::warn!("{}", err.message);
-                                } else {
-                                    tracing::debug!("Received subscribe response in listener: {:?}", response.result);
-                                }
-                            }
-                            PoolMessage::NewJob(request) => {
-                                tracing::info!(job_id = %request.params.id, "Received new job from pool (method 'job').");
-                                if let Err(e) = job_tx.send(request.params) {
-                                    tracing::error!("Failed to send job to worker: {}", e);
-                                    reconnect_tx_clone.send(()).unwrap();
-                                    break;
-                                }
-                            }
-                            PoolMessage::MiningNotify(request) => {
-                                tracing::info!("Received new job from pool (method 'mining.notify').");
-                                match Job::try_from(request.params) {
-                                    Ok(job) => {
-                                        let job_id = job.id.clone();
-                                        tracing::info!(job_id = %job_id, "Successfully parsed mining.notify job.");
-                                        if let Err(e) = job_tx.send(job) {
-                                            tracing::error!("Failed to send job to worker: {}", e);
-                                            reconnect_tx_clone.send(()).unwrap();
-                                            break;
-                                        }
-                                    }
-                                    Err(e) => {
-                                        tracing::error!(error = %e, "Failed to convert mining.notify params to Job.");
-                                    }
-                                }
-                            }
-                            PoolMessage::SetDifficulty(request) => {
-                                let SetDifficultyParams::Array(params) = request.params;
-                                if let Some(difficulty_value) = params[0].as_u64() {
-                                    tracing::info!("Received mining.set_difficulty in listener: {}", difficulty_value);
-                                } else {
-                                    tracing::warn!("Invalid difficulty value in mining.set_difficulty in listener.");
-                                }
-                            },
-                            PoolMessage::SetExtranonce(request) => {
-                                let SetExtranonceParams::Array(params) = request.params;
-                                let extranonce = params[0].as_str().unwrap_or_default().to_string();
-                                let extranonce_size = params[1].as_u64().unwrap_or_default();
-                                tracing::info!("Received mining.set_extranonce in listener: extranonce={}, size={}", extranonce, extranonce_size);
-                            },
-                        },
-                        Err(e) => {
-                            tracing::error!("Connection error in listener: {}", e);
-                            reconnect_tx_clone.send(()).unwrap();
-                            break;
-                        }
-                    }
-                }
-            });
+        shared.job_watchdog.feed();
+        // Submits still pending belong to the previous session and will never be answered.
+        shared.pending_submits.lock().unwrap().clear();
+        shared.connected.store(true, Ordering::SeqCst);
+        let listener_url = url.to_string();
+        let listener_reconnect_tx = reconnect_tx.clone();
+        thread::spawn(move || listen(reader, listener_url, shared, job_tx, listener_reconnect_tx));
         Ok((
             login_id,
             writer,
@@ -262,53 +758,196 @@ impl Stratum {
     }
 
     #[tracing::instrument]
-    pub fn login(url: &str, user: &str, pass: &str) -> io::Result<Self> {
+    pub fn login(url: &str, user: &str, pass: &str, options: &ConnectOptions) -> io::Result<Self> {
+        let (result_tx, result_rx) = mpsc::channel();
+        let shared = ListenerShared {
+            connected: Arc::new(AtomicBool::new(false)),
+            pending_submits: Arc::new(Mutex::new(HashMap::new())),
+            result_tx,
+            redirect: Arc::new(Mutex::new(None)),
+            difficulty: Arc::new(Mutex::new(None)),
+            job_watchdog: Arc::new(JobWatchdog::new()),
+            supports_hash_rate: Arc::new(AtomicBool::new(false)),
+        };
         let (login_id, writer, job_rx, reconnect_tx, reconnect_rx) =
-            Self::_connect_and_login(url, user, pass)?;
+            Self::_connect_and_login(url, user, pass, options, shared.clone())?;
+        let sender = if options.async_submit {
+            Some(spawn_sender(BufWriter::new(writer.get_ref().try_clone()?), &shared, &reconnect_tx))
+        } else {
+            None
+        };
         Ok(Self {
             url: url.into(),
             user: user.into(),
             pass: pass.into(),
+            options: options.clone(),
             login_id,
             writer,
             job_rx,
             reconnect_tx,
             reconnect_rx,
+            shared,
+            result_rx,
+            next_submit_id: FIRST_SUBMIT_ID,
+            sender,
         })
     }
 
-    pub fn submit(&mut self, share: Share) -> io::Result<()> {
+    /// Developer tool: logs in on a fresh connection, sends one arbitrary request and returns
+    /// the raw reply line. Object params without an "id" get the session's login id.
+    pub fn probe(url: &str, user: &str, pass: &str, options: &ConnectOptions, method: &str, mut params: serde_json::Value) -> io::Result<String> {
+        const PROBE_ID: u32 = 2;
+        const PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+        let stream = connect::connect(url, options).map_err(|e| pool_unreachable(url, e))?;
+        capture::connected(url);
+        capture::redact(user);
+        stream.set_read_timeout(Some(PROBE_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+
+        rpc::send(&mut writer, &Request::new_login(LoginParams { login: user.into(), pass: pass.into(), algo: vec![crate::job::ALGO.into()], rigid: options.rig_id.clone() }))?;
+        let response = rpc::recv::<_, Response<LoginResult>>(&mut reader)?;
+        let login_id = match (response.result, response.error) {
+            (Some(result), _) => result.id,
+            (None, Some(err)) => return Err(io::Error::other(err.message)),
+            (None, None) => return Err(io::Error::other("login response has neither result nor error")),
+        };
+
+        if let Some(obj) = params.as_object_mut() {
+            obj.entry("id").or_insert_with(|| login_id.into());
+        }
+        rpc::send(&mut writer, &Request { method: method.into(), params, id: PROBE_ID })?;
+
+        // Skip unrelated traffic (e.g. job notifications) until our reply shows up.
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "pool closed the connection"));
+            }
+            capture::received(&line);
+            let is_reply = serde_json::from_str::<serde_json::Value>(&line)
+                .ok()
+                .and_then(|v| v.get("id").and_then(|id| id.as_u64()))
+                == Some(PROBE_ID as u64);
+            if is_reply {
+                return Ok(line.trim_end().to_string());
+            }
+            tracing::debug!("Ignoring while probing: {}", line.trim());
+        }
+    }
+
+    pub fn submit(&mut self, share: &Share) -> io::Result<()> {
+        if !self.is_connected() {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "pool connection is down"));
+        }
         tracing::info!("Submitting share for job_id: {}", share.job_id);
-        rpc::send(
-            &mut self.writer,
-            &Request::new_submit_standard(SubmitParams {
+        let request_id = self.next_submit_id;
+        self.next_submit_id = self.next_submit_id.checked_add(1).unwrap_or(FIRST_SUBMIT_ID);
+        self.shared.pending_submits.lock().unwrap().insert(request_id, share.clone());
+        let request = Request {
+            id: request_id,
+            ..Request::new_submit_standard(SubmitParams {
                 id: self.login_id.clone(),
-                job_id: share.job_id,
-                nonce: share.nonce,
-                result: share.hash,
-            }),
-        )?;
+                job_id: share.job_id.clone(),
+                nonce: share.nonce.clone(),
+                result: share.hash.clone(),
+                rigid: self.options.rig_id.clone().filter(|_| self.options.tag_shares),
+            })
+        };
+        if let Err(e) = self.send(&request, Some(request_id)) {
+            self.shared.pending_submits.lock().unwrap().remove(&request_id);
+            return Err(e);
+        }
+        // With a sender thread, the share is only queued here; that thread records it once written.
+        if self.sender.is_none() {
+            stats::record_submit_latency(share.found_at.elapsed());
+        }
         tracing::debug!("Share submitted, awaiting new job from pool.");
         Ok(())
     }
+    /// Asks the pool to resend the current job, e.g. after it reported our job as unknown.
+    /// If the request can't be sent the connection is treated as down, so the caller's
+    /// reconnect picks up a fresh job instead of mining the stale one indefinitely.
+    pub fn request_job(&mut self) -> io::Result<()> {
+        let request = Request::new_get_job(GetJobParams {
+            id: self.login_id.clone(),
+        });
+        let sent = self.send(&request, None);
+        if sent.is_err() && self.shared.connected.swap(false, Ordering::SeqCst) {
+            let _ = self.reconnect_tx.send(());
+        }
+        sent
+    }
+
     pub fn keep_alive(&mut self) -> io::Result<()> {
-        rpc::send(
-            &mut self.writer,
-            &Request::new_keep_alive(KeepAlivedParams {
-                id: self.login_id.clone(),
-            }),
-        )
+        let request = Request::new_keep_alive(KeepAlivedParams {
+            id: self.login_id.clone(),
+        });
+        self.send(&request, None)
+    }
+
+    /// Sends the miner's hash rate for the pool's per-worker dashboard; does nothing
+    /// unless the pool advertised the "hashrate" extension at login.
+    pub fn report_hash_rate(&mut self, hash_rate: f64) -> io::Result<()> {
+        if !self.shared.supports_hash_rate.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let request = Request::new_hashrate(HashrateParams {
+            id: self.login_id.clone(),
+            hashrate: hash_rate,
+        });
+        self.send(&request, None)
     }
+
+    /// Writes `request` directly, or queues it for the sender thread so all writes
+    /// on the connection stay in order.
+    fn send<S: Serialize>(&mut self, request: &Request<S>, submit_id: Option<u32>) -> io::Result<()> {
+        let Some(sender) = &self.sender else {
+            return rpc::send(&mut self.writer, request);
+        };
+        let line = serde_json::to_string(request)?;
+        sender
+            .send(Outgoing { line, submit_id })
+            .map_err(|_| io::Error::new(io::ErrorKind::NotConnected, "sender thread has stopped"))
+    }
+    /// Returns the newest queued job, skipping any it superseded. A standby connection's
+    /// queue is only read when it becomes active, so older entries there are stale.
     pub fn try_recv_job(&self) -> Result<Job, TryRecvError> {
-        self.job_rx.try_recv()
+        let job = self.job_rx.try_recv()?;
+        Ok(self.newest_job(job))
+    }
+
+    /// Like `try_recv_job`, but waits up to `timeout` for a job to arrive.
+    pub fn recv_job_timeout(&self, timeout: Duration) -> Result<Job, RecvTimeoutError> {
+        let job = self.job_rx.recv_timeout(timeout)?;
+        Ok(self.newest_job(job))
+    }
+
+    fn newest_job(&self, mut job: Job) -> Job {
+        while let Ok(newer) = self.job_rx.try_recv() {
+            job = newer;
+        }
+        stats::record_difficulty(job.pool_difficulty());
+        job
     }
 
     #[tracing::instrument]
     pub fn reconnect(&mut self) -> io::Result<()> {
+        let redirect = self.shared.redirect.lock().unwrap().take();
+        if let Some(Redirect { url, delay }) = redirect {
+            tracing::info!("Following pool redirect to {} after {:?}", url, delay);
+            // Up to a minute; SIGTERM must still end it promptly.
+            crate::shutdown::sleep(delay);
+            self.url = url;
+        }
         tracing::info!("Attempting to reconnect...");
         let (login_id, writer, job_rx, reconnect_tx, reconnect_rx) =
-            Self::_connect_and_login(&self.url, &self.user, &self.pass)?;
+            Self::_connect_and_login(&self.url, &self.user, &self.pass, &self.options, self.shared.clone())?;
 
+        if self.options.async_submit {
+            self.sender = Some(spawn_sender(BufWriter::new(writer.get_ref().try_clone()?), &self.shared, &reconnect_tx));
+        }
         self.login_id = login_id;
         self.writer = writer;
         self.job_rx = job_rx;
@@ -319,7 +958,424 @@ impl Stratum {
         Ok(())
     }
 
+    /// Whether the connection is up but has gone `timeout` without a job, which most
+    /// likely means it stalled without erroring. A zero timeout disables the check.
+    pub fn job_timeout_expired(&self, timeout: Duration) -> bool {
+        self.is_connected() && self.shared.job_watchdog.expired(timeout)
+    }
+
     pub fn try_reconnect_signal(&self) -> Result<(), TryRecvError> {
         self.reconnect_rx.try_recv()
     }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The address the pool hostname resolved to for the current connection.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.writer.get_ref().peer_addr()
+    }
+
+    /// False from the moment the listener sees the connection drop until `reconnect` succeeds.
+    pub fn is_connected(&self) -> bool {
+        self.shared.connected.load(Ordering::SeqCst)
+    }
+
+    pub fn try_recv_share_result(&self) -> Result<ShareResult, TryRecvError> {
+        self.result_rx.try_recv()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use mock::{MockConnection, BLOB, SEED_HASH};
+    use serde_json::json;
+
+    const URL: &str = "pool.example.com:3333";
+
+    /// What one `listen` run over a scripted connection produced.
+    struct Listened {
+        jobs: Vec<Job>,
+        results: Vec<ShareResult>,
+        reconnect_requested: bool,
+        shared: ListenerShared,
+    }
+
+    fn share(job_id: &str) -> Share {
+        Share {
+            nonce: vec![1, 2, 3, 4],
+            hash: vec![0; 32],
+            job_id: job_id.to_string(),
+            blob: Vec::new(),
+            difficulty: 10_000,
+            found_at: Instant::now(),
+            pool: 0,
+        }
+    }
+
+    fn job_line(job_id: &str) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"job","params":{{"job_id":"{}","blob":"{}","seed_hash":"{}","target":"b88d0600"}}}}"#,
+            job_id,
+            BLOB,
+            SEED_HASH
+        )
+    }
+
+    /// Connection state with `pending` submits awaiting an answer.
+    fn listener_shared(pending: &[(u32, &str)]) -> (ListenerShared, Receiver<ShareResult>) {
+        let (result_tx, result_rx) = mpsc::channel();
+        let shared = ListenerShared {
+            connected: Arc::new(AtomicBool::new(true)),
+            pending_submits: Arc::new(Mutex::new(pending.iter().map(|&(id, job_id)| (id, share(job_id))).collect())),
+            result_tx,
+            redirect: Arc::new(Mutex::new(None)),
+            difficulty: Arc::new(Mutex::new(None)),
+            job_watchdog: Arc::new(JobWatchdog::new()),
+            supports_hash_rate: Arc::new(AtomicBool::new(false)),
+        };
+        (shared, result_rx)
+    }
+
+    /// Runs the listener over `connection` until it stops, with `pending` submits
+    /// awaiting an answer.
+    fn listen_to(connection: MockConnection, pending: &[(u32, &str)]) -> Listened {
+        let (job_tx, job_rx) = mpsc::channel();
+        let (reconnect_tx, reconnect_rx) = mpsc::channel();
+        let (shared, result_rx) = listener_shared(pending);
+        listen(connection, URL.to_string(), shared.clone(), job_tx, reconnect_tx);
+        Listened {
+            jobs: job_rx.try_iter().collect(),
+            results: result_rx.try_iter().collect(),
+            reconnect_requested: reconnect_rx.try_recv().is_ok(),
+            shared,
+        }
+    }
+
+    #[test]
+    fn job_notifications_reach_the_worker() {
+        let listened = listen_to(MockConnection::new([job_line("1").as_str(), job_line("2").as_str()]), &[]);
+        let ids: Vec<_> = listened.jobs.iter().map(|job| job.id.as_str()).collect();
+        assert_eq!(ids, ["1", "2"]);
+        assert_eq!(listened.jobs[0].pool_difficulty(), 10_000);
+        assert_eq!(listened.jobs[0].blob.len(), BLOB.len() / 2);
+    }
+
+    #[test]
+    fn malformed_jobs_are_skipped() {
+        let short = format!(
+            r#"{{"method":"job","params":{{"job_id":"short","blob":"0e0e","seed_hash":"{}","target":"b88d0600"}}}}"#,
+            SEED_HASH
+        );
+        let listened = listen_to(MockConnection::new([short.as_str(), job_line("ok").as_str()]), &[]);
+        let ids: Vec<_> = listened.jobs.iter().map(|job| job.id.as_str()).collect();
+        assert_eq!(ids, ["ok"]);
+    }
+
+    #[test]
+    fn set_difficulty_applies_to_following_notify_jobs() {
+        let notify = format!(r#"{{"id":null,"method":"mining.notify","params":["7","{}","{}"]}}"#, BLOB, "cd".repeat(32));
+        let listened = listen_to(
+            MockConnection::new([r#"{"id":null,"method":"mining.set_difficulty","params":[50000]}"#, notify.as_str()]),
+            &[],
+        );
+        assert_eq!(*listened.shared.difficulty.lock().unwrap(), Some(50_000));
+        assert_eq!(listened.jobs.len(), 1);
+        assert_eq!(listened.jobs[0].id, "7");
+        assert_eq!(listened.jobs[0].pool_difficulty(), 50_000);
+    }
+
+    #[test]
+    fn notify_with_a_short_blob_is_rejected() {
+        let params = MiningNotifyParams::Array(vec![json!("1"), json!(&BLOB[..84]), json!(SEED_HASH)]);
+        assert!(Job::try_from(params).unwrap_err().to_string().contains("need at least 43"));
+        let params = MiningNotifyParams::Array(vec![json!("1"), json!(&BLOB[..86]), json!(SEED_HASH)]);
+        assert!(Job::try_from(params).is_ok());
+    }
+
+    #[test]
+    fn short_set_extranonce_is_tolerated() {
+        let listened = listen_to(MockConnection::new([r#"{"id":null,"method":"mining.set_extranonce","params":[]}"#, job_line("1").as_str()]), &[]);
+        assert_eq!(listened.jobs.len(), 1);
+    }
+
+    #[test]
+    fn getjob_reply_carries_a_job() {
+        let reply = format!(
+            r#"{{"id":1,"jsonrpc":"2.0","error":null,"result":{{"job_id":"9","blob":"{}","seed_hash":"{}","target":"b88d0600"}}}}"#,
+            BLOB,
+            SEED_HASH
+        );
+        let listened = listen_to(MockConnection::new([reply.as_str()]), &[]);
+        assert_eq!(listened.jobs.len(), 1);
+        assert_eq!(listened.jobs[0].id, "9");
+    }
+
+    #[test]
+    fn submit_results_are_matched_by_id() {
+        let listened = listen_to(
+            MockConnection::new([
+                r#"{"id":3,"jsonrpc":"2.0","error":{"code":-1,"message":"Low difficulty share"}}"#,
+                r#"{"id":2,"jsonrpc":"2.0","error":null,"result":{"status":"OK"}}"#,
+                r#"{"id":4,"jsonrpc":"2.0","error":{"code":-1,"message":"Invalid job id"}}"#,
+                r#"{"id":5,"jsonrpc":"2.0","error":{"code":-1,"message":"Unauthenticated"}}"#,
+            ]),
+            &[(2, "a"), (3, "b"), (4, "c"), (5, "d")],
+        );
+        let outcomes: Vec<_> = listened
+            .results
+            .iter()
+            .map(|result| (result.share.job_id.as_str(), result.outcome.reject_reason(), result.outcome.recovery()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("b", Some(RejectReason::LowDifficulty), RejectRecovery::None),
+                ("a", None, RejectRecovery::None),
+                ("c", Some(RejectReason::UnknownJob), RejectRecovery::Resync),
+                ("d", Some(RejectReason::Unauthenticated), RejectRecovery::Relogin),
+            ]
+        );
+        assert!(listened.shared.pending_submits.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn unmatched_responses_are_not_share_results() {
+        let listened = listen_to(
+            MockConnection::new([r#"{"id":1,"jsonrpc":"2.0","error":null,"result":{"status":"KEEPALIVED"}}"#, r#"{"id":9,"jsonrpc":"2.0","error":null,"result":{"status":"OK"}}"#]),
+            &[(2, "a")],
+        );
+        assert!(listened.results.is_empty());
+        assert_eq!(listened.shared.pending_submits.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn eof_drops_the_connection() {
+        let listened = listen_to(MockConnection::new([job_line("1").as_str()]), &[]);
+        assert_eq!(listened.jobs.len(), 1);
+        assert!(listened.reconnect_requested);
+        assert!(!listened.shared.connected.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn read_errors_drop_the_connection() {
+        let listened = listen_to(MockConnection::new([]).failing_with(io::ErrorKind::ConnectionReset), &[(2, "a")]);
+        assert!(listened.reconnect_requested);
+        assert!(!listened.shared.connected.load(Ordering::SeqCst));
+        assert!(listened.results.is_empty());
+    }
+
+    #[test]
+    fn client_reconnect_redirects() {
+        let listened = listen_to(
+            MockConnection::new([r#"{"id":null,"method":"client.reconnect","params":["backup.example.com",4444,10]}"#, job_line("after").as_str()]),
+            &[],
+        );
+        assert!(listened.reconnect_requested);
+        assert!(!listened.shared.connected.load(Ordering::SeqCst));
+        // The listener stops at the redirect; the job after it belongs to the old connection.
+        assert!(listened.jobs.is_empty());
+        let redirect = listened.shared.redirect.lock().unwrap().take().unwrap();
+        assert_eq!(redirect.url, "backup.example.com:4444");
+        assert_eq!(redirect.delay, Duration::from_secs(10));
+    }
+
+    /// A connection whose writes each take `delay`, or all fail.
+    #[derive(Clone, Default)]
+    struct TestWriter {
+        written: Arc<Mutex<Vec<u8>>>,
+        delay: Duration,
+        fail: bool,
+    }
+
+    impl Write for TestWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.fail {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"));
+            }
+            thread::sleep(self.delay);
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn slow_writer_stalls_neither_submits_nor_jobs() {
+        let writer = TestWriter { delay: Duration::from_millis(100), ..TestWriter::default() };
+        let (shared, _results) = listener_shared(&[]);
+        let (reconnect_tx, _reconnect_rx) = mpsc::channel();
+        let sender = spawn_sender(writer.clone(), &shared, &reconnect_tx);
+
+        let started = Instant::now();
+        for id in 2..7 {
+            sender.send(Outgoing { line: format!(r#"{{"id":{}}}"#, id), submit_id: Some(id) }).unwrap();
+        }
+        let listened = listen_to(MockConnection::new([job_line("1").as_str()]), &[]);
+        assert_eq!(listened.jobs.len(), 1);
+        assert!(started.elapsed() < Duration::from_millis(100), "queueing waited for the writer");
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let expected = r#"{"id":2}{"id":3}{"id":4}{"id":5}{"id":6}"#;
+        loop {
+            let written = String::from_utf8(writer.written.lock().unwrap().clone()).unwrap();
+            if written.lines().count() == 5 {
+                assert_eq!(written.lines().collect::<String>(), expected);
+                break;
+            }
+            assert!(Instant::now() < deadline, "sender thread never caught up");
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn failed_write_drops_the_connection_and_the_share() {
+        let writer = TestWriter { fail: true, ..TestWriter::default() };
+        let (shared, _results) = listener_shared(&[(2, "a")]);
+        let (reconnect_tx, reconnect_rx) = mpsc::channel();
+        let sender = spawn_sender(writer, &shared, &reconnect_tx);
+
+        sender.send(Outgoing { line: r#"{"id":2}"#.to_string(), submit_id: Some(2) }).unwrap();
+        reconnect_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(!shared.connected.load(Ordering::SeqCst));
+        assert!(shared.pending_submits.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn requests_are_written_as_json_lines() {
+        let mut connection = MockConnection::new([]);
+        rpc::send(&mut connection, &Request::new_get_job(GetJobParams { id: "session".into() })).unwrap();
+        rpc::send(&mut connection, &Request::new_keep_alive(KeepAlivedParams { id: "session".into() })).unwrap();
+        let sent: Vec<Value> = connection.sent_lines().iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0]["method"], "getjob");
+        assert_eq!(sent[0]["params"]["id"], "session");
+        assert_eq!(sent[1]["method"], "keepalived");
+    }
+
+    #[test]
+    fn login_response_is_read_from_the_connection() {
+        let login = format!(
+            r#"{{"id":1,"jsonrpc":"2.0","error":null,"result":{{"id":"session","status":"OK","extensions":["keepalive","hashrate"],"job":{{"job_id":"1","blob":"{}","seed_hash":"{}","target":"b88d0600"}}}}}}"#,
+            BLOB,
+            SEED_HASH
+        );
+        let mut connection = MockConnection::new([login.as_str()]);
+        let response = rpc::recv::<_, Response<LoginResult>>(&mut connection).unwrap();
+        let result = response.result.unwrap();
+        assert_eq!(result.id, "session");
+        assert_eq!(result.extensions, ["keepalive", "hashrate"]);
+        assert_eq!(result.job.id, "1");
+        assert!(rpc::recv::<_, Response<LoginResult>>(&mut connection).is_err());
+    }
+
+    fn recovery(message: &str) -> RejectRecovery {
+        ShareOutcome::Rejected(message.to_string()).recovery()
+    }
+
+    #[test]
+    fn unknown_job_rejections_resync() {
+        assert_eq!(recovery("Invalid job id"), RejectRecovery::Resync);
+        assert_eq!(recovery("Job not found"), RejectRecovery::Resync);
+        assert_eq!(recovery("unknown job"), RejectRecovery::Resync);
+    }
+
+    #[test]
+    fn unauthenticated_rejections_relogin() {
+        assert_eq!(recovery("Unauthenticated"), RejectRecovery::Relogin);
+        assert_eq!(recovery("Unauthorized worker"), RejectRecovery::Relogin);
+        assert_eq!(recovery("Not logged in"), RejectRecovery::Relogin);
+    }
+
+    #[test]
+    fn ordinary_rejections_need_no_recovery() {
+        assert_eq!(recovery("Low difficulty share"), RejectRecovery::None);
+        assert_eq!(recovery("Block expired"), RejectRecovery::None);
+        assert_eq!(recovery("Duplicate share"), RejectRecovery::None);
+        assert_eq!(recovery("Invalid share"), RejectRecovery::None);
+        assert_eq!(ShareOutcome::Accepted.recovery(), RejectRecovery::None);
+    }
+
+    fn classified(messages: &[&str]) -> Vec<RejectReason> {
+        messages.iter().map(|message| RejectReason::classify(message)).collect()
+    }
+
+    #[test]
+    fn rejections_are_classified_by_keyword() {
+        use RejectReason::*;
+        assert_eq!(classified(&["Low difficulty share", "low diff", "Share above target"]), [LowDifficulty; 3]);
+        assert_eq!(classified(&["Stale share", "Block expired", "outdated job"]), [Stale; 3]);
+        assert_eq!(classified(&["Duplicate share", "DUPLICATE"]), [Duplicate; 2]);
+        assert_eq!(classified(&["Job not found", "unknown job", "Invalid job id"]), [UnknownJob; 3]);
+        assert_eq!(classified(&["Unauthenticated", "Unauthorized worker", "Not logged in"]), [Unauthenticated; 3]);
+        assert_eq!(classified(&["Invalid share", "Bad share", "Incorrect hash", "Malformed nonce"]), [Invalid; 4]);
+        assert_eq!(classified(&["Internal server error", ""]), [Other; 2]);
+    }
+
+    #[test]
+    fn specific_categories_win_over_invalid() {
+        // "Invalid job id" is about the job, not the hash.
+        assert_eq!(RejectReason::classify("Invalid job id"), RejectReason::UnknownJob);
+        assert_eq!(RejectReason::classify("Invalid share: duplicate"), RejectReason::Duplicate);
+        assert_eq!(RejectReason::classify("Invalid share: low difficulty"), RejectReason::LowDifficulty);
+    }
+
+    #[test]
+    fn job_watchdog_expires_after_silence() {
+        let clock = MockClock::new();
+        let watchdog = JobWatchdog::with_clock(clock.clone());
+        let timeout = Duration::from_secs(600);
+        clock.advance(Duration::from_secs(599));
+        assert!(!watchdog.expired(timeout));
+        clock.advance(Duration::from_secs(1));
+        assert!(watchdog.expired(timeout));
+        assert_eq!(watchdog.silence(), timeout);
+    }
+
+    #[test]
+    fn job_watchdog_is_reset_by_each_job() {
+        let clock = MockClock::new();
+        let watchdog = JobWatchdog::with_clock(clock.clone());
+        let timeout = Duration::from_secs(600);
+        for _ in 0..10 {
+            clock.advance(Duration::from_secs(500));
+            watchdog.feed();
+        }
+        assert!(!watchdog.expired(timeout));
+        clock.advance(Duration::from_secs(3600));
+        assert!(watchdog.expired(timeout));
+        assert!(!watchdog.expired(Duration::ZERO));
+    }
+
+    #[test]
+    fn reconnect_backoff_waits_out_the_delay() {
+        let clock = MockClock::new();
+        let mut backoff = ReconnectBackoff::with_clock(Duration::from_secs(300), clock.clone());
+        assert_eq!(backoff.remaining(), Duration::ZERO);
+
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused");
+        assert_eq!(backoff.failed(&refused), RECONNECT_RETRY_DELAY);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(backoff.remaining(), RECONNECT_RETRY_DELAY - Duration::from_secs(2));
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(backoff.remaining(), Duration::ZERO);
+    }
+
+    #[test]
+    fn reconnect_backoff_honours_the_ban_cooldown() {
+        let clock = MockClock::new();
+        let mut backoff = ReconnectBackoff::with_clock(Duration::from_secs(300), clock.clone());
+        let banned = io::Error::other(LoginRefused { message: "IP banned for too many connections".to_string() });
+        assert_eq!(backoff.failed(&banned), Duration::from_secs(300));
+        clock.advance(Duration::from_secs(299));
+        assert_eq!(backoff.remaining(), Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(backoff.remaining(), Duration::ZERO);
+    }
 }
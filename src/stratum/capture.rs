@@ -0,0 +1,143 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, LineWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+/// Replaces wallets, login ids, pool passwords and rig ids in captured lines
+/// unless `--capture-unredacted`.
+const REDACTED: &str = "<redacted>";
+/// Login params blanked in the login request itself: too short or common (`x`)
+/// to replace as substrings of every line like wallets are.
+const LOGIN_SECRETS: [&str; 2] = ["pass", "rigid"];
+
+struct Capture {
+    file: LineWriter<File>,
+    redact: bool,
+    // Wallets (login users) and session ids seen so far.
+    secrets: Vec<String>,
+}
+
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+// Lets the listener skip the login-id lookup when nothing is captured.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Starts teeing every Stratum line to `path` (appended), for `--capture-traffic`.
+pub fn init(path: &Path, redact: bool) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *CAPTURE.lock().unwrap() = Some(Capture { file: LineWriter::new(file), redact, secrets: Vec::new() });
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Registers a string (a wallet or login id) to redact from later lines; login
+/// ids are picked up from the login response by `received` itself.
+pub fn redact(secret: &str) {
+    if let Some(capture) = CAPTURE.lock().unwrap().as_mut() {
+        if !secret.is_empty() && !capture.secrets.iter().any(|s| s == secret) {
+            capture.secrets.push(secret.to_string());
+            // Longest first, so a wallet inside a longer login string is not half-replaced.
+            capture.secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        }
+    }
+}
+
+pub fn connected(url: &str) {
+    write("#", &format!("connected to {}", url));
+}
+
+pub fn sent(line: &str) {
+    write(">", line);
+}
+
+pub fn received(line: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    // The login response carries the session id; register it before the line is written.
+    if let Some(id) = serde_json::from_str::<serde_json::Value>(line)
+        .ok()
+        .and_then(|v| v.pointer("/result/id").and_then(|id| id.as_str()).map(str::to_string))
+    {
+        redact(&id);
+    }
+    write("<", line.trim_end());
+}
+
+/// `line` with `LOGIN_SECRETS` replaced if it's a login request.
+fn redact_login_params(line: String) -> String {
+    let Ok(mut request) = serde_json::from_str::<serde_json::Value>(&line) else {
+        return line;
+    };
+    if request["method"] != "login" {
+        return line;
+    }
+    let Some(params) = request.get_mut("params").and_then(serde_json::Value::as_object_mut) else {
+        return line;
+    };
+    for key in LOGIN_SECRETS {
+        if let Some(value) = params.get_mut(key).filter(|value| value.is_string()) {
+            *value = REDACTED.into();
+        }
+    }
+    request.to_string()
+}
+
+fn write(direction: &str, line: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut guard = CAPTURE.lock().unwrap();
+    let Some(capture) = guard.as_mut() else {
+        return;
+    };
+    let mut line = line.to_string();
+    if capture.redact {
+        line = redact_login_params(line);
+        for secret in &capture.secrets {
+            line = line.replace(secret.as_str(), REDACTED);
+        }
+    }
+    let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    if let Err(e) = writeln!(capture.file, "{} {} {}", timestamp, direction, line) {
+        tracing::warn!("Traffic capture write failed, stopping capture: {}", e);
+        *guard = None;
+        ENABLED.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn redacted(line: Value) -> Value {
+        serde_json::from_str(&redact_login_params(line.to_string())).unwrap()
+    }
+
+    #[test]
+    fn login_pass_and_rig_id_are_redacted() {
+        let login = json!({ "id": 1, "method": "login", "params": { "login": "wallet", "pass": "x", "rigid": "rig-7", "agent": "anonminer" } });
+        let params = &redacted(login)["params"];
+        assert_eq!(params["pass"], REDACTED);
+        assert_eq!(params["rigid"], REDACTED);
+        assert_eq!(params["agent"], "anonminer");
+    }
+
+    #[test]
+    fn missing_rig_id_stays_missing() {
+        let login = json!({ "id": 1, "method": "login", "params": { "login": "wallet", "pass": "x", "rigid": null } });
+        assert_eq!(redacted(login)["params"]["rigid"], Value::Null);
+    }
+
+    #[test]
+    fn other_lines_are_left_alone() {
+        let submit = json!({ "id": 2, "method": "submit", "params": { "id": "session", "pass": "not a login" } }).to_string();
+        assert_eq!(redact_login_params(submit.clone()), submit);
+        assert_eq!(redact_login_params("not json".into()), "not json");
+    }
+}
@@ -0,0 +1,170 @@
+use socket2::{Domain, Protocol, SockAddr, SockRef, Socket, TcpKeepalive, Type};
+use std::{
+    io::{self, Read, Write},
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// Unanswered keepalive probes before the OS drops the connection.
+#[cfg(target_os = "linux")]
+const KEEPALIVE_PROBES: u32 = 3;
+/// How long the proxy gets to answer a CONNECT.
+const PROXY_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Longest CONNECT response header accepted, so a misbehaving proxy can't make us read forever.
+const MAX_PROXY_RESPONSE: usize = 8 * 1024;
+
+/// An HTTP proxy that pool connections are tunnelled through with `CONNECT`.
+#[derive(Debug, Clone)]
+pub struct HttpProxy {
+    /// `host:port` of the proxy.
+    pub address: String,
+    /// `user:password` for Basic `Proxy-Authorization`.
+    pub credentials: Option<String>,
+}
+
+/// How outgoing pool connections are made. Every `Stratum` (the donation pool's too)
+/// keeps a copy and reuses it on reconnect.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// Local address the socket is bound to before connecting.
+    pub bind_address: Option<IpAddr>,
+    /// Write requests from a dedicated sender thread so a slow link never stalls the caller.
+    pub async_submit: bool,
+    /// Idle time before the OS starts TCP keepalive probes; `None` leaves keepalive off.
+    pub tcp_keepalive: Option<Duration>,
+    /// `--rig-id`, sent with the login.
+    pub rig_id: Option<String>,
+    /// Also tag every submitted share with the rig id.
+    pub tag_shares: bool,
+    /// `--http-proxy`: tunnel every connection through this proxy.
+    pub http_proxy: Option<HttpProxy>,
+}
+
+pub fn connect(url: &str, options: &ConnectOptions) -> io::Result<TcpStream> {
+    // Through a proxy, the socket goes to the proxy and the pool is only named in the CONNECT.
+    let target = options.http_proxy.as_ref().map_or(url, |proxy| &proxy.address);
+    let mut stream = match options.bind_address {
+        Some(local_ip) => connect_bound(target, local_ip)?,
+        None => TcpStream::connect(target)?,
+    };
+    if let Some(idle) = options.tcp_keepalive {
+        enable_keepalive(&stream, idle)?;
+    }
+    if let Some(proxy) = &options.http_proxy {
+        stream.set_read_timeout(Some(PROXY_HANDSHAKE_TIMEOUT))?;
+        http_connect(&mut stream, proxy, url)?;
+        stream.set_read_timeout(None)?;
+    }
+    Ok(stream)
+}
+
+/// Asks the proxy to open a tunnel to `url`. Anything but a 2xx answer is an error
+/// naming the proxy's status line; after success the stream carries the pool's bytes.
+fn http_connect(stream: &mut TcpStream, proxy: &HttpProxy, url: &str) -> io::Result<()> {
+    let mut request = format!("CONNECT {url} HTTP/1.1\r\nHost: {url}\r\n");
+    if let Some(credentials) = &proxy.credentials {
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", base64(credentials.as_bytes())));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+
+    // Byte by byte, so nothing past the header (the pool's first bytes) is consumed.
+    let mut response = Vec::new();
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_PROXY_RESPONSE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("HTTP proxy {} sent an oversized CONNECT response", proxy.address)));
+        }
+        if stream.read(&mut byte)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("HTTP proxy {} closed the connection during CONNECT to {}", proxy.address, url),
+            ));
+        }
+        response.push(byte[0]);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    let status = status_line.split_whitespace().nth(1).and_then(|code| code.parse::<u16>().ok());
+    match status {
+        Some(200..=299) => {
+            tracing::debug!("HTTP proxy {} tunnelling to {}", proxy.address, url);
+            Ok(())
+        }
+        Some(407) => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "HTTP proxy {} requires authentication ({}){}",
+                proxy.address,
+                status_line,
+                if proxy.credentials.is_some() { "; check --http-proxy-auth" } else { "; pass --http-proxy-auth" }
+            ),
+        )),
+        _ => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("HTTP proxy {} refused CONNECT to {}: {}", proxy.address, url, status_line),
+        )),
+    }
+}
+
+/// Standard base64 with padding, for the Basic credentials.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = chunk.iter().enumerate().fold(0u32, |word, (i, &b)| word | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(word >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Turns on OS-level keepalive so a half-open connection (e.g. a NAT entry that
+/// expired) errors out within about `2 * idle` instead of hanging until the job timeout.
+fn enable_keepalive(stream: &TcpStream, idle: Duration) -> io::Result<()> {
+    let keepalive = TcpKeepalive::new().with_time(idle);
+    #[cfg(target_os = "linux")]
+    let keepalive = keepalive
+        .with_interval((idle / KEEPALIVE_PROBES).max(Duration::from_secs(1)))
+        .with_retries(KEEPALIVE_PROBES);
+    SockRef::from(stream).set_tcp_keepalive(&keepalive)
+}
+
+fn connect_bound(url: &str, local_ip: IpAddr) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for remote in url.to_socket_addrs()?.filter(|addr| addr.is_ipv4() == local_ip.is_ipv4()) {
+        match connect_from(local_ip, remote) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => {
+                tracing::debug!("Connecting to {} from {} failed: {}", remote, local_ip, e);
+                last_err = Some(e);
+            }
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            format!("{} has no address of the same family as bind address {}", url, local_ip),
+        )
+    }))
+}
+
+fn connect_from(local_ip: IpAddr, remote: SocketAddr) -> io::Result<TcpStream> {
+    let socket = Socket::new(Domain::for_address(remote), Type::STREAM, Some(Protocol::TCP))?;
+    socket.bind(&SockAddr::from(SocketAddr::new(local_ip, 0)))?;
+    socket.connect(&SockAddr::from(remote))?;
+    Ok(socket.into())
+}
+
+/// Fails unless `ip` is assigned to a local interface, so a typo surfaces at startup
+/// instead of as an endless reconnect loop.
+pub fn check_bind_address(ip: IpAddr) -> io::Result<()> {
+    TcpListener::bind((ip, 0)).map(|_| ()).map_err(|e| {
+        io::Error::new(e.kind(), format!("cannot bind to local address {}: {}", ip, e))
+    })
+}
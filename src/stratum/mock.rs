@@ -0,0 +1,134 @@
+//! Stand-ins for a pool: an in-memory connection, so the listener and the request
+//! writers can be driven by scripted lines instead of a socket, and a loopback pool
+//! for tests that need real `Stratum` connections.
+
+use serde_json::{json, Value};
+use std::{
+    io::{self, BufRead, BufReader, Cursor, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+/// A hashing blob as pools send it, with the nonce at 39 zeroed.
+pub const BLOB: &str = "0e0ed286da8006ecdc1aab3033cf1716c52f13f9d8ae0051615a2453643de94643b550d543becd0000000002abc78b79a1cab2a20d9b9b5bec8e21de3a0ad1b6d90af5bf2ab10ce21a4d7c71b9";
+pub const SEED_HASH: &str = "abababababababababababababababababababababababababababababababab";
+
+/// Replays scripted pool lines to whatever reads it, then reports EOF or, with
+/// `failing_with`, an error; records everything written to it.
+#[derive(Debug, Default)]
+pub struct MockConnection {
+    incoming: Cursor<Vec<u8>>,
+    error: Option<io::ErrorKind>,
+    pub written: Vec<u8>,
+}
+
+impl MockConnection {
+    pub fn new<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut incoming = Vec::new();
+        for line in lines {
+            incoming.extend_from_slice(line.as_bytes());
+            incoming.push(b'\n');
+        }
+        Self { incoming: Cursor::new(incoming), ..Self::default() }
+    }
+
+    /// Fails reads with `kind` once the script runs out, like a reset connection.
+    pub fn failing_with(mut self, kind: io::ErrorKind) -> Self {
+        self.error = Some(kind);
+        self
+    }
+
+    /// The lines written so far.
+    pub fn sent_lines(&self) -> Vec<String> {
+        String::from_utf8_lossy(&self.written).lines().map(str::to_string).collect()
+    }
+
+    fn exhausted(&self) -> bool {
+        self.incoming.position() as usize >= self.incoming.get_ref().len()
+    }
+}
+
+impl Read for MockConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for MockConnection {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.exhausted() {
+            if let Some(kind) = self.error {
+                return Err(io::Error::new(kind, "scripted connection error"));
+            }
+        }
+        self.incoming.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.incoming.consume(amt);
+    }
+}
+
+impl Write for MockConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A pool on a loopback port that answers every login with a job. Each login is
+/// handed to the test with its connection, so dropping that stream disconnects
+/// the miner.
+pub struct FakePool {
+    pub url: String,
+    logins: mpsc::Receiver<(Value, TcpStream)>,
+}
+
+impl FakePool {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = listener.local_addr().unwrap().to_string();
+        let (login_tx, logins) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    break;
+                };
+                let mut line = String::new();
+                if BufReader::new(stream.try_clone().unwrap()).read_line(&mut line).is_err() {
+                    continue;
+                }
+                let login: Value = serde_json::from_str(&line).unwrap_or_default();
+                let reply = json!({
+                    "id": 1,
+                    "jsonrpc": "2.0",
+                    "error": null,
+                    "result": {
+                        "id": "session",
+                        "status": "OK",
+                        "job": { "job_id": "1", "blob": BLOB, "seed_hash": SEED_HASH, "target": "b88d0600" },
+                    },
+                });
+                if writeln!(stream, "{}", reply).is_err() || login_tx.send((login, stream)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { url, logins }
+    }
+
+    /// The next login's request and connection; panics if none arrives soon.
+    pub fn next_login(&self) -> (Value, TcpStream) {
+        self.logins.recv_timeout(Duration::from_secs(5)).expect("no login reached the fake pool")
+    }
+}
@@ -0,0 +1,156 @@
+use super::{
+    listen,
+    rpc::response::{LoginResult, Response},
+    JobWatchdog, ListenerShared, ShareOutcome,
+};
+use crate::{color::Colorize, share::Share};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Cursor},
+    path::Path,
+    sync::{atomic::AtomicBool, mpsc, Arc, Mutex},
+    time::Instant,
+};
+
+/// The lines of one captured connection, from its `#` marker to the next.
+struct Session {
+    url: String,
+    sent: Vec<String>,
+    received: Vec<String>,
+}
+
+/// Splits a `--capture-traffic` file into connections. Lines before the first
+/// `#` marker (e.g. from a truncated capture) form a session of their own.
+fn parse_capture(text: &str) -> Vec<Session> {
+    let mut sessions: Vec<Session> = Vec::new();
+    for line in text.lines() {
+        // "<timestamp> <direction> <line>"
+        let Some((direction, payload)) = line.split_once(' ').and_then(|(_, rest)| rest.split_once(' ')) else {
+            continue;
+        };
+        if direction == "#" {
+            if let Some(url) = payload.strip_prefix("connected to ") {
+                sessions.push(Session { url: url.to_string(), sent: Vec::new(), received: Vec::new() });
+            }
+            continue;
+        }
+        if sessions.is_empty() {
+            sessions.push(Session { url: "unknown:0".to_string(), sent: Vec::new(), received: Vec::new() });
+        }
+        let session = sessions.last_mut().unwrap();
+        match direction {
+            ">" => session.sent.push(payload.to_string()),
+            "<" => session.received.push(payload.to_string()),
+            _ => {}
+        }
+    }
+    sessions
+}
+
+/// The share a captured `submit` request stood for, so the listener can match
+/// the pool's response to it by id like it does live.
+fn submitted_share(line: &str) -> Option<(u32, Share)> {
+    let request: Value = serde_json::from_str(line).ok()?;
+    if request.get("method")?.as_str()? != "submit" {
+        return None;
+    }
+    let id = u32::try_from(request.get("id")?.as_u64()?).ok()?;
+    let params = request.get("params")?;
+    let hex_field = |name: &str| params.get(name).and_then(Value::as_str).and_then(|s| hex::decode(s).ok()).unwrap_or_default();
+    Some((
+        id,
+        Share {
+            nonce: hex_field("nonce"),
+            hash: hex_field("result"),
+            job_id: params.get("job_id").and_then(Value::as_str).unwrap_or_default().to_string(),
+            blob: Vec::new(),
+            difficulty: 0,
+            found_at: Instant::now(),
+            pool: 0,
+        },
+    ))
+}
+
+/// Developer tool: feeds the pool side of a `--capture-traffic` file back through
+/// the login-response parsing and the listener's dispatch, connection by connection,
+/// and prints the jobs, share results, difficulty changes and redirects they produce.
+/// Run with `--log-level debug` to also see the listener's own tracing.
+pub fn run(path: &Path) -> io::Result<()> {
+    let sessions = parse_capture(&fs::read_to_string(path)?);
+    if sessions.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has no captured Stratum lines", path.display())));
+    }
+
+    for (index, session) in sessions.into_iter().enumerate() {
+        println!(
+            "{} Connection {} to {}: {} lines received, {} sent",
+            "📼".cyan(),
+            index + 1,
+            session.url.yellow(),
+            session.received.len(),
+            session.sent.len()
+        );
+        let mut received = session.received.into_iter();
+
+        // The first line back is the login response, which is read before the listener starts.
+        if let Some(line) = received.next() {
+            match serde_json::from_str::<Response<LoginResult>>(&line) {
+                Ok(Response { result: Some(result), .. }) => match result.job.validate() {
+                    Ok(()) => println!("  Login accepted, initial job {} (difficulty {})", result.job.id, result.job.pool_difficulty()),
+                    Err(e) => println!("  {} Login accepted with an unusable job: {}", "❌".red(), e),
+                },
+                Ok(Response { error: Some(error), .. }) => println!("  {} Login refused: {}", "❌".red(), error.message),
+                Ok(_) => println!("  {} Login response has neither result nor error", "❌".red()),
+                Err(e) => println!("  {} First line is not a login response ({}): {}", "❌".red(), e, line),
+            }
+        }
+
+        let (job_tx, job_rx) = mpsc::channel();
+        let (reconnect_tx, reconnect_rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let shared = ListenerShared {
+            connected: Arc::new(AtomicBool::new(true)),
+            pending_submits: Arc::new(Mutex::new(session.sent.iter().filter_map(|line| submitted_share(line)).collect::<HashMap<_, _>>())),
+            result_tx,
+            redirect: Arc::new(Mutex::new(None)),
+            difficulty: Arc::new(Mutex::new(None)),
+            job_watchdog: Arc::new(JobWatchdog::new()),
+            supports_hash_rate: Arc::new(AtomicBool::new(false)),
+        };
+        let lines: String = received.map(|line| line + "\n").collect();
+        listen(Cursor::new(lines), session.url.clone(), shared.clone(), job_tx, reconnect_tx);
+
+        for job in job_rx.try_iter() {
+            println!(
+                "  Job {} difficulty {}, height {}, {}-byte blob, seed {}…",
+                job.id,
+                job.pool_difficulty(),
+                job.height.map_or_else(|| "-".to_string(), |height| height.to_string()),
+                job.blob.len(),
+                hex::encode(&job.seed[..job.seed.len().min(4)])
+            );
+        }
+        for result in result_rx.try_iter() {
+            match result.outcome {
+                ShareOutcome::Accepted => println!("  {} Share for job {} accepted", "✓".green(), result.share.job_id),
+                ShareOutcome::Rejected(reason) => println!("  {} Share for job {} rejected: {}", "❌".red(), result.share.job_id, reason),
+            }
+        }
+        let unanswered = shared.pending_submits.lock().unwrap().len();
+        if unanswered > 0 {
+            println!("  {} {} submitted shares got no response", "⚠️".yellow(), unanswered);
+        }
+        if let Some(difficulty) = *shared.difficulty.lock().unwrap() {
+            println!("  Difficulty last set to {}", difficulty);
+        }
+        if let Some(redirect) = shared.redirect.lock().unwrap().as_ref() {
+            println!("  Pool redirected to {} after {:?}", redirect.url.yellow(), redirect.delay);
+        }
+        if reconnect_rx.try_recv().is_ok() {
+            println!("  Connection ended; a live miner would reconnect here");
+        }
+    }
+    Ok(())
+}
@@ -3,26 +3,25 @@ pub mod response;
 
 use request::Request;
 use serde::{de::DeserializeOwned, Serialize};
-use std::{
-    io::{self, BufReader, BufWriter, Write, BufRead},
-    net::TcpStream,
-};
+use std::io::{self, Write, BufRead};
 
-pub fn send<S: Serialize>(
-    writer: &mut BufWriter<TcpStream>,
+pub fn send<W: Write, S: Serialize>(
+    writer: &mut W,
     request: &Request<S>,
 ) -> io::Result<()> {
-    serde_json::to_writer(&mut *writer, request)?;
-    writeln!(writer)?;
+    let line = serde_json::to_string(request)?;
+    super::capture::sent(&line);
+    writeln!(writer, "{}", line)?;
     writer.flush()?;
     Ok(())
 }
 
-pub fn recv<D: DeserializeOwned>(reader: &mut BufReader<TcpStream>) -> serde_json::Result<D> {
+pub fn recv<R: BufRead, D: DeserializeOwned>(reader: &mut R) -> serde_json::Result<D> {
     let mut line = String::new();
     reader.read_line(&mut line).map_err(serde_json::Error::io)?;
     if line.is_empty() {
         return Err(serde_json::Error::io(io::Error::new(io::ErrorKind::UnexpectedEof, "EOF while reading line")));
     }
+    super::capture::received(&line);
     serde_json::from_str(&line)
 }
@@ -14,6 +14,11 @@ pub struct Request<P> {
 pub struct LoginParams {
     pub login: String,
     pub pass: String,
+    /// Algorithms the miner can hash, so pools serving several coins pick a RandomX one.
+    pub algo: Vec<String>,
+    /// Instance identifier from `--rig-id`, left out when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rigid: Option<String>,
 }
 
 impl Request<LoginParams> {
@@ -62,6 +67,10 @@ pub struct SubmitParams {
     pub nonce: Vec<u8>,
     #[serde(with = "hex")]
     pub result: Vec<u8>,
+    /// Instance tag for pool-side attribution; only sent with `--rig-id-in-submit`,
+    /// since some pools reject submits with fields they don't know.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rigid: Option<String>,
 }
 
 impl Request<SubmitParams> {
@@ -89,3 +98,70 @@ impl Request<KeepAlivedParams> {
         }
     }
 }
+
+// For "hashrate" method, sent only to pools listing the "hashrate" extension
+#[derive(Debug, Serialize)]
+pub struct HashrateParams {
+    pub id: String,
+    /// H/s, averaged the same way as the miner's own report.
+    pub hashrate: f64,
+}
+
+impl Request<HashrateParams> {
+    pub fn new_hashrate(params: HashrateParams) -> Self {
+        Self {
+            method: "hashrate".into(),
+            params,
+            id: 1,
+        }
+    }
+}
+
+// For "getjob" method
+#[derive(Debug, Serialize)]
+pub struct GetJobParams {
+    pub id: String,
+}
+
+impl Request<GetJobParams> {
+    pub fn new_get_job(params: GetJobParams) -> Self {
+        Self {
+            method: "getjob".into(),
+            params,
+            id: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn submit(rigid: Option<&str>) -> Value {
+        serde_json::to_value(Request::new_submit_standard(SubmitParams {
+            id: "session".into(),
+            job_id: "7".into(),
+            nonce: vec![0xde, 0xad, 0xbe, 0xef],
+            result: vec![0xab; 4],
+            rigid: rigid.map(str::to_string),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn untagged_submit_has_no_rigid() {
+        assert_eq!(
+            submit(None),
+            json!({ "method": "submit", "params": { "id": "session", "job_id": "7", "nonce": "deadbeef", "result": "abababab" }, "id": 1 })
+        );
+    }
+
+    #[test]
+    fn tagged_submit_carries_the_rig_id() {
+        assert_eq!(
+            submit(Some("rig-7")),
+            json!({ "method": "submit", "params": { "id": "session", "job_id": "7", "nonce": "deadbeef", "result": "abababab", "rigid": "rig-7" }, "id": 1 })
+        );
+    }
+}
@@ -23,6 +23,9 @@ pub struct LoginResult {
     pub job: Job,
     pub id: String,
     pub status: String,
+    /// Optional protocol extensions the pool supports, e.g. "keepalive" or "hashrate".
+    #[serde(default)]
+    pub extensions: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -62,8 +65,8 @@ pub struct NotifyParamsObject {
     pub seed_hash: String,
     // next_seed_hash can be empty, so we don't need to parse it if it's not always present or critical
     // pub next_seed_hash: Option<String>,
-    // pub algo: Option<String>,
-    // pub height: Option<u64>,
+    pub algo: Option<String>,
+    pub height: Option<u64>,
 }
 
 // For "mining.set_difficulty" method (Server to Miner)
@@ -81,3 +84,11 @@ pub enum SetDifficultyParams {
 pub enum SetExtranonceParams {
     Array(Vec<Value>),
 }
+
+// For "client.reconnect" method (Server to Miner)
+// Params is an array: [HOST, PORT, WAIT_SECONDS], all optional
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ClientReconnectParams {
+    Array(Vec<Value>),
+}
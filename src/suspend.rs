@@ -0,0 +1,71 @@
+use crate::color::Colorize;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Oversleeping by this much means the process was frozen, not merely scheduled late.
+const MIN_GAP: Duration = Duration::from_secs(30);
+
+// Total time the detector found the process frozen, in milliseconds.
+static SUSPENDED_MILLIS: AtomicU64 = AtomicU64::new(0);
+
+/// Starts a thread that notices when the process stops running for a while with the
+/// monotonic clock still advancing: system sleep where `Instant` counts it (Windows),
+/// SIGSTOP, a paused VM. Each gap is logged, added to `suspended_total` and the hash
+/// rate window is restarted, so the rate isn't averaged over time nothing ran.
+pub fn start_detector() {
+    thread::spawn(|| loop {
+        let before = Instant::now();
+        thread::sleep(CHECK_INTERVAL);
+        let Some(gap) = frozen_gap(before.elapsed()) else {
+            continue;
+        };
+        SUSPENDED_MILLIS.fetch_add(gap.as_millis() as u64, Ordering::Relaxed);
+        crate::hash_rate::get_hash_rate_tracker().lock().unwrap().restart_window();
+        tracing::warn!(gap_secs = gap.as_secs(), "Clock jumped; the process was suspended");
+        eprintln!(
+            "{} Mining was frozen for {}s (system sleep or a paused process); restarting the hash rate window",
+            "⏸".yellow(),
+            gap.as_secs()
+        );
+    });
+}
+
+/// How long the process was frozen during a `CHECK_INTERVAL` sleep that took
+/// `slept`, or `None` if it overslept by less than `MIN_GAP`.
+fn frozen_gap(slept: Duration) -> Option<Duration> {
+    let gap = slept.saturating_sub(CHECK_INTERVAL);
+    (gap >= MIN_GAP).then_some(gap)
+}
+
+/// Time the process spent frozen so far; timers that should only count running
+/// time subtract the growth of this over their span.
+pub fn suspended_total() -> Duration {
+    Duration::from_millis(SUSPENDED_MILLIS.load(Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_wakeups_are_not_gaps() {
+        assert_eq!(frozen_gap(CHECK_INTERVAL), None);
+        assert_eq!(frozen_gap(CHECK_INTERVAL + Duration::from_millis(250)), None);
+        assert_eq!(frozen_gap(CHECK_INTERVAL + MIN_GAP - Duration::from_millis(1)), None);
+    }
+
+    #[test]
+    fn gap_excludes_the_sleep_itself() {
+        assert_eq!(frozen_gap(CHECK_INTERVAL + MIN_GAP), Some(MIN_GAP));
+        assert_eq!(frozen_gap(CHECK_INTERVAL + Duration::from_secs(3600)), Some(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn early_wakeup_is_not_a_gap() {
+        assert_eq!(frozen_gap(Duration::ZERO), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Conversions between pool difficulty, the compact 32-bit target pools send and
+//! the thresholds hashes are compared against, following Monero's `check_hash`:
+//! a hash meets difficulty `d` when, read as a 256-bit little-endian number, it is
+//! at most `(2^256 - 1) / d`.
+
+/// Difficulty a compact pool target (the 4-byte little-endian `target` of a
+/// Stratum job) stands for: `0xffffffff / target`.
+pub fn compact_to_difficulty(compact: u32) -> u64 {
+    (u32::MAX / compact.max(1)) as u64
+}
+
+/// Parses a job's hex `target` into its compact form.
+pub fn parse_compact_target(hex: &str) -> Result<u32, String> {
+    let bytes = hex::decode(hex).map_err(|e| format!("invalid target '{}': {}", hex, e))?;
+    let bytes: [u8; 4] = bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| format!("target '{}' is {} bytes, expected 4", hex, bytes.len()))?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Full 256-bit little-endian target for `difficulty`: `(2^256 - 1) / difficulty`.
+pub fn difficulty_to_target(difficulty: u64) -> [u8; 32] {
+    let divisor = difficulty.max(1) as u128;
+    let mut target = [0u8; 32];
+    let mut remainder: u128 = 0;
+    // Long division of 2^256 - 1 (four all-ones words), most significant word first.
+    for word in (0..4).rev() {
+        let dividend = (remainder << 64) | u64::MAX as u128;
+        target[word * 8..word * 8 + 8].copy_from_slice(&((dividend / divisor) as u64).to_le_bytes());
+        remainder = dividend % divisor;
+    }
+    target
+}
+
+/// The top 8 bytes of `difficulty_to_target(difficulty)`, which is `u64::MAX / difficulty`.
+/// A hash whose `hash_top_word` is above it can never meet the target, so it
+/// filters hashes before the full comparison.
+pub fn difficulty_to_threshold(difficulty: u64) -> u64 {
+    u64::MAX / difficulty.max(1)
+}
+
+/// The top 8 bytes of a 32-byte hash as a little-endian word.
+pub fn hash_top_word(hash: &[u8]) -> u64 {
+    u64::from_le_bytes(hash[24..32].try_into().unwrap())
+}
+
+/// Whether `hash` is at most `target`, both read as 256-bit little-endian numbers.
+pub fn hash_meets_target(hash: &[u8; 32], target: &[u8; 32]) -> bool {
+    hash.iter().rev().cmp(target.iter().rev()) != std::cmp::Ordering::Greater
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_target(word: u64) -> [u8; 32] {
+        let mut target = [0u8; 32];
+        for chunk in target.chunks_mut(8) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        target
+    }
+
+    #[test]
+    fn parses_pool_targets() {
+        assert_eq!(parse_compact_target("b88d0600"), Ok(0x00068db8));
+        assert_eq!(compact_to_difficulty(0x00068db8), 10_000);
+        assert_eq!(compact_to_difficulty(parse_compact_target("ffffffff").unwrap()), 1);
+        assert_eq!(compact_to_difficulty(parse_compact_target("e4a50100").unwrap()), 39_766);
+        assert_eq!(compact_to_difficulty(0), u32::MAX as u64);
+    }
+
+    #[test]
+    fn rejects_malformed_targets() {
+        assert!(parse_compact_target("b88d06").is_err());
+        assert!(parse_compact_target("b88d060000").is_err());
+        assert!(parse_compact_target("zz8d0600").is_err());
+    }
+
+    #[test]
+    fn difficulty_one_accepts_every_hash() {
+        assert_eq!(difficulty_to_target(1), [0xff; 32]);
+        assert_eq!(difficulty_to_threshold(1), u64::MAX);
+        assert!(hash_meets_target(&[0xff; 32], &difficulty_to_target(1)));
+        // Zero would divide by zero; it is treated as 1.
+        assert_eq!(difficulty_to_target(0), difficulty_to_target(1));
+        assert_eq!(difficulty_to_threshold(0), u64::MAX);
+    }
+
+    #[test]
+    fn max_difficulty() {
+        // (2^256 - 1) / (2^64 - 1) = 2^192 + 2^128 + 2^64 + 1.
+        assert_eq!(difficulty_to_target(u64::MAX), word_target(1));
+        assert_eq!(difficulty_to_threshold(u64::MAX), 1);
+    }
+
+    #[test]
+    fn threshold_is_the_targets_top_word() {
+        for difficulty in [1, 2, 3, 10_000, 39_321, 1 << 32, 300_000_000_000, u64::MAX - 1, u64::MAX] {
+            let target = difficulty_to_target(difficulty);
+            assert_eq!(hash_top_word(&target), difficulty_to_threshold(difficulty), "difficulty {}", difficulty);
+        }
+    }
+
+    #[test]
+    fn hash_equal_to_target_meets_it() {
+        let target = difficulty_to_target(10_000);
+        assert!(hash_meets_target(&target, &target));
+
+        // The target's low byte is 0x43, so these don't carry.
+        let mut above = target;
+        above[0] += 1;
+        assert!(!hash_meets_target(&above, &target));
+
+        let mut below = target;
+        below[0] -= 1;
+        assert!(hash_meets_target(&below, &target));
+    }
+
+    #[test]
+    fn comparison_is_little_endian() {
+        let target = difficulty_to_target(10_000);
+        // Only the top byte is larger: the hash is above the target even though
+        // every lower byte is zero.
+        let mut hash = [0u8; 32];
+        hash[31] = target[31] + 1;
+        assert!(!hash_meets_target(&hash, &target));
+        // A larger low byte doesn't matter when the top bytes are smaller.
+        let mut hash = [0xff; 32];
+        hash[24..32].copy_from_slice(&(difficulty_to_threshold(10_000) - 1).to_le_bytes());
+        assert!(hash_meets_target(&hash, &target));
+    }
+}
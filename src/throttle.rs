@@ -0,0 +1,68 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
+    thread,
+    time::Duration,
+};
+use sysinfo::{CpuRefreshKind, RefreshKind, System};
+
+/// Per-batch sleep used by light mode when no `--target-cpu` is given.
+const FIXED_SLEEP_MICROS: u64 = 100;
+const MAX_SLEEP_MICROS: u64 = 50_000;
+const MIN_STEP_MICROS: u64 = 50;
+const CONTROL_INTERVAL: Duration = Duration::from_secs(2);
+// Dead band around the target so the controller doesn't chase sampling noise.
+const TOLERANCE_PERCENT: f32 = 2.0;
+
+static SLEEP_MICROS: AtomicU64 = AtomicU64::new(FIXED_SLEEP_MICROS);
+// 0 means the fixed sleep is in effect.
+static TARGET_PERCENT: AtomicU8 = AtomicU8::new(0);
+
+/// Sleep a light-mode worker thread takes after each batch.
+#[inline(always)]
+pub fn batch_sleep() -> Duration {
+    Duration::from_micros(SLEEP_MICROS.load(Ordering::Relaxed))
+}
+
+/// The CPU utilization the adaptive throttle is steering towards, if enabled.
+pub fn target_percent() -> Option<u8> {
+    match TARGET_PERCENT.load(Ordering::Relaxed) {
+        0 => None,
+        p => Some(p),
+    }
+}
+
+/// Starts a controller thread that samples system-wide CPU usage and grows or
+/// shrinks the per-batch sleep until usage settles around `target_percent`.
+pub fn start_adaptive(target_percent: u8) {
+    let target_percent = target_percent.clamp(1, 100);
+    TARGET_PERCENT.store(target_percent, Ordering::SeqCst);
+
+    thread::spawn(move || {
+        let span = tracing::info_span!("throttle");
+        let _enter = span.enter();
+
+        let mut sys = System::new_with_specifics(RefreshKind::nothing().with_cpu(CpuRefreshKind::nothing().with_cpu_usage()));
+        sys.refresh_cpu_usage();
+        let target = target_percent as f32;
+
+        loop {
+            thread::sleep(CONTROL_INTERVAL);
+            sys.refresh_cpu_usage();
+            let usage = sys.global_cpu_usage();
+            let current = SLEEP_MICROS.load(Ordering::Relaxed);
+
+            let next = if usage > target + TOLERANCE_PERCENT {
+                (current + (current / 4).max(MIN_STEP_MICROS)).min(MAX_SLEEP_MICROS)
+            } else if usage < target - TOLERANCE_PERCENT {
+                current.saturating_sub((current / 5).max(MIN_STEP_MICROS))
+            } else {
+                current
+            };
+
+            if next != current {
+                tracing::debug!("CPU usage {:.1}% (target {}%), batch sleep {}µs -> {}µs", usage, target_percent, current, next);
+                SLEEP_MICROS.store(next, Ordering::Relaxed);
+            }
+        }
+    });
+}
@@ -0,0 +1,92 @@
+use crate::{
+    color::Colorize,
+    display::Display,
+    stratum::{connect::ConnectOptions, ShareOutcome, Stratum},
+    worker::{NonceEndian, Worker},
+};
+use std::{
+    io,
+    num::NonZeroUsize,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long the pool gets to answer the submitted share.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Mines on the real pool until the first share, submits it and reports the pool's
+/// verdict: the end-to-end check that blob layout, nonce placement and target math
+/// match what the pool expects. A rejected share is returned as an error.
+pub fn run(
+    url: &str,
+    user: &str,
+    pass: &str,
+    options: &ConnectOptions,
+    num_threads: NonZeroUsize,
+    fast: bool,
+    nonce_endian: NonceEndian,
+) -> io::Result<()> {
+    let mut stratum = Stratum::login(url, user, pass, options)?;
+    let initial_job = wait_for(|| stratum.try_recv_job().ok(), RESPONSE_TIMEOUT)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "pool sent no job after login"))?;
+    println!(
+        "{} Validating {}: mining until the first share at difficulty {}...",
+        "🔎".cyan(),
+        url.yellow(),
+        initial_job.pool_difficulty()
+    );
+    let worker = Worker::init(initial_job, num_threads, fast, false, false, None, nonce_endian);
+
+    let share = loop {
+        if stratum.try_reconnect_signal().is_ok() {
+            return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "pool connection lost before a share was found"));
+        }
+        if let Ok(job) = stratum.try_recv_job() {
+            Display::job_received(&job.id);
+            worker.work(job);
+        }
+        if let Ok(share) = worker.try_recv_share() {
+            break share;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    stratum.submit(&share)?;
+    println!(
+        "{} Share for job {} submitted after {} (nonce {}), waiting for the pool...",
+        "📤".cyan(),
+        share.job_id,
+        Display::format_duration(worker.get_elapsed_time()),
+        hex::encode(&share.nonce)
+    );
+    let result = wait_for(|| stratum.try_recv_share_result().ok(), RESPONSE_TIMEOUT)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "pool did not answer the submitted share"))?;
+
+    match result.outcome {
+        ShareOutcome::Accepted => {
+            println!(
+                "{} {}",
+                "✓".green(),
+                format!("Pool accepted the share in {} ms: blob layout, nonce placement and target math all match", result.latency.as_millis())
+                    .green()
+                    .bold()
+            );
+            Ok(())
+        }
+        ShareOutcome::Rejected(reason) => {
+            Display::share_rejected(&share.job_id, &reason);
+            Err(io::Error::other(format!("pool rejected the validation share: {}", reason)))
+        }
+    }
+}
+
+fn wait_for<T>(mut poll: impl FnMut() -> Option<T>, timeout: Duration) -> Option<T> {
+    let started = Instant::now();
+    while started.elapsed() < timeout {
+        if let Some(value) = poll() {
+            return Some(value);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    None
+}
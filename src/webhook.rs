@@ -0,0 +1,123 @@
+use crate::hash_rate::get_hash_rate_tracker;
+use once_cell::sync::OnceCell;
+use serde_json::{json, Value};
+use std::{
+    io::{self, Write},
+    net::{TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// No hashes for this long (after startup) sends a `zero_hashrate` event.
+const ZERO_HASH_RATE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Plain-HTTP endpoint that events are POSTed to as JSON.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    host: String,
+    path: String,
+}
+
+/// Parses `http://host[:port][/path]`; there is no TLS support for `https://`.
+pub fn parse_webhook(url: &str) -> Result<Webhook, String> {
+    let rest = url.strip_prefix("http://").ok_or("only http:// webhook URLs are supported")?;
+    let (host, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err("webhook URL has no host".into());
+    }
+    let host = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    Ok(Webhook { host, path: path.to_string() })
+}
+
+impl Webhook {
+    /// Blocking POST; the response is not read.
+    pub fn post(&self, body: &Value) -> io::Result<()> {
+        let body = body.to_string();
+        let addr = self
+            .host
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "webhook host did not resolve"))?;
+        let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+}
+
+/// The `--webhook` endpoint and the rig name every event carries.
+static EVENTS: OnceCell<(Webhook, String)> = OnceCell::new();
+
+/// Enables `notify` and starts watching for a stalled hash rate.
+pub fn init(webhook: Webhook, rig: &str) {
+    if EVENTS.set((webhook, rig.to_string())).is_err() {
+        return;
+    }
+    thread::spawn(watch_zero_hash_rate);
+}
+
+fn event_body(event: &str, details: Value) -> Option<(&'static Webhook, Value)> {
+    let (webhook, rig) = EVENTS.get()?;
+    Some((
+        webhook,
+        json!({
+            "event": event,
+            "rig": rig,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "details": details,
+        }),
+    ))
+}
+
+/// Sends an event from a background thread so mining never waits on it.
+/// Does nothing without `--webhook`, apart from the `--events-file` copy.
+pub fn notify(event: &str, details: Value) {
+    crate::event_log::emit(event, &details);
+    let Some((webhook, body)) = event_body(event, details) else {
+        return;
+    };
+    thread::spawn(move || {
+        if let Err(e) = webhook.post(&body) {
+            tracing::warn!("Webhook event failed: {}", e);
+        }
+    });
+}
+
+/// Like `notify`, but waits (up to the timeout) so the event is sent before the process exits.
+pub fn notify_blocking(event: &str, details: Value) {
+    crate::event_log::emit(event, &details);
+    let Some((webhook, body)) = event_body(event, details) else {
+        return;
+    };
+    if let Err(e) = webhook.post(&body) {
+        tracing::warn!("Webhook event failed: {}", e);
+    }
+}
+
+fn watch_zero_hash_rate() {
+    let lifetime_hashes = || get_hash_rate_tracker().lock().unwrap().get_lifetime_hashes();
+    let mut last = lifetime_hashes();
+    let mut reported = false;
+    loop {
+        thread::sleep(ZERO_HASH_RATE_AFTER);
+        let hashes = lifetime_hashes();
+        if hashes == last && !reported {
+            notify("zero_hashrate", json!({ "stalled_secs": ZERO_HASH_RATE_AFTER.as_secs() }));
+            reported = true;
+        } else if hashes != last {
+            reported = false;
+        }
+        last = hashes;
+    }
+}
@@ -1,294 +1,450 @@
-use crate::{job::Job, share::Share};
-use randomx_rs::{RandomXVM, RandomXFlag};
+use crate::{clock::{Clock, SystemClock}, job::{Job, MIN_BLOB_LEN, NONCE_OFFSET}, randomx, share::Share, target};
+use randomx_rs::RandomXFlag;
 use std::{
     num::NonZeroUsize,
-    sync::mpsc::{self, Receiver, TryRecvError},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc, Mutex,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
-use watch::WatchSender;
+use watch::{WatchReceiver, WatchSender};
+
+/// Running total of the shares the hashes done so far should have produced,
+/// accounted at the difficulty each hash was done against.
+struct ExpectedShares {
+    hashes_accounted: u64,
+    expected: f64,
+    pool_difficulty: u64,
+}
+
+impl ExpectedShares {
+    fn account(&mut self, lifetime_hashes: u64) {
+        let new_hashes = lifetime_hashes.saturating_sub(self.hashes_accounted);
+        self.expected += new_hashes as f64 / self.pool_difficulty.max(1) as f64;
+        self.hashes_accounted = lifetime_hashes;
+    }
+}
+
+/// What a worker thread ended up running with after its fallback chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadMode {
+    Starting,
+    Running(RandomXFlag),
+    Paused,
+    Failed,
+    /// Exited after `Worker::shutdown`.
+    Stopped,
+}
+
+/// How often the stall monitor samples the threads' heartbeats.
+const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Stalled threads replaced per session before the miner gives up: every restart
+/// leaves a wedged thread holding its memory, so they can't go on indefinitely.
+const MAX_THREAD_RESTARTS: u64 = 5;
+/// How long `Worker::shutdown` waits for the threads to exit.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Hashes per batch; hash rate accounting and light-mode sleeps happen per batch.
+pub const BATCH_SIZE: usize = 100;
+/// With `--debug-all`, batches between checks that a thread's blob still matches its job.
+const BLOB_CHECK_BATCHES: u32 = 100;
+
+// Hashes between checks for a new job within a batch, for `--job-poll-interval`.
+static JOB_POLL_INTERVAL: AtomicUsize = AtomicUsize::new(BATCH_SIZE);
+
+/// Sets how many hashes a thread does between checks for a new job; a new job
+/// abandons the rest of the batch. Call before the workers start.
+pub fn set_job_poll_interval(hashes: usize) {
+    JOB_POLL_INTERVAL.store(hashes.max(1), Ordering::Relaxed);
+}
+
+// Whether a found share ends the batch, for `--end-batch-on-share`.
+static END_BATCH_ON_SHARE: AtomicBool = AtomicBool::new(false);
+
+/// Makes a thread end its batch and yield its CPU right after sending a share, so
+/// the loop that submits it gets scheduled at once even with every core hashing.
+/// Call before the workers start.
+pub fn set_end_batch_on_share(enabled: bool) {
+    END_BATCH_ON_SHARE.store(enabled, Ordering::Relaxed);
+}
+
+/// Liveness of one thread slot, shared by whichever thread currently fills it.
+#[derive(Default)]
+struct ThreadSlot {
+    /// Bumped on every pass through the thread's loop.
+    heartbeat: AtomicU64,
+    /// Bumped when the slot's thread is replaced; a thread from an older
+    /// generation exits as soon as it gets unstuck.
+    generation: AtomicU64,
+    /// The current generation's thread, joined by `Worker::shutdown`.
+    handle: Mutex<Option<thread::JoinHandle<()>>>,
+    /// The thread the last restart replaced, to tell whether it is still stuck.
+    retired: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+/// Spots threads whose heartbeat hasn't moved for `timeout` while they should be hashing.
+struct StallDetector<C: Clock = SystemClock> {
+    clock: C,
+    timeout: Duration,
+    // (heartbeat, when it was last seen to change) per slot.
+    last_seen: Vec<(u64, Instant)>,
+}
+
+impl StallDetector {
+    fn new(timeout: Duration) -> Self {
+        Self::with_clock(timeout, SystemClock)
+    }
+}
+
+impl<C: Clock> StallDetector<C> {
+    fn with_clock(timeout: Duration, clock: C) -> Self {
+        Self { clock, timeout, last_seen: Vec::new() }
+    }
+
+    /// Takes each slot's heartbeat, or `None` for a slot that legitimately goes quiet
+    /// (paused, starting, rebuilding), and returns the slots stalled for `timeout`.
+    /// A returned slot's timer starts over, as its replacement gets a full timeout.
+    fn stalled(&mut self, heartbeats: &[Option<u64>]) -> Vec<usize> {
+        let now = self.clock.now();
+        self.last_seen.resize(heartbeats.len(), (0, now));
+        let mut stalled = Vec::new();
+        for (i, (heartbeat, seen)) in heartbeats.iter().zip(&mut self.last_seen).enumerate() {
+            match heartbeat {
+                Some(heartbeat) if *heartbeat == seen.0 => {
+                    if now.duration_since(seen.1) >= self.timeout {
+                        stalled.push(i);
+                        seen.1 = now;
+                    }
+                }
+                _ => *seen = (heartbeat.unwrap_or(seen.0), now),
+            }
+        }
+        stalled
+    }
+}
+
+/// Why a stalled thread must not be replaced, if it mustn't: the restart budget is
+/// spent, or the thread its last restart replaced is still stuck, so another
+/// replacement would only pile up wedged threads and their datasets.
+fn restart_refusal(restarts: u64, previous_still_stuck: bool) -> Option<String> {
+    if previous_still_stuck {
+        Some("the thread it replaced last time is still stuck".to_string())
+    } else if restarts >= MAX_THREAD_RESTARTS {
+        Some(format!("{} threads have already been restarted", restarts))
+    } else {
+        None
+    }
+}
+
+/// Marks the thread's slot `Failed` if the thread exits, whichever `return` it takes,
+/// or `Stopped` if it was asked to. A thread that has been replaced no longer
+/// touches the slot's mode.
+struct ThreadModeGuard {
+    modes: Arc<Mutex<Vec<ThreadMode>>>,
+    index: usize,
+    slot: Arc<ThreadSlot>,
+    generation: u64,
+    stopping: Arc<AtomicBool>,
+}
+
+impl ThreadModeGuard {
+    fn is_current(&self) -> bool {
+        self.slot.generation.load(Ordering::Relaxed) == self.generation
+    }
+
+    fn set(&self, mode: ThreadMode) {
+        let mut modes = self.modes.lock().unwrap();
+        if self.is_current() {
+            modes[self.index] = mode;
+        }
+    }
+}
+
+impl Drop for ThreadModeGuard {
+    fn drop(&mut self) {
+        self.set(if self.stopping.load(Ordering::Relaxed) { ThreadMode::Stopped } else { ThreadMode::Failed });
+    }
+}
+
+/// Counts a thread as rebuilding its cache/dataset for a new seed until dropped,
+/// whichever `continue` the rebuild leaves through.
+struct RebuildingGuard;
+
+impl RebuildingGuard {
+    fn new() -> Self {
+        crate::stats::dataset_rebuild_started();
+        Self
+    }
+}
+
+impl Drop for RebuildingGuard {
+    fn drop(&mut self) {
+        crate::stats::dataset_rebuild_finished();
+    }
+}
+
+/// Byte order the nonce counter is written into the blob in, i.e. the order the
+/// nonce space is walked. Pools read the nonce back from the submitted bytes, so
+/// either order finds equally valid shares. For the same reason there is no
+/// switching order after rejected shares: it can't turn a reject into an accept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum NonceEndian {
+    #[default]
+    Be,
+    Le,
+}
+
+impl NonceEndian {
+    fn bytes(self, nonce: u32) -> [u8; 4] {
+        match self {
+            NonceEndian::Be => nonce.to_be_bytes(),
+            NonceEndian::Le => nonce.to_le_bytes(),
+        }
+    }
+}
+
+/// What the workers do while the pool connection is being re-established.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnDisconnect {
+    /// Keep hashing the last job, in case the pool comes back and still accepts it.
+    #[default]
+    KeepHashing,
+    /// Stop hashing until a new job arrives, to save power during outages.
+    Pause,
+}
+
+/// What each worker thread is started with; kept on the `Worker` so more
+/// threads can be spawned after `init`.
+struct ThreadContext {
+    share_tx: mpsc::Sender<Share>,
+    job_rx: WatchReceiver<Job>,
+    thread_modes: Arc<Mutex<Vec<ThreadMode>>>,
+    slots: Mutex<Vec<Arc<ThreadSlot>>>,
+    // How long each thread's most recent dataset took to build.
+    dataset_build_times: Arc<Mutex<Vec<Option<Duration>>>>,
+    active_threads: Arc<AtomicUsize>,
+    // Set while every thread should idle regardless of the active count.
+    suspended: Arc<AtomicBool>,
+    // Set once by `Worker::shutdown`; every thread exits at the top of its loop.
+    stopping: Arc<AtomicBool>,
+    max_threads: usize,
+    // Light mode's per-batch sleep; switchable at runtime.
+    light_mode: Arc<AtomicBool>,
+    debug_all: bool,
+    debug_hash_log: bool,
+    min_submit_difficulty: Option<u64>,
+    nonce_endian: NonceEndian,
+}
 
 pub struct Worker {
     share_rx: Receiver<Share>,
     job_tx: WatchSender<Job>,
+    expected_shares: Mutex<ExpectedShares>,
+    context: Arc<Mutex<ThreadContext>>,
+    min_submit_difficulty: Option<u64>,
 }
 
 impl Worker {
     #[tracing::instrument(skip(job))]
-    pub fn init(job: Job, num_threads: NonZeroUsize, fast: bool, debug_all: bool, debug_hash_log: bool) -> Self {
+    pub fn init(job: Job, num_threads: NonZeroUsize, fast: bool, debug_all: bool, debug_hash_log: bool, min_submit_difficulty: Option<u64>, nonce_endian: NonceEndian) -> Self {
         let (share_tx, share_rx) = mpsc::channel();
         let (job_tx, job_rx) = watch::channel(job.clone());
         let light_mode = !fast;
-        
-        
-        for i in 0..num_threads.get() {
-            let share_tx = share_tx.clone();
-            let mut job_rx = job_rx.clone();
-            
-            let worker_light_mode = light_mode;
-            thread::spawn(move || {
-                let span = tracing::info_span!("thread", id = i);
-                let _enter = span.enter();
-                
-                let mut vm: Option<RandomXVM> = None;
-                let mut cache: Option<randomx_rs::RandomXCache> = None;
-                let mut dataset: Option<randomx_rs::RandomXDataset> = None;
-                let mut current_seed: Vec<u8> = Vec::new();
-                let mut blob: Vec<u8> = Vec::new();
-                let mut difficulty: u64 = 0;
-                let mut job_id: String = String::new();
-                let light_mode = worker_light_mode;
-                
-                
-                let mut flags = RandomXFlag::get_recommended_flags();
-                flags.insert(RandomXFlag::FLAG_LARGE_PAGES);
-                flags.insert(RandomXFlag::FLAG_FULL_MEM);
-                
-                let thread_flags = flags;
-                let mut flags = thread_flags;
-                
-                let debug_all = debug_all;
-                let debug_hash_log = debug_hash_log;
-                
-                
-                let thread_offset = i as u32;
-                let thread_step = num_threads.get() as u32;
-                let mut nonce_counter: u32 = thread_offset;
-                
-                #[repr(align(64))]
-                struct AlignedBuffer([u8; 4]);
-                let mut aligned_nonce = AlignedBuffer([0u8; 4]);
-                
-                let initial_job = job_rx.get();
-                if !initial_job.seed.is_empty() {
-                    current_seed = initial_job.seed.clone();
-                    
-                    let cache_result = randomx_rs::RandomXCache::new(flags, &current_seed);
-                    cache = match cache_result {
-                        Ok(c) => {
-                            Some(c)
-                        },
-                        Err(e) => {
-                            let mut fallback_flags = flags;
-                            fallback_flags.remove(RandomXFlag::FLAG_LARGE_PAGES);
-                            match randomx_rs::RandomXCache::new(fallback_flags, &current_seed) {
-                                Ok(c) => {
-                                    flags = fallback_flags;
-                                    Some(c)
-                                },
-                                Err(_e2) => {
-                                    eprintln!("ERROR: Thread {} - Failed to create RandomXCache even without large pages", i);
-                                    return;
-                                }
-                            }
-                        }
-                    };
-                    
-                        if let Some(ref cache_ref) = cache {
-                            let dataset_result = randomx_rs::RandomXDataset::new(flags, cache_ref.clone(), 0);
-                            dataset = match dataset_result {
-                                Ok(d) => Some(d),
-                                Err(e) => {
-                                    let mut fallback_flags = flags;
-                                    fallback_flags.remove(RandomXFlag::FLAG_FULL_MEM);
-                                    if let Ok(d) = randomx_rs::RandomXDataset::new(fallback_flags, cache_ref.clone(), 0) {
-                                        flags = fallback_flags;
-                                        Some(d)
-                                    } else {
-                                        return;
-                                    }
-                                }
-                            };
-                        
-                        if let Some(ref dataset_ref) = dataset {
-                            let vm_result = randomx_rs::RandomXVM::new(flags, Some(cache_ref.clone()), Some(dataset_ref.clone()));
-                            match vm_result {
-                                Ok(new_vm) => {
-                                    vm = Some(new_vm);
-                                },
-                                Err(e) => {
-                                    eprintln!("ERROR: Thread {} - Failed to create RandomXVM: {}", i, e);
-                                    let mut fallback_flags = flags;
-                                    fallback_flags.remove(RandomXFlag::FLAG_LARGE_PAGES);
-                                    let vm_result = randomx_rs::RandomXVM::new(fallback_flags, Some(cache_ref.clone()), Some(dataset_ref.clone()));
-                                    match vm_result {
-                                        Ok(new_vm) => {
-                                            vm = Some(new_vm);
-                                        },
-                                        Err(_e2) => {
-                                            eprintln!("ERROR: Thread {} - Failed to create RandomXVM even with fallback flags", i);
-                                            return;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    
-                    blob = initial_job.blob.clone();
-                    difficulty = initial_job.difficulty();
-                    job_id = initial_job.id.clone();
-                    nonce_counter = thread_offset;
-                }
-                
-                
-                loop {
-                    
-                    if let Some(new_job) = job_rx.get_if_new() {
-                        if current_seed != new_job.seed {
-                            current_seed = new_job.seed.clone();
-                            
-                            let new_cache_result = randomx_rs::RandomXCache::new(flags, &current_seed);
-                            let new_cache = match new_cache_result {
-                                Ok(c) => c,
-                                Err(e) => {
-                                    eprintln!("ERROR: Thread {} - Failed to create new RandomXCache: {}", i, e);
-                                    continue;
-                                }
-                            };
-                            
-                            if let Some(ref mut vm_ref) = vm {
-                                if let Err(e) = vm_ref.reinit_cache(new_cache.clone()) {
-                                    eprintln!("ERROR: Thread {} - Failed to reinitialize VM cache: {}", i, e);
-                                    let vm_result = randomx_rs::RandomXVM::new(flags, Some(new_cache.clone()), dataset.clone());
-                                    match vm_result {
-                                        Ok(new_vm) => {
-                                            vm = Some(new_vm);
-                                        },
-                                        Err(e2) => {
-                                            eprintln!("ERROR: Thread {} - Failed to recreate RandomXVM after reinit_cache failure: {}", i, e2);
-                                            continue;
-                                        }
-                                    }
-                                }
-                            } else {
-                                let vm_result = randomx_rs::RandomXVM::new(flags, Some(new_cache.clone()), dataset.clone());
-                                match vm_result {
-                                    Ok(new_vm) => {
-                                        vm = Some(new_vm);
-                                    },
-                                    Err(e) => {
-                                        eprintln!("ERROR: Thread {} - Failed to create RandomXVM with new cache: {}", i, e);
-                                        continue;
-                                    }
-                                }
-                            }
-                            
-                            cache = Some(new_cache.clone());
-                            
-                            if flags.contains(RandomXFlag::FLAG_FULL_MEM) {
-                                if let Some(ref cache_ref) = cache {
-                                    let new_dataset_result = randomx_rs::RandomXDataset::new(flags, cache_ref.clone(), 0);
-                                    let new_dataset = match new_dataset_result {
-                                        Ok(d) => Some(d),
-                                        Err(e) => {
-                                            eprintln!("ERROR: Thread {} - Failed to create new RandomXDataset: {}", i, e);
-                                            let mut fallback_flags = flags;
-                                            fallback_flags.remove(RandomXFlag::FLAG_FULL_MEM);
-                                            if let Ok(d) = randomx_rs::RandomXDataset::new(fallback_flags, cache_ref.clone(), 0) {
-                                                flags = fallback_flags;
-                                                Some(d)
-                                            } else {
-                                                eprintln!("ERROR: Thread {} - Failed to create RandomXDataset even in cache-only mode", i);
-                                                continue;
-                                            }
-                                        }
-                                    };
-                                    
-                                    if let Some(ref mut vm_ref) = vm {
-                                        if let Some(ds) = new_dataset.clone() {
-                                            if let Err(e) = vm_ref.reinit_dataset(ds) {
-                                                eprintln!("ERROR: Thread {} - Failed to reinitialize VM dataset: {}", i, e);
-                                                let vm_result = randomx_rs::RandomXVM::new(flags, cache.clone(), new_dataset.clone());
-                                                match vm_result {
-                                                    Ok(new_vm) => {
-                                                        vm = Some(new_vm);
-                                                    },
-                                                    Err(e2) => {
-                                                        eprintln!("ERROR: Thread {} - Failed to recreate RandomXVM after reinit_dataset failure: {}", i, e2);
-                                                        continue;
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    dataset = new_dataset;
-                                }
-                            }
-                        }
-                        
-                        blob = new_job.blob.clone();
-                        difficulty = new_job.difficulty();
-                        job_id = new_job.id.clone();
-                        nonce_counter = thread_offset;
-                    }
-                    
-                    if let Some(ref vm) = vm {
-                        const BATCH_SIZE: usize = 100;
-                        
-                        for batch_idx in 0..BATCH_SIZE {
-                            nonce_counter = nonce_counter.wrapping_add(thread_step);
-                            
-                            aligned_nonce.0.copy_from_slice(&nonce_counter.to_be_bytes());
-                            blob[39..=42].copy_from_slice(&aligned_nonce.0);
-                            
-                            let hash_result = vm.calculate_hash(&blob);
-                            let hash = match hash_result {
-                                Ok(h) => h,
-                                Err(e) => {
-                                    eprintln!("ERROR: Thread {} - Batch {} - Hash calculation failed: {}", i, batch_idx, e);
-                                    continue;
-                                }
-                            };
-                            
-                            crate::hash_rate::get_hash_rate_tracker().lock().unwrap().increment(1);
-                            
-                            let hash_bytes: &[u8] = hash.as_ref();
-                            let hash_value = u64::from_le_bytes([
-                                hash_bytes[24], hash_bytes[25], 
-                                hash_bytes[26], hash_bytes[27],
-                                hash_bytes[28], hash_bytes[29], 
-                                hash_bytes[30], hash_bytes[31]
-                            ]);
-                            
-                            if debug_all || debug_hash_log {
-                                crate::hash_logger::log_hash_value(nonce_counter, hash_value, difficulty, &job_id);
-                            }
-                            
-                            if hash_value < difficulty {
-                                let _ = share_tx.send(Share {
-                                    job_id: job_id.clone(),
-                                    nonce: aligned_nonce.0.to_vec(),
-                                    hash: hash_bytes.into(),
-                                });
-                            }
-                        }
-                        
-                        if light_mode {
-                            std::thread::sleep(Duration::from_micros(100));
-                        }
-                        
-                    } else {
-                        std::thread::sleep(Duration::from_millis(10));
-                    }
-                }
-            });
+        let expected_shares = Mutex::new(ExpectedShares {
+            hashes_accounted: 0,
+            expected: 0.0,
+            pool_difficulty: counted_difficulty(&job, min_submit_difficulty),
+        });
+        let max_threads = crate::affinity::allowed_cpu_count()
+            .unwrap_or_else(|| thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+            .max(num_threads.get());
+        let context = ThreadContext {
+            share_tx,
+            job_rx,
+            thread_modes: Arc::new(Mutex::new(Vec::new())),
+            slots: Mutex::new(Vec::new()),
+            dataset_build_times: Arc::new(Mutex::new(Vec::new())),
+            active_threads: Arc::new(AtomicUsize::new(num_threads.get())),
+            suspended: Arc::new(AtomicBool::new(false)),
+            stopping: Arc::new(AtomicBool::new(false)),
+            max_threads,
+            light_mode: Arc::new(AtomicBool::new(light_mode)),
+            debug_all,
+            debug_hash_log,
+            min_submit_difficulty,
+            nonce_endian,
+        };
+        for _ in 0..num_threads.get() {
+            spawn_thread(&context);
         }
         
         Self {
             share_rx,
             job_tx,
+            expected_shares,
+            context: Arc::new(Mutex::new(context)),
+            min_submit_difficulty,
         }
     }
     
+    /// Switches every thread to `job`. Shares already found for the previous job
+    /// are still submitted under their own job id, and the pool decides whether
+    /// they still count, so no set of recent jobs is kept here.
     pub fn work(&self, job: Job) {
+        {
+            let mut expected = self.expected_shares.lock().unwrap();
+            expected.account(self.get_lifetime_hashes());
+            expected.pool_difficulty = counted_difficulty(&job, self.min_submit_difficulty);
+        }
         self.job_tx.send(job);
     }
+
+    /// Shares the work done so far should have found on average at the pools' difficulties.
+    pub fn get_expected_shares(&self) -> f64 {
+        let mut expected = self.expected_shares.lock().unwrap();
+        expected.account(self.get_lifetime_hashes());
+        expected.expected
+    }
     
     pub fn try_recv_share(&self) -> Result<Share, TryRecvError> {
         self.share_rx.try_recv()
     }
 
+    /// Waits up to `timeout` for a share, so a loop can idle without sitting on one.
+    pub fn recv_share_timeout(&self, timeout: Duration) -> Option<Share> {
+        self.share_rx.recv_timeout(timeout).ok()
+    }
+
+    /// Drops shares queued for a job the pool no longer knows about (e.g. after a reconnect).
+    pub fn discard_pending_shares(&self) -> usize {
+        self.share_rx.try_iter().count()
+    }
+
+    /// Modes of the active threads; threads paused above the count are left out.
+    pub fn get_thread_modes(&self) -> Vec<ThreadMode> {
+        let context = self.context.lock().unwrap();
+        let active = context.active_threads.load(Ordering::Relaxed);
+        let modes = context.thread_modes.lock().unwrap();
+        modes.iter().take(active).copied().collect()
+    }
+
+    /// How long the active threads' current dataset took to build. The threads share
+    /// one dataset per seed, so this is also roughly how long a seed change stalls hashing.
+    pub fn get_dataset_build_time(&self) -> Option<Duration> {
+        let context = self.context.lock().unwrap();
+        let active = context.active_threads.load(Ordering::Relaxed);
+        let times = context.dataset_build_times.lock().unwrap();
+        times.iter().take(active).flatten().max().copied()
+    }
+
+    /// The job the threads are currently working on.
+    pub fn current_job(&self) -> Job {
+        self.context.lock().unwrap().job_rx.clone().get()
+    }
+
+    pub fn get_thread_count(&self) -> usize {
+        self.context.lock().unwrap().active_threads.load(Ordering::Relaxed)
+    }
+
+    /// Changes how many threads hash, clamped to 1..=available (unreserved) CPUs.
+    /// Threads above the count are paused rather than stopped, so they keep
+    /// their dataset and resume without rebuilding it. Returns the new count.
+    pub fn set_thread_count(&self, count: usize) -> usize {
+        let context = self.context.lock().unwrap();
+        let count = count.clamp(1, context.max_threads);
+        let spawned = context.thread_modes.lock().unwrap().len();
+        for _ in spawned..count {
+            spawn_thread(&context);
+        }
+        context.active_threads.store(count, Ordering::Relaxed);
+        count
+    }
+
+    /// Turns light mode's per-batch sleep on or off for every thread.
+    pub fn set_light_mode(&self, light: bool) {
+        self.context.lock().unwrap().light_mode.store(light, Ordering::Relaxed);
+    }
+
+    /// Pauses (or resumes) every thread without touching the thread count; paused
+    /// threads keep their dataset, like those above the count.
+    pub fn set_suspended(&self, suspended: bool) {
+        self.context.lock().unwrap().suspended.store(suspended, Ordering::Relaxed);
+    }
+
+    /// Starts a thread that replaces any active thread whose heartbeat hasn't moved
+    /// for `timeout` while it should be hashing. Threads that are starting, paused or
+    /// rebuilding a dataset are left alone, since those legitimately go quiet. The
+    /// wedged thread can't be killed; it exits on its own if it ever gets unstuck.
+    /// When a restart is refused (see `restart_refusal`), the process exits with
+    /// `exit_code::THREADS_STALLED` so a supervisor can start it afresh.
+    pub fn start_stall_monitor(&self, timeout: Duration) {
+        let context = self.context.clone();
+        thread::spawn(move || {
+            let mut detector = StallDetector::new(timeout);
+            loop {
+                thread::sleep(STALL_CHECK_INTERVAL);
+                let context = context.lock().unwrap();
+                if context.stopping.load(Ordering::Relaxed) {
+                    return;
+                }
+                let slots = context.slots.lock().unwrap().clone();
+                let modes = context.thread_modes.lock().unwrap().clone();
+                let active = context.active_threads.load(Ordering::Relaxed);
+                let idle = context.suspended.load(Ordering::Relaxed) || crate::stats::is_rebuilding_dataset();
+                let heartbeats: Vec<_> = slots
+                    .iter()
+                    .enumerate()
+                    .map(|(i, slot)| {
+                        let watched = !idle && i < active && matches!(modes[i], ThreadMode::Running(_));
+                        watched.then(|| slot.heartbeat.load(Ordering::Relaxed))
+                    })
+                    .collect();
+
+                for i in detector.stalled(&heartbeats) {
+                    let previous_still_stuck = slots[i].retired.lock().unwrap().as_ref().is_some_and(|handle| !handle.is_finished());
+                    if let Some(reason) = restart_refusal(crate::stats::thread_restarts(), previous_still_stuck) {
+                        tracing::error!(thread = i, stalled_secs = timeout.as_secs(), reason = %reason, "worker thread stalled, not restarting it");
+                        eprintln!("ERROR: Thread {} - No progress for {}s and {}; exiting so the miner can be started afresh", i, timeout.as_secs(), reason);
+                        crate::keyboard::restore_terminal();
+                        std::process::exit(crate::exit_code::THREADS_STALLED.into());
+                    }
+                    tracing::warn!(thread = i, stalled_secs = timeout.as_secs(), "worker thread stalled, restarting it");
+                    eprintln!("WARNING: Thread {} - No progress for {}s, restarting it", i, timeout.as_secs());
+                    restart_thread(&context, i);
+                    crate::stats::record_thread_restart();
+                }
+            }
+        });
+    }
+
+    /// Stops every thread and waits up to `SHUTDOWN_TIMEOUT` for them to exit, which
+    /// frees their VMs, caches and datasets. A thread in the middle of building a
+    /// dataset can't be interrupted; it is left to exit when the build finishes,
+    /// or with the process. Queued shares are dropped with the worker.
+    pub fn shutdown(self) {
+        let handles: Vec<_> = {
+            let context = self.context.lock().unwrap();
+            context.stopping.store(true, Ordering::Relaxed);
+            let slots = context.slots.lock().unwrap();
+            slots.iter().filter_map(|slot| slot.handle.lock().unwrap().take()).collect()
+        };
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while Instant::now() < deadline && !handles.iter().all(|handle| handle.is_finished()) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let (finished, running): (Vec<_>, Vec<_>) = handles.into_iter().partition(|handle| handle.is_finished());
+        for handle in finished {
+            let _ = handle.join();
+        }
+        if running.is_empty() {
+            tracing::debug!("All worker threads stopped");
+        } else {
+            tracing::debug!(threads = running.len(), "Worker threads still busy at shutdown, not waiting for them");
+        }
+    }
+
     pub fn get_hash_rate(&self) -> f64 {
         crate::hash_rate::get_hash_rate_tracker().lock().unwrap().get_hash_rate()
     }
@@ -297,15 +453,321 @@ impl Worker {
         crate::hash_rate::get_hash_rate_tracker().lock().unwrap().get_total_hashes()
     }
 
+    pub fn get_lifetime_hashes(&self) -> u64 {
+        crate::hash_rate::get_hash_rate_tracker().lock().unwrap().get_lifetime_hashes()
+    }
+
     pub fn get_elapsed_time(&self) -> std::time::Duration {
         crate::hash_rate::get_hash_rate_tracker().lock().unwrap().get_elapsed_time()
     }
+
+    /// Whether hash rate reporting has started; see `hash_rate::WarmupCriteria`.
+    pub fn is_warmed_up(&self) -> bool {
+        crate::hash_rate::get_hash_rate_tracker().lock().unwrap().is_warmup_complete()
+    }
+}
+
+/// First byte outside the nonce where a thread's working blob differs from the
+/// job's prepared blob, or `None` if only the nonce changed. Guards against a bug
+/// writing the nonce in the wrong place, which would get every share rejected.
+fn blob_divergence(blob: &[u8], job_blob: &[u8]) -> Option<usize> {
+    if blob.len() != job_blob.len() {
+        return Some(blob.len().min(job_blob.len()));
+    }
+    let nonce = NONCE_OFFSET..MIN_BLOB_LEN;
+    (0..blob.len()).find(|&i| !nonce.contains(&i) && blob[i] != job_blob[i])
+}
+
+/// A VM for `seed` on the dataset every thread shares, and the flags it runs with;
+/// retries without large pages if the VM can't get them. Records the dataset's
+/// build time in the thread's slot. `None`, once logged, if RandomX can't be set up.
+fn vm_for_seed(i: usize, seed: &[u8], flags: RandomXFlag, dataset_build_times: &Mutex<Vec<Option<Duration>>>) -> Option<(randomx::Vm, RandomXFlag)> {
+    let data = match randomx::seed_data(seed, flags) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("ERROR: Thread {} - Failed to create RandomXCache even without large pages: {}", i, e);
+            return None;
+        }
+    };
+    if data.build_time.is_some() {
+        dataset_build_times.lock().unwrap()[i] = data.build_time;
+    }
+    let mut flags = data.flags;
+    let vm = randomx::Vm::new(flags, data.clone()).or_else(|e| {
+        eprintln!("ERROR: Thread {} - Failed to create RandomXVM: {}", i, e);
+        flags.remove(RandomXFlag::FLAG_LARGE_PAGES);
+        randomx::Vm::new(flags, data)
+    });
+    match vm {
+        Ok(vm) => Some((vm, flags)),
+        Err(_) => {
+            eprintln!("ERROR: Thread {} - Failed to create RandomXVM even with fallback flags", i);
+            None
+        }
+    }
+}
+
+/// A new job, if one has arrived, checked every `poll_interval` hashes into a batch.
+/// The top of the thread loop already checks before the batch's first hash.
+fn job_arrived_mid_batch(job_rx: &mut WatchReceiver<Job>, batch_idx: usize, poll_interval: usize) -> Option<Job> {
+    if batch_idx > 0 && batch_idx.is_multiple_of(poll_interval) {
+        job_rx.get_if_new()
+    } else {
+        None
+    }
+}
+
+/// The per-hash step of the batch loop: the nonce is written into the blob in
+/// place and the hash comes back on the stack, so a batch allocates nothing
+/// until it finds a share.
+fn hash_nonce(vm: &randomx::Vm, blob: &mut [u8], nonce: &[u8; 4]) -> [u8; 32] {
+    blob[NONCE_OFFSET..MIN_BLOB_LEN].copy_from_slice(nonce);
+    vm.calculate_hash(blob)
+}
+
+/// The top word (`hash_value`) filters almost every hash; the full 256-bit check decides.
+fn is_share(hash: &[u8; 32], hash_value: u64, submit_threshold: u64, submit_target: &[u8; 32]) -> bool {
+    hash_value <= submit_threshold && target::hash_meets_target(hash, submit_target)
+}
+
+/// Threshold and full target a share must meet: the job's own, raised to
+/// `--min-submit-difficulty` when the pool's target is below that floor.
+fn submit_target_for(job: &Job, min_submit_difficulty: Option<u64>) -> (u64, [u8; 32]) {
+    let difficulty = counted_difficulty(job, min_submit_difficulty);
+    (target::difficulty_to_threshold(difficulty), target::difficulty_to_target(difficulty))
+}
+
+/// Difficulty shares are actually found at, for the expected-share count.
+fn counted_difficulty(job: &Job, min_submit_difficulty: Option<u64>) -> u64 {
+    job.pool_difficulty().max(min_submit_difficulty.unwrap_or(0))
+}
+
+/// Spawns the next worker thread. Every thread strides the nonce space by
+/// `max_threads` from its own index, so threads added later never overlap.
+fn spawn_thread(context: &ThreadContext) {
+    let i = {
+        let mut modes = context.thread_modes.lock().unwrap();
+        modes.push(ThreadMode::Starting);
+        context.dataset_build_times.lock().unwrap().push(None);
+        context.slots.lock().unwrap().push(Arc::default());
+        modes.len() - 1
+    };
+    start_thread(context, i);
+}
+
+/// Replaces the thread in slot `i` with a fresh one on the same nonce stride,
+/// retiring the old one by bumping the slot's generation.
+fn restart_thread(context: &ThreadContext, i: usize) {
+    {
+        let mut modes = context.thread_modes.lock().unwrap();
+        let slot = context.slots.lock().unwrap()[i].clone();
+        slot.generation.fetch_add(1, Ordering::Relaxed);
+        *slot.retired.lock().unwrap() = slot.handle.lock().unwrap().take();
+        modes[i] = ThreadMode::Starting;
+    }
+    start_thread(context, i);
+}
+
+fn start_thread(context: &ThreadContext, i: usize) {
+    let slot = context.slots.lock().unwrap()[i].clone();
+    let generation = slot.generation.load(Ordering::Relaxed);
+    let dataset_build_times = context.dataset_build_times.clone();
+    let share_tx = context.share_tx.clone();
+    let mut job_rx = context.job_rx.clone();
+    let stopping = context.stopping.clone();
+    let mode_guard = ThreadModeGuard { modes: context.thread_modes.clone(), index: i, slot: slot.clone(), generation, stopping: stopping.clone() };
+    let active_threads = context.active_threads.clone();
+    let suspended = context.suspended.clone();
+    let thread_step = context.max_threads as u32;
+    let light_mode = context.light_mode.clone();
+    let debug_all = context.debug_all;
+    let debug_hash_log = context.debug_hash_log;
+    let min_submit_difficulty = context.min_submit_difficulty;
+    let nonce_endian = context.nonce_endian;
+    let hash_log_sample = crate::hash_logger::sample_interval();
+    let near_miss_factor = crate::share_logger::near_miss_factor();
+    let job_poll_interval = JOB_POLL_INTERVAL.load(Ordering::Relaxed);
+    let end_batch_on_share = END_BATCH_ON_SHARE.load(Ordering::Relaxed);
+    
+    let handle = thread::spawn(move || {
+        let span = tracing::info_span!("thread", id = i);
+        let _enter = span.enter();
+        let mode_guard = mode_guard;
+        crate::affinity::apply_to_current_thread();
+        
+        let mut vm: Option<randomx::Vm> = None;
+        let mut hashes_since_logged: u64 = 0;
+        let mut current_seed: Vec<u8> = Vec::new();
+        let mut blob: Vec<u8> = Vec::new();
+        // The job's blob as prepared, before any nonce; what `blob` is checked against.
+        let mut job_blob: Vec<u8> = Vec::new();
+        let mut batches_since_blob_check: u32 = 0;
+        let mut difficulty: u64 = 0;
+        let mut submit_threshold: u64 = 0;
+        let mut submit_target = [0u8; 32];
+        let mut job_id: String = String::new();
+        let mut job_pool: usize = 0;
+        
+        
+        let mut flags = crate::argon2::apply(RandomXFlag::get_recommended_flags());
+        flags.insert(RandomXFlag::FLAG_LARGE_PAGES);
+        flags.insert(RandomXFlag::FLAG_FULL_MEM);
+        
+        let thread_flags = flags;
+        
+        
+        let thread_offset = i as u32;
+        let mut nonce_counter: u32 = thread_offset;
+        
+        let initial_job = job_rx.get();
+        if !initial_job.seed.is_empty() {
+            current_seed = initial_job.seed.clone();
+            let Some((new_vm, vm_flags)) = vm_for_seed(i, &current_seed, thread_flags, &dataset_build_times) else {
+                return;
+            };
+            vm = Some(new_vm);
+            flags = vm_flags;
+            
+            blob = initial_job.blob.clone();
+            initial_job.write_extra_nonce(&mut blob);
+            job_blob.clone_from(&blob);
+            difficulty = initial_job.difficulty();
+            (submit_threshold, submit_target) = submit_target_for(&initial_job, min_submit_difficulty);
+            job_id = initial_job.id.clone();
+            job_pool = initial_job.pool;
+            nonce_counter = thread_offset;
+            if vm.is_some() {
+                mode_guard.set(ThreadMode::Running(flags));
+            }
+        }
+        
+        
+        let mut paused = false;
+        // A job picked up mid-batch, switched to at the top of the loop.
+        let mut pending_job: Option<Job> = None;
+        loop {
+            if !mode_guard.is_current() || stopping.load(Ordering::Relaxed) {
+                return;
+            }
+            mode_guard.slot.heartbeat.fetch_add(1, Ordering::Relaxed);
+            if i >= active_threads.load(Ordering::Relaxed) || suspended.load(Ordering::Relaxed) {
+                if !paused {
+                    mode_guard.set(ThreadMode::Paused);
+                    paused = true;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            if paused {
+                paused = false;
+                if vm.is_some() {
+                    mode_guard.set(ThreadMode::Running(flags));
+                }
+            }
+            
+            if let Some(new_job) = pending_job.take().or_else(|| job_rx.get_if_new()) {
+                if current_seed != new_job.seed {
+                    let _rebuilding = RebuildingGuard::new();
+                    current_seed = new_job.seed.clone();
+                    // Let go of the old seed's dataset first, so it can be freed before
+                    // the new one is allocated.
+                    vm = None;
+                    match vm_for_seed(i, &current_seed, thread_flags, &dataset_build_times) {
+                        Some((new_vm, vm_flags)) => {
+                            vm = Some(new_vm);
+                            flags = vm_flags;
+                            mode_guard.set(ThreadMode::Running(flags));
+                        }
+                        None => mode_guard.set(ThreadMode::Failed),
+                    }
+                }
+                
+                // clone_from reuses the existing allocations when the sizes allow.
+                blob.clone_from(&new_job.blob);
+                new_job.write_extra_nonce(&mut blob);
+                job_blob.clone_from(&blob);
+                difficulty = new_job.difficulty();
+                (submit_threshold, submit_target) = submit_target_for(&new_job, min_submit_difficulty);
+                job_id.clone_from(&new_job.id);
+                job_pool = new_job.pool;
+                nonce_counter = thread_offset;
+            }
+            
+            if let Some(ref vm) = vm {
+                // Counted per batch: the tracker takes two locks per call.
+                let mut batch_hashes: u64 = 0;
+                
+                for batch_idx in 0..BATCH_SIZE {
+                    pending_job = job_arrived_mid_batch(&mut job_rx, batch_idx, job_poll_interval);
+                    if pending_job.is_some() {
+                        break;
+                    }
+                    nonce_counter = nonce_counter.wrapping_add(thread_step);
+                    
+                    let nonce = nonce_endian.bytes(nonce_counter);
+                    let hash = hash_nonce(vm, &mut blob, &nonce);
+                    
+                    batch_hashes += 1;
+                    
+                    let hash_value = target::hash_top_word(&hash);
+                    
+                    if debug_all || debug_hash_log {
+                        hashes_since_logged += 1;
+                        // Shares are always logged, sampled or not.
+                        if hashes_since_logged >= hash_log_sample || hash_value <= difficulty {
+                            hashes_since_logged = 0;
+                            crate::hash_logger::log_hash_value(nonce_counter, hash_value, difficulty, &job_id);
+                        }
+                    }
+                    
+                    if is_share(&hash, hash_value, submit_threshold, &submit_target) {
+                        let _ = share_tx.send(Share {
+                            job_id: job_id.clone(),
+                            blob: blob.clone(),
+                            nonce: nonce.to_vec(),
+                            hash: hash.to_vec(),
+                            difficulty: u64::MAX / difficulty,
+                            found_at: Instant::now(),
+                            pool: job_pool,
+                        });
+                        if end_batch_on_share {
+                            thread::yield_now();
+                            break;
+                        }
+                    } else if near_miss_factor.is_some_and(|factor| hash_value as f64 <= submit_threshold as f64 * factor) {
+                        crate::share_logger::log_near_miss(&job_id, &nonce, &hash, u64::MAX / difficulty);
+                    }
+                }
+                crate::hash_rate::get_hash_rate_tracker().lock().unwrap().increment(batch_hashes);
+                
+                if debug_all {
+                    batches_since_blob_check += 1;
+                    if batches_since_blob_check >= BLOB_CHECK_BATCHES {
+                        batches_since_blob_check = 0;
+                        if let Some(offset) = blob_divergence(&blob, &job_blob) {
+                            tracing::error!(thread = i, job_id = %job_id, offset, "Working blob diverged from the job outside the nonce; resyncing");
+                            eprintln!("ERROR: Thread {} - Working blob for job {} differs from the job at byte {} (outside the nonce at {}); shares would be rejected, resyncing", i, job_id, offset, NONCE_OFFSET);
+                            blob.clone_from(&job_blob);
+                        }
+                    }
+                }
+                
+                if light_mode.load(Ordering::Relaxed) {
+                    std::thread::sleep(crate::throttle::batch_sleep());
+                }
+                
+            } else {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    });
+    // A replaced thread's handle is dropped, detaching it: it may never get unstuck.
+    *slot.handle.lock().unwrap() = Some(handle);
 }
 
 #[cfg(target_os = "linux")]
 pub fn enable_huge_pages(num_threads: NonZeroUsize) {
-    use std::process::{Command, Stdio};
-    use std::io::Write;
+    use std::process::Command;
     use sysinfo::{RefreshKind, System};
 
     println!("Checking for non-interactive sudo permissions...");
@@ -330,10 +792,12 @@ pub fn enable_huge_pages(num_threads: NonZeroUsize) {
     sys.refresh_memory();
     let total_memory_bytes = sys.total_memory();
 
-    const RANDOMX_THREAD_MEMORY_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+    // One dataset and cache shared by every thread, plus each thread's scratchpad.
+    const RANDOMX_SHARED_MEMORY_BYTES: u64 = (2080 + 256) * 1024 * 1024;
+    const RANDOMX_THREAD_MEMORY_BYTES: u64 = 2 * 1024 * 1024;
     const HUGE_PAGE_SIZE_BYTES: u64 = 2 * 1024 * 1024;
 
-    let required_memory_for_threads_bytes = num_threads.get() as u64 * RANDOMX_THREAD_MEMORY_BYTES;
+    let required_memory_for_threads_bytes = RANDOMX_SHARED_MEMORY_BYTES + num_threads.get() as u64 * RANDOMX_THREAD_MEMORY_BYTES;
     let required_huge_pages = required_memory_for_threads_bytes / HUGE_PAGE_SIZE_BYTES;
 
     const MIN_FREE_MEMORY_BYTES: u64 = 4 * 1024 * 1024 * 1024;
@@ -356,9 +820,49 @@ pub fn enable_huge_pages(num_threads: NonZeroUsize) {
 
     println!("Attempting to configure {} huge pages...", required_huge_pages);
 
+    if let Err(status) = sudo_write("/proc/sys/vm/nr_hugepages", required_huge_pages) {
+        eprintln!("❌ Failed to configure huge pages. Status: {:?}", status);
+        eprintln!("   Please ensure you have 'sudo' permissions and that the command is allowed.");
+        eprintln!("   You can manually run: echo {} | sudo tee /proc/sys/vm/nr_hugepages", required_huge_pages);
+        return;
+    }
+
+    // The kernel silently allocates fewer pages than asked for when memory is
+    // fragmented, so read back what it actually reserved.
+    let mut allocated = read_nr_hugepages();
+    if allocated.is_some_and(|n| n < required_huge_pages) {
+        println!("⚠️  Kernel only allocated {} of {} huge pages. Compacting memory and retrying...",
+                 allocated.unwrap_or(0), required_huge_pages);
+        let _ = sudo_write("/proc/sys/vm/compact_memory", 1);
+        let _ = sudo_write("/proc/sys/vm/nr_hugepages", required_huge_pages);
+        allocated = read_nr_hugepages();
+    }
+
+    let free = read_meminfo_value("HugePages_Free");
+    match allocated {
+        Some(n) if n >= required_huge_pages => {
+            println!("✅ Successfully configured {} huge pages ({} free).", n, free.unwrap_or(n));
+        }
+        Some(n) => {
+            println!("⚠️  Only {} of {} huge pages could be allocated ({} free); some threads will run without large pages.",
+                     n, required_huge_pages, free.unwrap_or(0));
+            println!("   Memory is likely fragmented. Reboot, or reserve them at boot with vm.nr_hugepages={} in /etc/sysctl.conf.",
+                     required_huge_pages);
+        }
+        None => {
+            println!("⚠️  Configured huge pages but could not read back /proc/sys/vm/nr_hugepages to confirm.");
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn sudo_write(path: &str, value: u64) -> Result<(), std::process::ExitStatus> {
+    use std::process::{Command, Stdio};
+    use std::io::Write;
+
     let mut child = Command::new("sudo")
         .arg("tee")
-        .arg("/proc/sys/vm/nr_hugepages")
+        .arg(path)
         .stdin(Stdio::piped())
         .stdout(Stdio::null()) 
         .stderr(Stdio::inherit()) 
@@ -367,18 +871,25 @@ pub fn enable_huge_pages(num_threads: NonZeroUsize) {
 
     {
         let stdin = child.stdin.as_mut().expect("Failed to open stdin");
-        stdin.write_all(format!("{}", required_huge_pages).as_bytes()).expect("Failed to write to stdin");
+        stdin.write_all(format!("{}", value).as_bytes()).expect("Failed to write to stdin");
     }
 
     let status = child.wait().expect("Failed to wait on sudo tee command");
+    if status.success() { Ok(()) } else { Err(status) }
+}
 
-    if status.success() {
-        println!("✅ Successfully configured {} huge pages.", required_huge_pages);
-    } else {
-        eprintln!("❌ Failed to configure huge pages. Status: {:?}", status);
-        eprintln!("   Please ensure you have 'sudo' permissions and that the command is allowed.");
-        eprintln!("   You can manually run: echo {} | sudo tee /proc/sys/vm/nr_hugepages", required_huge_pages);
-    }
+#[cfg(target_os = "linux")]
+pub(crate) fn read_nr_hugepages() -> Option<u64> {
+    std::fs::read_to_string("/proc/sys/vm/nr_hugepages").ok()?.trim().parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn read_meminfo_value(key: &str) -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    meminfo.lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+        .and_then(|value| value.split_whitespace().next())
+        .and_then(|value| value.parse().ok())
 }
 
 #[cfg(not(target_os = "linux"))]
@@ -487,3 +998,232 @@ pub fn apply_msr_mods() {
 pub fn apply_msr_mods() {
     println!("ℹ️  MSR modifications only available on Linux");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::MockClock, stratum::mock::{BLOB, SEED_HASH}};
+    use serde_json::json;
+
+    fn job(id: &str) -> Job {
+        serde_json::from_value(json!({ "job_id": id, "blob": BLOB, "seed_hash": SEED_HASH, "target": "b88d0600" })).unwrap()
+    }
+
+    /// Batch indices at which a job sent before the batch starts is picked up.
+    fn pickups(poll_interval: usize) -> Vec<usize> {
+        let (job_tx, mut job_rx) = watch::channel(job("1"));
+        job_rx.get_if_new();
+        job_tx.send(job("2"));
+        (0..BATCH_SIZE).filter(|&batch_idx| job_arrived_mid_batch(&mut job_rx, batch_idx, poll_interval).is_some()).collect()
+    }
+
+    #[test]
+    fn new_job_is_picked_up_at_the_next_poll() {
+        assert_eq!(pickups(10), vec![10]);
+        assert_eq!(pickups(1), vec![1]);
+    }
+
+    #[test]
+    fn default_poll_interval_leaves_the_batch_alone() {
+        assert!(pickups(BATCH_SIZE).is_empty());
+    }
+
+    #[test]
+    fn no_new_job_means_no_switch() {
+        let (_job_tx, mut job_rx) = watch::channel(job("1"));
+        job_rx.get_if_new();
+        assert!((0..BATCH_SIZE).all(|batch_idx| job_arrived_mid_batch(&mut job_rx, batch_idx, 1).is_none()));
+    }
+
+    fn job_blob() -> Vec<u8> {
+        job("1").blob
+    }
+
+    #[test]
+    fn nonce_changes_are_not_divergence() {
+        let job_blob = job_blob();
+        let mut blob = job_blob.clone();
+        assert_eq!(blob_divergence(&blob, &job_blob), None);
+        blob[NONCE_OFFSET..MIN_BLOB_LEN].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(blob_divergence(&blob, &job_blob), None);
+    }
+
+    #[test]
+    fn bytes_around_the_nonce_are_checked() {
+        let job_blob = job_blob();
+        for offset in [0, NONCE_OFFSET - 1, MIN_BLOB_LEN, job_blob.len() - 1] {
+            let mut blob = job_blob.clone();
+            blob[offset] ^= 0xff;
+            assert_eq!(blob_divergence(&blob, &job_blob), Some(offset));
+        }
+    }
+
+    #[test]
+    fn nonce_written_off_by_one_is_caught() {
+        let job_blob = job_blob();
+        let mut blob = job_blob.clone();
+        blob[NONCE_OFFSET + 1..MIN_BLOB_LEN + 1].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(blob_divergence(&blob, &job_blob), Some(MIN_BLOB_LEN));
+    }
+
+    #[test]
+    fn length_mismatch_is_divergence() {
+        let job_blob = job_blob();
+        assert_eq!(blob_divergence(&job_blob[..60], &job_blob), Some(60));
+        let mut longer = job_blob.clone();
+        longer.push(0);
+        assert_eq!(blob_divergence(&longer, &job_blob), Some(job_blob.len()));
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(120);
+
+    fn detector() -> (StallDetector<MockClock>, MockClock) {
+        let clock = MockClock::new();
+        (StallDetector::with_clock(TIMEOUT, clock.clone()), clock)
+    }
+
+    #[test]
+    fn thread_without_progress_is_reported_after_the_timeout() {
+        let (mut detector, clock) = detector();
+        assert!(detector.stalled(&[Some(7), Some(3)]).is_empty());
+        clock.advance(TIMEOUT - STALL_CHECK_INTERVAL);
+        assert!(detector.stalled(&[Some(8), Some(3)]).is_empty());
+        clock.advance(STALL_CHECK_INTERVAL);
+        assert_eq!(detector.stalled(&[Some(9), Some(3)]), vec![1]);
+    }
+
+    #[test]
+    fn restarted_slot_gets_a_full_timeout() {
+        let (mut detector, clock) = detector();
+        detector.stalled(&[Some(3)]);
+        clock.advance(TIMEOUT);
+        assert_eq!(detector.stalled(&[Some(3)]), vec![0]);
+        clock.advance(TIMEOUT - STALL_CHECK_INTERVAL);
+        assert!(detector.stalled(&[Some(3)]).is_empty());
+        clock.advance(STALL_CHECK_INTERVAL);
+        assert_eq!(detector.stalled(&[Some(3)]), vec![0]);
+    }
+
+    #[test]
+    fn quiet_slots_are_not_timed() {
+        let (mut detector, clock) = detector();
+        detector.stalled(&[Some(3)]);
+        clock.advance(TIMEOUT);
+        // Paused or rebuilding for the whole timeout: not a stall.
+        assert!(detector.stalled(&[None]).is_empty());
+        clock.advance(TIMEOUT - STALL_CHECK_INTERVAL);
+        assert!(detector.stalled(&[Some(3)]).is_empty());
+        clock.advance(STALL_CHECK_INTERVAL);
+        assert_eq!(detector.stalled(&[Some(3)]), vec![0]);
+    }
+
+    #[test]
+    fn slots_added_later_start_their_own_timer() {
+        let (mut detector, clock) = detector();
+        detector.stalled(&[Some(1)]);
+        clock.advance(TIMEOUT / 2);
+        assert!(detector.stalled(&[Some(2), Some(0)]).is_empty());
+        clock.advance(TIMEOUT / 2);
+        assert!(detector.stalled(&[Some(3), Some(0)]).is_empty());
+        clock.advance(TIMEOUT / 2);
+        assert_eq!(detector.stalled(&[Some(4), Some(0)]), vec![1]);
+    }
+
+    #[test]
+    fn restarts_are_capped() {
+        assert_eq!(restart_refusal(0, false), None);
+        assert_eq!(restart_refusal(MAX_THREAD_RESTARTS - 1, false), None);
+        assert!(restart_refusal(MAX_THREAD_RESTARTS, false).is_some());
+    }
+
+    #[test]
+    fn restart_is_refused_while_the_replaced_thread_is_stuck() {
+        assert!(restart_refusal(1, true).is_some());
+    }
+
+    /// A worker whose threads wait for a seed, so no dataset is built.
+    fn idle_worker(threads: usize) -> Worker {
+        let job = Job { seed: Vec::new(), ..job("1") };
+        Worker::init(job, NonZeroUsize::new(threads).unwrap(), true, false, false, None, NonceEndian::Le)
+    }
+
+    /// How long `shutdown` took to return.
+    fn time_shutdown(worker: Worker) -> Duration {
+        let started = Instant::now();
+        worker.shutdown();
+        started.elapsed()
+    }
+
+    #[test]
+    fn shutdown_returns_promptly() {
+        let elapsed = time_shutdown(idle_worker(4));
+        // Well inside SHUTDOWN_TIMEOUT: every thread saw the flag and exited.
+        assert!(elapsed < Duration::from_secs(1), "took {:?}", elapsed);
+    }
+
+    #[test]
+    fn paused_threads_stop_promptly() {
+        let worker = idle_worker(4);
+        worker.set_thread_count(1);
+        worker.set_suspended(true);
+        thread::sleep(Duration::from_millis(50));
+        let elapsed = time_shutdown(worker);
+        assert!(elapsed < Duration::from_secs(1), "took {:?}", elapsed);
+    }
+
+    /// A light-mode VM: the same per-hash code as full mode without the 2 GiB dataset.
+    fn light_vm() -> randomx::Vm {
+        let data = randomx::seed_data(&hex::decode(SEED_HASH).unwrap(), RandomXFlag::get_recommended_flags()).unwrap();
+        randomx::Vm::new(data.flags, data).unwrap()
+    }
+
+    #[test]
+    fn hashing_a_batch_does_not_allocate() {
+        let vm = light_vm();
+        let mut blob = job_blob();
+        let (submit_threshold, submit_target) = submit_target_for(&job("1"), None);
+        let before = crate::alloc_counter::allocations();
+        for nonce_counter in 0..BATCH_SIZE as u32 {
+            let hash = hash_nonce(&vm, &mut blob, &NonceEndian::Le.bytes(nonce_counter));
+            std::hint::black_box(is_share(&hash, target::hash_top_word(&hash), submit_threshold, &submit_target));
+        }
+        assert_eq!(crate::alloc_counter::allocations(), before);
+    }
+
+    /// Micro-benchmark of the batch loop's per-hash step, split into RandomX and
+    /// the loop's own work around it. RandomX runs in light mode here, several
+    /// times slower than a real full-mode hash.
+    ///
+    ///     cargo test --release hot_path -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark"]
+    fn hot_path_benchmark() {
+        const HASHES: u32 = 200;
+        const LOOP_ONLY_HASHES: u32 = 10_000_000;
+        let vm = light_vm();
+        let mut blob = job_blob();
+        let (submit_threshold, submit_target) = submit_target_for(&job("1"), None);
+
+        let allocations = crate::alloc_counter::allocations();
+        let started = Instant::now();
+        for nonce_counter in 0..HASHES {
+            let hash = hash_nonce(&vm, &mut blob, &NonceEndian::Le.bytes(nonce_counter));
+            std::hint::black_box(is_share(&hash, target::hash_top_word(&hash), submit_threshold, &submit_target));
+        }
+        let per_hash = started.elapsed() / HASHES;
+        let allocations = crate::alloc_counter::allocations() - allocations;
+
+        // Everything but RandomX: the nonce write and the share check on a fixed hash.
+        let hash = vm.calculate_hash(&blob);
+        let started = Instant::now();
+        for nonce_counter in 0..LOOP_ONLY_HASHES {
+            blob[NONCE_OFFSET..MIN_BLOB_LEN].copy_from_slice(&NonceEndian::Le.bytes(std::hint::black_box(nonce_counter)));
+            let hash = std::hint::black_box(&hash);
+            std::hint::black_box(is_share(hash, target::hash_top_word(hash), submit_threshold, &submit_target));
+        }
+        let loop_per_hash = started.elapsed() / LOOP_ONLY_HASHES;
+        std::hint::black_box(&blob);
+
+        println!("per hash: {:?} in all, {:?} outside RandomX, {} allocations", per_hash, loop_per_hash, allocations as f64 / HASHES as f64);
+    }
+}
@@ -0,0 +1,60 @@
+//! SIGTERM ends a running miner cleanly: exit code 0 and the summary printed.
+#![cfg(target_os = "linux")]
+
+use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// Longest the miner may go without printing a line before the test gives up.
+const QUIET_LIMIT: Duration = Duration::from_secs(60);
+
+const SEED: &str = "abababababababababababababababababababababababababababababababab";
+const BLOB: &str = "0e0ed286da8006ecdc1aab3033cf1716c52f13f9d8ae0051615a2453643de94643b550d543becd0000000002abc78b79a1cab2a20d9b9b5bec8e21de3a0ad1b6d90af5bf2ab10ce21a4d7c71b9";
+
+#[test]
+fn sigterm_exits_zero_with_a_summary() {
+    let mut miner = Command::new(env!("CARGO_BIN_EXE_anonminer"))
+        .args(["--fixed-seed", SEED, "--fixed-blob", BLOB, "--light", "--threads", "1"])
+        // No sudo on an empty PATH, so the run never touches huge pages or MSRs.
+        .env("PATH", "")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let (line_tx, lines) = mpsc::channel();
+    let stdout = miner.stdout.take().unwrap();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let _ = line_tx.send(line);
+        }
+    });
+
+    let mut output = Vec::new();
+    while !output.last().is_some_and(|line: &String| line.contains("Offline benchmark")) {
+        output.push(lines.recv_timeout(QUIET_LIMIT).expect("the miner never started"));
+    }
+    // The handler goes in once the workers are started, right after that line.
+    thread::sleep(Duration::from_secs(2));
+    let killed = Command::new("kill").args(["-TERM", &miner.id().to_string()]).status().unwrap();
+    assert!(killed.success());
+
+    loop {
+        match lines.recv_timeout(QUIET_LIMIT) {
+            Ok(line) => output.push(line),
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {
+                let _ = miner.kill();
+                panic!("the miner didn't stop after SIGTERM: {:?}", output);
+            }
+        }
+    }
+    let status = miner.wait().unwrap();
+    assert_eq!(status.code(), Some(0));
+    assert!(output.iter().any(|line| line.contains("Stop requested")), "{:?}", output);
+    assert!(output.iter().any(|line| line.contains("Mined for")), "{:?}", output);
+}